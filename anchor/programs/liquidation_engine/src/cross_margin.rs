@@ -0,0 +1,154 @@
+//! Account-level margin for cross-margin `MarginAccount`s.
+//!
+//! Instructions that need an account's health take the market and oracle of
+//! every open position in `remaining_accounts`, in position order:
+//! `[market_0, oracle_0, market_1, oracle_1, ...]`, covering the positions as
+//! they stand once the instruction has applied its own change. They are
+//! followed by the asset and oracle of every collateral deposit, in deposit
+//! order: `[asset_0, asset_oracle_0, ...]`.
+//!
+//! Cross positions settle funding against their side of the market's stored
+//! indices. Instructions that change a market's open interest accrue its
+//! funding first; otherwise the `update_funding` crank is what advances it.
+
+use anchor_lang::prelude::*;
+
+use crate::{
+    math, oracle, CollateralAsset, CollateralBalance, CrossPosition, LiquidationError, MarginAccount, Market,
+};
+
+/// One position marked at the current oracle price.
+pub struct PositionRisk {
+    pub market: Market,
+    pub notional: u64,
+    /// Unrealized PnL net of funding owed since the last settlement.
+    pub pnl: i128,
+    pub maintenance_requirement: u64,
+    pub initial_requirement: u64,
+}
+
+pub struct AccountHealth {
+    /// Collateral plus the weighted value of the deposits plus every
+    /// position's PnL net of funding.
+    pub equity: i128,
+    /// Deposits valued at their oracle prices less each asset's haircut.
+    pub collateral_value: u64,
+    pub maintenance_requirement: u64,
+    pub initial_requirement: u64,
+    pub positions: Vec<PositionRisk>,
+}
+
+impl AccountHealth {
+    pub fn is_liquidatable(&self) -> bool {
+        self.equity < self.maintenance_requirement as i128
+    }
+
+    /// Liquidation fee rate on `market`'s curve for this account, scaled by
+    /// how far its margin ratio is below its maintenance ratio, both as
+    /// shares of the account's total notional.
+    pub fn liquidation_fee_bps(&self, market: &Market) -> u16 {
+        let notional: u128 = self.positions.iter().map(|risk| risk.notional as u128).sum();
+        if notional == 0 {
+            return market.liquidation_fee_bps;
+        }
+        let margin_ratio_bps = self.equity * math::BPS_DENOMINATOR as i128 / notional as i128;
+        let maintenance_margin_bps =
+            (self.maintenance_requirement as u128 * math::BPS_DENOMINATOR / notional).min(math::BPS_DENOMINATOR);
+        market.liquidation_fee_bps_at(margin_ratio_bps, maintenance_margin_bps as u16)
+    }
+}
+
+pub fn position_risk(position: &CrossPosition, market: Market, price: u64) -> Result<PositionRisk> {
+    let notional = math::notional(position.size, price)?;
+    let funding = market.funding_owed(position.size, position.is_long, position.last_funding_index)?;
+    let pnl = math::pnl(position.size, position.entry_price, price, position.is_long)? - funding;
+    let maintenance_requirement =
+        math::apply_bps(notional, market.maintenance_margin_bps(position.leverage))?;
+    Ok(PositionRisk {
+        market,
+        notional,
+        pnl,
+        maintenance_requirement,
+        initial_requirement: notional / position.leverage as u64,
+    })
+}
+
+/// Marks every position of `account` using the markets and oracles in
+/// `remaining_accounts` and sums the account's equity and requirements.
+pub fn account_health(account: &MarginAccount, remaining_accounts: &[AccountInfo]) -> Result<AccountHealth> {
+    require!(
+        remaining_accounts.len() == (account.positions.len() + account.deposits.len()) * 2,
+        LiquidationError::InvalidRemainingAccounts
+    );
+    let (position_accounts, deposit_accounts) = remaining_accounts.split_at(account.positions.len() * 2);
+
+    let mut collateral_value = 0u64;
+    for (deposit, accounts) in account.deposits.iter().zip(deposit_accounts.chunks(2)) {
+        let asset = load_collateral_asset(&accounts[0], deposit)?;
+        let price = oracle::load_collateral_price(&accounts[1], &asset)?;
+        collateral_value = math::add(collateral_value, asset.weighted_value(deposit.amount, price)?)?;
+    }
+
+    let mut health = AccountHealth {
+        equity: account.collateral as i128 + collateral_value as i128,
+        collateral_value,
+        maintenance_requirement: 0,
+        initial_requirement: 0,
+        positions: Vec::with_capacity(account.positions.len()),
+    };
+    for (position, accounts) in account.positions.iter().zip(position_accounts.chunks(2)) {
+        let market = load_market(&accounts[0], position)?;
+        let price = oracle::load_market_price(&accounts[1], &market)?;
+
+        let risk = position_risk(position, market, price)?;
+        health.equity = health
+            .equity
+            .checked_add(risk.pnl)
+            .ok_or(LiquidationError::MathOverflow)?;
+        health.maintenance_requirement =
+            math::add(health.maintenance_requirement, risk.maintenance_requirement)?;
+        health.initial_requirement = math::add(health.initial_requirement, risk.initial_requirement)?;
+        health.positions.push(risk);
+    }
+    Ok(health)
+}
+
+/// Accrues funding up to `now` on the market of every position of `account`,
+/// at its oracle price, and writes the markets back. The markets must have
+/// been passed writable.
+pub fn accrue_funding(account: &MarginAccount, remaining_accounts: &[AccountInfo], now: i64) -> Result<()> {
+    require!(
+        remaining_accounts.len() >= account.positions.len() * 2,
+        LiquidationError::InvalidRemainingAccounts
+    );
+    for (position, accounts) in account.positions.iter().zip(remaining_accounts.chunks(2)) {
+        let mut market = load_market(&accounts[0], position)?;
+        let price = oracle::load_market_price(&accounts[1], &market)?;
+        market.accrue_funding(price, now)?;
+        store_market(&accounts[0], &market)?;
+    }
+    Ok(())
+}
+
+fn load_market(info: &AccountInfo, position: &CrossPosition) -> Result<Market> {
+    require_keys_eq!(*info.owner, crate::ID, LiquidationError::MarketMismatch);
+    let market = Market::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require!(market.symbol == position.symbol, LiquidationError::MarketMismatch);
+    Ok(market)
+}
+
+fn load_collateral_asset(info: &AccountInfo, deposit: &CollateralBalance) -> Result<CollateralAsset> {
+    require_keys_eq!(*info.owner, crate::ID, LiquidationError::CollateralAssetMismatch);
+    let asset = CollateralAsset::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require_keys_eq!(asset.mint, deposit.mint, LiquidationError::CollateralAssetMismatch);
+    Ok(asset)
+}
+
+/// Writes back a market read through `account_health`, e.g. after its open
+/// interest changed. The account must have been passed writable.
+pub fn store_market(info: &AccountInfo, market: &Market) -> Result<()> {
+    require!(info.is_writable, ErrorCode::AccountNotMutable);
+    let mut data = info.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    market.try_serialize(&mut writer)
+}
@@ -1,4 +1,15 @@
+// `anchor_lang::error::Error` is large by design and every handler and helper
+// returns it; boxing it would diverge from Anchor's `Result`.
+#![allow(clippy::result_large_err)]
+
 use anchor_lang::prelude::*;
+use anchor_lang::{system_program, Discriminator};
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+pub mod cross_margin;
+pub mod math;
+pub mod migration;
+pub mod oracle;
 
 declare_id!("HCJaVaM9GEH4nYoX6UquxWXroXJyjSLp2LLKCa1gg5NJ");
 
@@ -6,33 +17,583 @@ declare_id!("HCJaVaM9GEH4nYoX6UquxWXroXJyjSLp2LLKCa1gg5NJ");
 pub mod liquidation_engine {
     use super::*;
 
+    /// Opens a position at the oracle price. `limit_price` is the worst
+    /// entry the owner accepts: the highest price for a long, the lowest for
+    /// a short. The collateral must cover the market's minimum and the
+    /// initial margin (`notional / leverage`).
     pub fn open_position(
         ctx: Context<OpenPosition>,
         symbol: String,
+        is_long: bool,
         size: u64,
         collateral: u64,
-        entry_price: u64,
+        limit_price: u64,
         leverage: u16,
     ) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        market.check_leverage(leverage)?;
+        require!(collateral >= market.min_collateral, LiquidationError::CollateralBelowMinimum);
+        let price = oracle::load_market_price(&ctx.accounts.oracle, market)?;
+        let entry_price = price;
+        require!(
+            if is_long { price <= limit_price } else { price >= limit_price },
+            LiquidationError::SlippageExceeded
+        );
+        require!(
+            collateral >= math::notional(size, price)? / leverage as u64,
+            LiquidationError::InitialMarginBreached
+        );
+        market.accrue_funding(price, Clock::get()?.unix_timestamp)?;
+        market.admit_position(is_long, size)?;
+
         let position = &mut ctx.accounts.position;
+        position.version = POSITION_VERSION;
         position.owner = ctx.accounts.owner.key();
         position.symbol = symbol;
+        position.is_long = is_long;
         position.size = size;
         position.collateral = collateral;
         position.entry_price = entry_price;
         position.leverage = leverage;
-        // Set initial maintenance margin (e.g., 5% of notional value for demo)
-        position.maintenance_margin = (size * entry_price) / 20; 
+        // Maintenance margin at entry, from the market's tier for this leverage
+        position.maintenance_margin = math::apply_bps(
+            math::notional(size, entry_price)?,
+            market.maintenance_margin_bps(leverage),
+        )?;
+        position.last_funding_index = market.funding_index(is_long);
+
+        emit!(PositionOpened {
+            position: position.key(),
+            owner: position.owner,
+            symbol: position.symbol.clone(),
+            is_long,
+            size,
+            collateral,
+            entry_price,
+            leverage,
+            maintenance_margin: position.maintenance_margin,
+            last_funding_index: position.last_funding_index,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        // Lock collateral in the market vault
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            collateral,
+        )?;
+        Ok(())
+    }
+
+    pub fn add_collateral(ctx: Context<AddCollateral>, amount: u64) -> Result<()> {
+        require!(amount > 0, LiquidationError::InvalidAmount);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let position = &mut ctx.accounts.position;
+        let collateral_before = position.collateral;
+        // Credited first so the deposit can pay any funding owed
+        position.collateral = math::add(position.collateral, amount)?;
+        settle_funding(position, &mut ctx.accounts.market)?;
+        emit_position_modified(position.key(), position, position.size, collateral_before)
+    }
+
+    /// Withdraws collateral as long as the remaining margin still covers the
+    /// initial margin (`notional / leverage`) at the oracle price.
+    pub fn withdraw_collateral(ctx: Context<WithdrawCollateral>, amount: u64) -> Result<()> {
+        require!(amount > 0, LiquidationError::InvalidAmount);
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let position = &mut ctx.accounts.position;
+        let collateral_before = position.collateral;
+        let price = oracle::load_market_price(&ctx.accounts.oracle, &ctx.accounts.market)?;
+        touch_position(position, &mut ctx.accounts.market, price)?;
+
+        require!(amount <= position.collateral, LiquidationError::InitialMarginBreached);
+        position.collateral -= amount;
+
+        let initial_margin = math::notional(position.size, price)? / position.leverage as u64;
+        require!(
+            margin_amount(position, price)? >= initial_margin as i128,
+            LiquidationError::InitialMarginBreached
+        );
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault,
+            &ctx.accounts.owner_token_account,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            amount,
+        )?;
+        emit_position_modified(position.key(), position, position.size, collateral_before)
+    }
+
+    /// Rewrites a position opened by the originally deployed program into
+    /// the current layout, topping up rent from `payer`. That program moved
+    /// no tokens into a vault and had no direction, so nothing backs the
+    /// recorded collateral and the exposure has no side. The position is
+    /// carried over flat, as a long with no size or collateral and its entry
+    /// price and leverage kept for reference; the owner can only close it to
+    /// reclaim the rent. Permissionless, since nothing of value moves.
+    pub fn migrate_position(ctx: Context<MigratePosition>) -> Result<()> {
+        let info = ctx.accounts.position.to_account_info();
+        let legacy = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == Position::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            migration::decode_baseline_position(&data[8..])
+                .ok_or(LiquidationError::PositionAlreadyMigrated)?
+        };
+        // The length alone identified the layout; the address confirms it
+        require_keys_eq!(
+            info.key(),
+            migration::baseline_position_address(&legacy.owner, &legacy.symbol),
+            ErrorCode::ConstraintSeeds
+        );
+        let market = &ctx.accounts.market;
+        require!(market.symbol == legacy.symbol, LiquidationError::MarketMismatch);
+
+        let position = Position {
+            version: POSITION_VERSION,
+            owner: legacy.owner,
+            symbol: legacy.symbol,
+            is_long: true,
+            size: 0,
+            collateral: 0,
+            entry_price: legacy.entry_price,
+            leverage: legacy.leverage,
+            maintenance_margin: 0,
+            last_funding_index: market.funding_index(true),
+        };
+
+        resize_account(&info, 8 + Position::INIT_SPACE, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+
+        let mut data = info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data;
+        position.try_serialize(&mut writer)?;
+        emit_position_modified(info.key(), &position, legacy.size, legacy.collateral)
+    }
+
+    /// Closes `amount` of the position at the oracle price, realizing its PnL
+    /// into collateral.
+    pub fn reduce_position(ctx: Context<ReducePosition>, amount: u64) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        require!(amount > 0 && amount < position.size, LiquidationError::InvalidAmount);
+        let (size_before, collateral_before) = (position.size, position.collateral);
+
+        let price = oracle::load_market_price(&ctx.accounts.oracle, &ctx.accounts.market)?;
+        let market = &mut ctx.accounts.market;
+        touch_position(position, market, price)?;
+        market.remove_open_interest(position.is_long, amount);
+        reduce_size(position, amount, price)?;
+        emit_position_modified(position.key(), position, size_before, collateral_before)
+    }
+
+    /// Realizes all PnL at the oracle price, pays the remaining collateral back
+    /// to the owner and closes the account, returning its rent.
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let position = &mut ctx.accounts.position;
+        let price = oracle::load_market_price(&ctx.accounts.oracle, &ctx.accounts.market)?;
+        let market = &mut ctx.accounts.market;
+        touch_position(position, market, price)?;
+        let closed_size = position.size;
+
+        if position.size > 0 {
+            let size = position.size;
+            market.remove_open_interest(position.is_long, size);
+            reduce_size(position, size, price)?;
+        }
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault,
+            &ctx.accounts.owner_token_account,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            position.collateral,
+        )?;
+
+        emit!(PositionClosed {
+            position: position.key(),
+            owner: position.owner,
+            symbol: position.symbol.clone(),
+            is_long: position.is_long,
+            closed_size,
+            close_price: price,
+            collateral_returned: position.collateral,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        position.collateral = 0;
         Ok(())
     }
 
+    /// Rewrites the insurance fund created by the originally deployed
+    /// program into the current layout and creates its vault for `mint`.
+    /// That program had no vault, so no tokens back the recorded balance:
+    /// the fund restarts empty, keeping its authority and lifetime totals.
+    /// Only the fund's authority may migrate it, as it picks the mint.
+    pub fn migrate_insurance_fund(ctx: Context<MigrateInsuranceFund>) -> Result<()> {
+        let info = ctx.accounts.insurance_fund.to_account_info();
+        let new_len = 8 + InsuranceFund::INIT_SPACE;
+        let legacy = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == InsuranceFund::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            require!(data.len() != new_len, LiquidationError::InsuranceFundAlreadyMigrated);
+            migration::decode_baseline_insurance_fund(&data[8..]).ok_or(ErrorCode::AccountDidNotDeserialize)?
+        };
+        require_keys_eq!(legacy.authority, ctx.accounts.authority.key(), ErrorCode::ConstraintHasOne);
+
+        let insurance_fund = InsuranceFund {
+            authority: legacy.authority,
+            mint: ctx.accounts.mint.key(),
+            balance: 0,
+            total_contributions: legacy.total_contributions,
+            total_bad_debt_covered: legacy.total_bad_debt_covered,
+            utilization_ratio: 0,
+            withdrawal_window_start: 0,
+            withdrawn_in_window: 0,
+            pending_authority: Pubkey::default(),
+            total_shares: 0,
+            protocol_shares: 0,
+        };
+
+        resize_account(&info, new_len, &ctx.accounts.authority, &ctx.accounts.system_program)?;
+
+        let mut data = info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data;
+        insurance_fund.try_serialize(&mut writer)?;
+        emit_insurance_fund_changed(&insurance_fund, legacy.balance)
+    }
+
     pub fn initialize_insurance_fund(ctx: Context<InitializeInsuranceFund>) -> Result<()> {
         let insurance_fund = &mut ctx.accounts.insurance_fund;
         insurance_fund.authority = ctx.accounts.authority.key();
+        insurance_fund.mint = ctx.accounts.mint.key();
         insurance_fund.balance = 0;
         insurance_fund.total_contributions = 0;
         insurance_fund.total_bad_debt_covered = 0;
         insurance_fund.utilization_ratio = 0;
+        insurance_fund.withdrawal_window_start = 0;
+        insurance_fund.withdrawn_in_window = 0;
+        insurance_fund.pending_authority = Pubkey::default();
+        insurance_fund.total_shares = 0;
+        insurance_fund.protocol_shares = 0;
+        Ok(())
+    }
+
+    /// First step of an authority handover; proposing the default key cancels.
+    pub fn propose_insurance_fund_authority(
+        ctx: Context<ProposeInsuranceFundAuthority>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let insurance_fund = &mut ctx.accounts.insurance_fund;
+        insurance_fund.pending_authority = new_authority;
+        emit_authority_proposed(insurance_fund.key(), insurance_fund.authority, new_authority)
+    }
+
+    pub fn accept_insurance_fund_authority(ctx: Context<AcceptInsuranceFundAuthority>) -> Result<()> {
+        let key = ctx.accounts.insurance_fund.key();
+        let new_authority = ctx.accounts.new_authority.key();
+        let insurance_fund: &mut InsuranceFund = &mut ctx.accounts.insurance_fund;
+        let previous_authority =
+            accept_pending_authority(&mut insurance_fund.authority, &mut insurance_fund.pending_authority, new_authority)?;
+        emit_authority_transferred(key, previous_authority, insurance_fund.authority)
+    }
+
+    /// Permissionless top-up of the insurance fund vault.
+    pub fn contribute_to_insurance(ctx: Context<ContributeToInsurance>, amount: u64) -> Result<()> {
+        require!(amount > 0, LiquidationError::InvalidAmount);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.contributor_token_account.to_account_info(),
+                    to: ctx.accounts.insurance_vault.to_account_info(),
+                    authority: ctx.accounts.contributor.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let insurance_fund = &mut ctx.accounts.insurance_fund;
+        let balance_before = insurance_fund.balance;
+        insurance_fund.balance = math::add(insurance_fund.balance, amount)?;
+        insurance_fund.total_contributions = math::add(insurance_fund.total_contributions, amount)?;
+
+        emit!(ContributionEvent {
+            contributor: ctx.accounts.contributor.key(),
+            amount,
+            new_balance: insurance_fund.balance,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        emit_insurance_fund_changed(insurance_fund, balance_before)
+    }
+
+    /// Authority-only withdrawal, capped at `INSURANCE_WITHDRAWAL_LIMIT_BPS` of the
+    /// fund per `INSURANCE_WITHDRAWAL_WINDOW` seconds.
+    pub fn withdraw_from_insurance(ctx: Context<WithdrawFromInsurance>, amount: u64) -> Result<()> {
+        require!(amount > 0, LiquidationError::InvalidAmount);
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let insurance_fund = &mut ctx.accounts.insurance_fund;
+        let now = Clock::get()?.unix_timestamp;
+        let balance_before = insurance_fund.balance;
+        insurance_fund.record_withdrawal(amount, now)?;
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.destination,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            amount,
+        )?;
+
+        emit!(InsuranceWithdrawalEvent {
+            authority: ctx.accounts.authority.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            new_balance: insurance_fund.balance,
+            timestamp: now,
+        });
+        emit_insurance_fund_changed(insurance_fund, balance_before)
+    }
+
+    pub fn create_insurance_stake(ctx: Context<CreateInsuranceStake>) -> Result<()> {
+        let stake = &mut ctx.accounts.stake;
+        stake.owner = ctx.accounts.owner.key();
+        stake.shares = 0;
+        stake.unstake_shares = 0;
+        stake.unstake_requested_at = 0;
+        Ok(())
+    }
+
+    /// Deposits into the insurance fund for shares at the current share
+    /// price. Shares earn the fund's liquidation fees and bear its bad-debt
+    /// losses pro rata.
+    pub fn stake_insurance(ctx: Context<StakeInsurance>, amount: u64) -> Result<()> {
+        require!(amount > 0, LiquidationError::InvalidAmount);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    to: ctx.accounts.insurance_vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let insurance_fund = &mut ctx.accounts.insurance_fund;
+        let balance_before = insurance_fund.balance;
+        insurance_fund.credit_protocol_shares();
+        let shares = insurance_fund.shares_for_deposit(amount)?;
+        require!(shares > 0, LiquidationError::InvalidAmount);
+        insurance_fund.balance = math::add(insurance_fund.balance, amount)?;
+        insurance_fund.total_shares = math::add(insurance_fund.total_shares, shares)?;
+
+        let stake = &mut ctx.accounts.stake;
+        stake.shares = math::add(stake.shares, shares)?;
+
+        emit!(InsuranceStaked {
+            owner: stake.owner,
+            amount,
+            shares,
+            total_shares: insurance_fund.total_shares,
+            new_balance: insurance_fund.balance,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        emit_insurance_fund_changed(insurance_fund, balance_before)
+    }
+
+    /// Starts the cooldown for unstaking `shares`, replacing any pending
+    /// request; zero cancels. Shares stay exposed to losses until unstaked,
+    /// and the request lapses if not redeemed within
+    /// `INSURANCE_UNSTAKE_WINDOW` of the cooldown ending.
+    pub fn request_unstake(ctx: Context<RequestUnstake>, shares: u64) -> Result<()> {
+        let stake = &mut ctx.accounts.stake;
+        require!(shares <= stake.shares, LiquidationError::InsufficientShares);
+        let now = Clock::get()?.unix_timestamp;
+        stake.unstake_shares = shares;
+        stake.unstake_requested_at = now;
+
+        emit!(UnstakeRequested {
+            owner: stake.owner,
+            shares,
+            available_at: now + INSURANCE_UNSTAKE_COOLDOWN,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    /// Redeems the pending unstake request at the current share price once
+    /// `INSURANCE_UNSTAKE_COOLDOWN` has passed, within its redemption window.
+    pub fn unstake_insurance(ctx: Context<UnstakeInsurance>) -> Result<()> {
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let stake = &mut ctx.accounts.stake;
+        let now = Clock::get()?.unix_timestamp;
+        let shares = stake.redeemable_shares(now)?;
+
+        let insurance_fund = &mut ctx.accounts.insurance_fund;
+        let balance_before = insurance_fund.balance;
+        let amount = insurance_fund.value_of_shares(shares)?;
+        insurance_fund.balance = math::sub(insurance_fund.balance, amount)?;
+        insurance_fund.total_shares = math::sub(insurance_fund.total_shares, shares)?;
+        stake.shares = math::sub(stake.shares, shares)?;
+        stake.unstake_shares = 0;
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.owner_token_account,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            amount,
+        )?;
+
+        emit!(InsuranceUnstaked {
+            owner: stake.owner,
+            amount,
+            shares,
+            total_shares: insurance_fund.total_shares,
+            new_balance: insurance_fund.balance,
+            timestamp: now,
+        });
+        emit_insurance_fund_changed(insurance_fund, balance_before)
+    }
+
+    /// Creates the global config; the signer becomes the config authority and
+    /// `guardian` may pause the program in an emergency.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, guardian: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.authority = ctx.accounts.authority.key();
+        config.guardian = guardian;
+        config.opening_paused = false;
+        config.liquidations_paused = false;
+        config.pending_authority = Pubkey::default();
+        Ok(())
+    }
+
+    /// First step of an authority handover; proposing the default key cancels.
+    pub fn propose_config_authority(
+        ctx: Context<ProposeConfigAuthority>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.pending_authority = new_authority;
+        emit_authority_proposed(config.key(), config.authority, new_authority)
+    }
+
+    pub fn accept_config_authority(ctx: Context<AcceptConfigAuthority>) -> Result<()> {
+        let key = ctx.accounts.config.key();
+        let new_authority = ctx.accounts.new_authority.key();
+        let config: &mut Config = &mut ctx.accounts.config;
+        let previous_authority =
+            accept_pending_authority(&mut config.authority, &mut config.pending_authority, new_authority)?;
+        emit_authority_transferred(key, previous_authority, config.authority)
+    }
+
+    pub fn set_guardian(ctx: Context<SetGuardian>, guardian: Pubkey) -> Result<()> {
+        ctx.accounts.config.guardian = guardian;
+        Ok(())
+    }
+
+    /// Halts the selected operations. Flags left `false` are not touched.
+    pub fn emergency_pause(
+        ctx: Context<SetPauseState>,
+        pause_opening: bool,
+        pause_liquidations: bool,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.opening_paused |= pause_opening;
+        config.liquidations_paused |= pause_liquidations;
+        emit_pause_state(config, ctx.accounts.guardian.key())
+    }
+
+    /// Resumes the selected operations. Flags left `false` are not touched.
+    pub fn unpause(
+        ctx: Context<SetPauseState>,
+        resume_opening: bool,
+        resume_liquidations: bool,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.opening_paused &= !resume_opening;
+        config.liquidations_paused &= !resume_liquidations;
+        emit_pause_state(config, ctx.accounts.guardian.key())
+    }
+
+    /// Lists a market for `symbol` with its risk parameters.
+    pub fn initialize_market(
+        ctx: Context<InitializeMarket>,
+        symbol: String,
+        params: MarketParams,
+    ) -> Result<()> {
+        require!(symbol.len() <= MAX_SYMBOL_LEN, LiquidationError::InvalidMarketParams);
+        params.validate()?;
+        let market = &mut ctx.accounts.market;
+        market.symbol = symbol;
+        market.apply(&params);
+        market.last_funding_update = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    pub fn update_market(ctx: Context<UpdateMarket>, params: MarketParams) -> Result<()> {
+        params.validate()?;
+        ctx.accounts.market.apply(&params);
+        Ok(())
+    }
+
+    /// Permissionless crank advancing a market's funding indices to
+    /// the current time.
+    pub fn update_funding(ctx: Context<UpdateFunding>) -> Result<()> {
+        let price = oracle::load_market_price(&ctx.accounts.oracle, &ctx.accounts.market)?;
+        let now = Clock::get()?.unix_timestamp;
+        let market = &mut ctx.accounts.market;
+        market.accrue_funding(price, now)?;
+
+        emit!(FundingUpdated {
+            symbol: market.symbol.clone(),
+            long_funding_index: market.long_funding_index,
+            short_funding_index: market.short_funding_index,
+            open_interest_long: market.open_interest_long,
+            open_interest_short: market.open_interest_short,
+            price,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    /// Creates the token vault holding collateral for positions in `symbol`.
+    pub fn initialize_vault(_ctx: Context<InitializeVault>, _symbol: String) -> Result<()> {
+        Ok(())
+    }
+
+    /// Creates the token vault holding every margin account's collateral.
+    pub fn initialize_margin_vault(_ctx: Context<InitializeMarginVault>) -> Result<()> {
         Ok(())
     }
 
@@ -44,33 +605,51 @@ pub mod liquidation_engine {
         let liquidator = &mut ctx.accounts.liquidator;
         let insurance_fund = &mut ctx.accounts.insurance_fund;
         
-        // 1. Verify position is liquidatable
-        // In a real app, we would fetch price from an Oracle account passed in ctx
-        // For this demo, we assume the caller provides the correct state or we check an oracle here.
-        // Let's assume we have an oracle account.
-        
-        // Placeholder for oracle price check
-        // let price = get_price(&ctx.accounts.oracle)?;
-        // let margin_ratio = calculate_margin_ratio(position, price);
-        // require!(margin_ratio < position.maintenance_margin, LiquidationError::NotLiquidatable);
+        // 1. Verify position is liquidatable at the oracle price
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let market = &mut ctx.accounts.market;
+        let (size_before, collateral_before) = (position.size, position.collateral);
+        let price = oracle::load_market_price(&ctx.accounts.oracle, market)?;
+        touch_position(position, market, price)?;
+        require!(is_liquidatable(position, market, price)?, LiquidationError::NotLiquidatable);
+        let margin_before = margin_amount(position, price)?;
+        let fee_bps = liquidation_fee_bps(position, market, price)?;
 
-        // 2. Reduce position size
-        require!(liquidation_amount <= position.size, LiquidationError::InvalidLiquidationAmount);
-        
-        // Logic to reduce position size
-        position.size = position.size.checked_sub(liquidation_amount).unwrap();
+        // 2. Reduce position size, within the market's close factor and
+        // target margin
+        check_partial_liquidation(position, market, price, liquidation_amount, fee_bps)?;
+        market.remove_open_interest(position.is_long, liquidation_amount);
+
+        // Realize the PnL of the liquidated slice into collateral
+        reduce_size(position, liquidation_amount, price)?;
 
         // 3. Calculate and distribute liquidation fee
-        // Reward = 2.5% of liquidated value
-        // For simplicity, let's say 1 unit of size = 1 USDC for now, or we use the price.
-        // let reward = (liquidation_amount * price * 0.025)
-        let reward = liquidation_amount / 40; // 2.5% approximation
-        
-        // Transfer reward from position collateral to liquidator
-        // This usually involves CPI to a token program, but we'll update internal state for this demo
-        position.collateral = position.collateral.checked_sub(reward).unwrap();
+        // Penalty = health-scaled liquidation fee on the liquidated value at the
+        // oracle price, split between the liquidator and the insurance fund
+        let penalty = math::apply_bps(math::notional(liquidation_amount, price)?, fee_bps)?;
+        let (reward, insurance_fee) = market.split_liquidation_fee(penalty)?;
         
-        // Update liquidator stats (if we were tracking them on-chain, or just emit event)
+        // Charge the penalty to position collateral
+        require!(penalty <= position.collateral, LiquidationError::PositionUnderwater);
+        position.collateral -= penalty;
+        let margin_after = margin_amount(position, price)?;
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault,
+            &ctx.accounts.liquidator_token_account,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            reward,
+        )?;
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            insurance_fee,
+        )?;
+        collect_insurance_fee(insurance_fund, insurance_fee)?;
         
         // 4. Emit event
         emit!(LiquidationEvent {
@@ -78,152 +657,4037 @@ pub mod liquidation_engine {
             liquidator: liquidator.key(),
             symbol: position.symbol.clone(),
             liquidated_size: liquidation_amount,
-            liquidation_price: 100, // Placeholder
-            margin_before: 1000, // Placeholder
-            margin_after: 1200, // Placeholder
+            liquidation_price: price,
+            margin_before: math::to_i64(margin_before)?,
+            margin_after: math::to_i64(margin_after)?,
             liquidator_reward: reward,
+            insurance_fee,
             bad_debt: 0,
             timestamp: Clock::get()?.unix_timestamp,
             is_full: false,
         });
-
-        Ok(())
+        emit_position_modified(position.key(), position, size_before, collateral_before)
     }
 
+    /// Closes the whole position. Only for positions a partial liquidation
+    /// cannot fix: at or below the minimum size, bankrupt, or too far below
+    /// the target margin for one close-factor-capped partial.
     pub fn liquidate_full(ctx: Context<LiquidateFull>) -> Result<()> {
         let position = &mut ctx.accounts.position;
         let insurance_fund = &mut ctx.accounts.insurance_fund;
         let liquidator = &mut ctx.accounts.liquidator;
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
 
-        // 1. Close entire position
+        let market = &mut ctx.accounts.market;
+        let collateral_before = position.collateral;
+        let price = oracle::load_market_price(&ctx.accounts.oracle, market)?;
+        touch_position(position, market, price)?;
+        require!(is_liquidatable(position, market, price)?, LiquidationError::NotLiquidatable);
+        let fee_bps = liquidation_fee_bps(position, market, price)?;
+        require!(
+            full_liquidation_allowed(position, market, price, fee_bps)?,
+            LiquidationError::PartialLiquidationRequired
+        );
+
+        // 1. Calculate remaining margin at the oracle price
         let size = position.size;
-        position.size = 0;
+        market.remove_open_interest(position.is_long, size);
+        let margin_before = margin_amount(position, price)?;
 
-        // 2. Calculate remaining margin
-        // let pnl = calculate_pnl(position, price);
-        // let remaining_margin = position.collateral + pnl;
-        
-        let remaining_margin: i64 = 100; // Placeholder positive margin
-        
-        if remaining_margin >= 0 {
-            // Pay liquidator fee
-            let reward = size / 40; // 2.5%
-            // Transfer reward...
-        } else {
-            // Bad debt
-            let bad_debt = remaining_margin.abs() as u64;
-            insurance_fund.balance = insurance_fund.balance.checked_sub(bad_debt).unwrap_or(0);
-            insurance_fund.total_bad_debt_covered += bad_debt;
-        }
+        // 2. Penalty = health-scaled liquidation fee on the liquidated value, split
+        // between liquidator and insurance fund and paid out of collateral first
+        let penalty = math::apply_bps(math::notional(size, price)?, fee_bps)?;
+        let (reward, insurance_fee) = market.split_liquidation_fee(penalty)?;
+
+        // 3. Split the margin between liquidator, insurance fund and bad debt
+        let balance_before = insurance_fund.balance;
+        let settlement = settle_full_liquidation(margin_before, reward, insurance_fee, insurance_fund)?;
+        emit_insurance_fund_changed(insurance_fund, balance_before)?;
+        let liquidator_reward = settlement.liquidator_reward()?;
+        let bad_debt = settlement.bad_debt;
+        market.record_bad_debt(settlement.vault_shortfall, bankruptcy_price(position)?, position.is_long)?;
+
+        // 4. Settle tokens: the market vault pays what the position covers,
+        // the insurance vault pays the unpaid reward and refills negative equity
+        let token_program = &ctx.accounts.token_program;
+        let vault = &ctx.accounts.vault;
+        let insurance_vault = &ctx.accounts.insurance_vault;
+        let vault_authority = &ctx.accounts.vault_authority;
+        let liquidator_token_account = &ctx.accounts.liquidator_token_account;
+
+        transfer_from_vault(token_program, vault, liquidator_token_account, vault_authority, vault_authority_bump, settlement.reward_from_collateral)?;
+        transfer_from_vault(token_program, vault, insurance_vault, vault_authority, vault_authority_bump, settlement.insurance_fee)?;
+        transfer_from_vault(token_program, insurance_vault, liquidator_token_account, vault_authority, vault_authority_bump, settlement.fund_to_liquidator)?;
+        transfer_from_vault(token_program, insurance_vault, vault, vault_authority, vault_authority_bump, settlement.fund_to_vault)?;
+
+        // 5. Close entire position; the surplus stays as collateral for the
+        // owner to withdraw with `close_position`
+        position.size = 0;
+        position.collateral = settlement.surplus;
+        position.maintenance_margin = 0;
 
         emit!(LiquidationEvent {
             position_owner: position.owner,
             liquidator: liquidator.key(),
             symbol: position.symbol.clone(),
             liquidated_size: size,
-            liquidation_price: 100, // Placeholder
-            margin_before: 500, // Placeholder
-            margin_after: 0,
-            liquidator_reward: 10, // Placeholder
-            bad_debt: 0, // Placeholder
+            liquidation_price: price,
+            margin_before: math::to_i64(margin_before)?,
+            margin_after: math::to_i64(settlement.surplus as i128)?,
+            liquidator_reward,
+            insurance_fee: settlement.insurance_fee,
+            bad_debt,
             timestamp: Clock::get()?.unix_timestamp,
             is_full: true,
         });
+        emit_position_modified(position.key(), position, size, collateral_before)
+    }
 
-        Ok(())
+    /// Reports a position's health at the oracle price, with funding accrued
+    /// up to now. Nothing is written; the result is returned as return data
+    /// so clients can simulate this instead of re-implementing margin math.
+    pub fn get_position_health(ctx: Context<GetPositionHealth>) -> Result<PositionHealth> {
+        let mut market = (*ctx.accounts.market).clone();
+        let mut position = (*ctx.accounts.position).clone();
+        let price = oracle::load_market_price(&ctx.accounts.oracle, &market)?;
+        touch_position(&mut position, &mut market, price)?;
+
+        let notional = math::notional(position.size, price)?;
+        let maintenance_margin_bps = market.maintenance_margin_bps(position.leverage);
+        let margin = margin_amount(&position, price)?;
+        let margin_ratio_bps = if notional == 0 {
+            i64::MAX
+        } else {
+            math::to_i64(margin * math::BPS_DENOMINATOR as i128 / notional as i128)?
+        };
+
+        Ok(PositionHealth {
+            price,
+            collateral: position.collateral,
+            unrealized_pnl: math::to_i64(unrealized_pnl(&position, price)?)?,
+            margin: math::to_i64(margin)?,
+            maintenance_requirement: math::apply_bps(notional, maintenance_margin_bps)?,
+            margin_ratio_bps,
+            maintenance_margin_bps,
+            liquidation_price: if position.size == 0 {
+                0
+            } else {
+                price_at_margin(&position, maintenance_margin_bps)?
+            },
+            is_liquidatable: is_liquidatable(&position, &market, price)?,
+        })
     }
-}
 
-#[derive(Accounts)]
-#[instruction(symbol: String)]
-pub struct OpenPosition<'info> {
-    #[account(
-        init, 
-        payer = owner, 
-        space = 8 + 32 + 4 + symbol.len() + 8 + 8 + 8 + 2 + 8,
-        seeds = [b"position", owner.key().as_ref(), symbol.as_bytes()], 
-        bump
-    )]
-    pub position: Account<'info, Position>,
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Opens a Dutch auction for a liquidatable position on an auction-mode
+    /// market. The discount schedule is snapshotted from the market.
+    pub fn start_auction(ctx: Context<StartAuction>) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        let market = &mut ctx.accounts.market;
+        let collateral_before = position.collateral;
+        let price = oracle::load_market_price(&ctx.accounts.oracle, market)?;
+        touch_position(position, market, price)?;
+        require!(is_liquidatable(position, market, price)?, LiquidationError::NotLiquidatable);
 
-#[derive(Accounts)]
-pub struct InitializeInsuranceFund<'info> {
-    #[account(
-        init, 
-        payer = authority, 
-        space = 8 + 32 + 8 + 8 + 8 + 8,
-        seeds = [b"insurance_fund"], 
-        bump
-    )]
-    pub insurance_fund: Account<'info, InsuranceFund>,
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+        let auction = &mut ctx.accounts.auction;
+        auction.position = position.key();
+        auction.market = market.key();
+        auction.keeper = ctx.accounts.keeper.key();
+        auction.start_slot = Clock::get()?.slot;
+        auction.start_discount_bps = market.auction_start_discount_bps;
+        auction.max_discount_bps = market.auction_max_discount_bps;
+        auction.duration_slots = market.auction_duration_slots;
 
-#[derive(Accounts)]
-pub struct LiquidatePartial<'info> {
-    #[account(mut)]
-    pub position: Account<'info, Position>,
-    #[account(mut)]
-    pub insurance_fund: Account<'info, InsuranceFund>,
-    #[account(mut)]
-    pub liquidator: Signer<'info>,
-}
+        emit!(AuctionStarted {
+            position: auction.position,
+            keeper: auction.keeper,
+            symbol: position.symbol.clone(),
+            start_slot: auction.start_slot,
+            start_discount_bps: auction.start_discount_bps,
+            max_discount_bps: auction.max_discount_bps,
+            duration_slots: auction.duration_slots,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        // Settling funding may have moved collateral
+        emit_position_modified(position.key(), position, position.size, collateral_before)
+    }
 
-#[derive(Accounts)]
-pub struct LiquidateFull<'info> {
-    #[account(mut)]
-    pub position: Account<'info, Position>,
-    #[account(mut)]
-    pub insurance_fund: Account<'info, InsuranceFund>,
-    #[account(mut)]
-    pub liquidator: Signer<'info>,
-}
+    /// Liquidates `amount` of an auctioned position, paying the liquidator the
+    /// auction's current discount on the liquidated value. Taking the whole
+    /// remaining size settles like `liquidate_full` and closes the auction.
+    pub fn take_auction(ctx: Context<TakeAuction>, amount: u64) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        let insurance_fund = &mut ctx.accounts.insurance_fund;
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
 
-#[account]
-pub struct Position {
-    pub owner: Pubkey,
-    pub symbol: String,
-    pub size: u64,
-    pub collateral: u64,
-    pub entry_price: u64,
-    pub leverage: u16,
-    pub maintenance_margin: u64,
-}
+        let market = &mut ctx.accounts.market;
+        let (size_before, collateral_before) = (position.size, position.collateral);
+        let price = oracle::load_market_price(&ctx.accounts.oracle, market)?;
+        touch_position(position, market, price)?;
+        require!(is_liquidatable(position, market, price)?, LiquidationError::NotLiquidatable);
+        require!(
+            amount > 0 && amount <= position.size,
+            LiquidationError::InvalidLiquidationAmount
+        );
 
-#[account]
-pub struct InsuranceFund {
-    pub authority: Pubkey,
-    pub balance: u64,
-    pub total_contributions: u64,
-    pub total_bad_debt_covered: u64,
-    pub utilization_ratio: u64, // basis points
-}
+        let discount_bps = ctx.accounts.auction.discount_bps(Clock::get()?.slot);
+        let reward = math::apply_bps(math::notional(amount, price)?, discount_bps)?;
+        let margin_before = margin_amount(position, price)?;
+        let is_full = amount == position.size;
+        if !is_full {
+            check_partial_liquidation(position, market, price, amount, discount_bps)?;
+        }
+        market.remove_open_interest(position.is_long, amount);
 
-#[event]
-pub struct LiquidationEvent {
-    pub position_owner: Pubkey,
-    pub liquidator: Pubkey,
-    pub symbol: String,
-    pub liquidated_size: u64,
-    pub liquidation_price: u64,
-    pub margin_before: u64,
-    pub margin_after: u64,
-    pub liquidator_reward: u64,
-    pub bad_debt: u64,
-    pub timestamp: i64,
-    pub is_full: bool,
-}
+        let token_program = &ctx.accounts.token_program;
+        let vault = &ctx.accounts.vault;
+        let insurance_vault = &ctx.accounts.insurance_vault;
+        let vault_authority = &ctx.accounts.vault_authority;
+        let liquidator_token_account = &ctx.accounts.liquidator_token_account;
 
-#[error_code]
-pub enum LiquidationError {
-    #[msg("Position is not liquidatable")]
-    NotLiquidatable,
-    #[msg("Invalid liquidation amount")]
-    InvalidLiquidationAmount,
+        let (liquidator_reward, bad_debt, margin_after) = if is_full {
+            let balance_before = insurance_fund.balance;
+            let settlement = settle_full_liquidation(margin_before, reward, 0, insurance_fund)?;
+            emit_insurance_fund_changed(insurance_fund, balance_before)?;
+            market.record_bad_debt(settlement.vault_shortfall, bankruptcy_price(position)?, position.is_long)?;
+            transfer_from_vault(token_program, vault, liquidator_token_account, vault_authority, vault_authority_bump, settlement.reward_from_collateral)?;
+            transfer_from_vault(token_program, insurance_vault, liquidator_token_account, vault_authority, vault_authority_bump, settlement.fund_to_liquidator)?;
+            transfer_from_vault(token_program, insurance_vault, vault, vault_authority, vault_authority_bump, settlement.fund_to_vault)?;
+
+            position.size = 0;
+            position.collateral = settlement.surplus;
+            position.maintenance_margin = 0;
+            (settlement.liquidator_reward()?, settlement.bad_debt, settlement.surplus as i128)
+        } else {
+            reduce_size(position, amount, price)?;
+            require!(reward <= position.collateral, LiquidationError::PositionUnderwater);
+            position.collateral -= reward;
+            transfer_from_vault(token_program, vault, liquidator_token_account, vault_authority, vault_authority_bump, reward)?;
+            (reward, 0, margin_amount(position, price)?)
+        };
+
+        emit!(LiquidationEvent {
+            position_owner: position.owner,
+            liquidator: ctx.accounts.liquidator.key(),
+            symbol: position.symbol.clone(),
+            liquidated_size: amount,
+            liquidation_price: price,
+            margin_before: math::to_i64(margin_before)?,
+            margin_after: math::to_i64(margin_after)?,
+            liquidator_reward,
+            insurance_fee: 0,
+            bad_debt,
+            timestamp: Clock::get()?.unix_timestamp,
+            is_full,
+        });
+        emit_position_modified(position.key(), position, size_before, collateral_before)?;
+
+        if is_full {
+            ctx.accounts.auction.close(ctx.accounts.keeper.to_account_info())?;
+        }
+        Ok(())
+    }
+
+    /// Closes an auction that can no longer run: the position was closed,
+    /// recovered above maintenance margin, or the market left auction mode.
+    /// Rent goes back to the keeper that started it.
+    pub fn cancel_auction(ctx: Context<CancelAuction>) -> Result<()> {
+        let market = &ctx.accounts.market;
+        let info = ctx.accounts.position.to_account_info();
+        if market.liquidation_mode == LiquidationMode::Auction
+            && info.owner == &crate::ID
+            && !info.data_is_empty()
+        {
+            // Funding is settled on this copy only; the position is not written back
+            let mut position = Account::<Position>::try_from(&info)?;
+            apply_funding(&mut position, market)?;
+            let price = oracle::load_market_price(&ctx.accounts.oracle, market)?;
+            require!(
+                !is_liquidatable(&position, market, price)?,
+                LiquidationError::AuctionStillActive
+            );
+        }
+
+        emit!(AuctionCancelled {
+            position: ctx.accounts.auction.position,
+            keeper: ctx.accounts.auction.keeper,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Recovers a market's uncovered bad debt from a profitable position on
+    /// the other side of the bankruptcy. `amount` of the position is closed
+    /// at the bankruptcy price rather than the oracle price, and the profit
+    /// given up pays down the debt; once the debt is gone the rest of the
+    /// slice realizes at the oracle price. Restricted to the config authority,
+    /// whose keeper picks targets by ADL rank (profit times leverage).
+    pub fn auto_deleverage(ctx: Context<AutoDeleverage>, amount: u64) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        let market = &mut ctx.accounts.market;
+        // Debt from unpaid funding alone has no bankruptcy price to close at
+        require!(
+            market.uncovered_bad_debt > 0 && market.bankruptcy_price > 0,
+            LiquidationError::NoUncoveredBadDebt
+        );
+        require!(
+            position.is_long != market.bankrupt_is_long,
+            LiquidationError::NotAdlCounterparty
+        );
+        require!(amount > 0 && amount <= position.size, LiquidationError::InvalidAmount);
+        let (size_before, collateral_before) = (position.size, position.collateral);
+
+        let price = oracle::load_market_price(&ctx.accounts.oracle, market)?;
+        touch_position(position, market, price)?;
+        require!(unrealized_pnl(position, price)? > 0, LiquidationError::NotAdlCounterparty);
+
+        // Profit the slice gives up by closing at the bankruptcy price
+        let forgone = math::pnl(amount, market.bankruptcy_price, price, position.is_long)?;
+        let recovered = math::to_u64(forgone.max(0))?.min(market.uncovered_bad_debt);
+        require!(recovered > 0, LiquidationError::NoUncoveredBadDebt);
+
+        market.remove_open_interest(position.is_long, amount);
+        reduce_size(position, amount, price)?;
+        require!(recovered <= position.collateral, LiquidationError::PositionUnderwater);
+        position.collateral -= recovered;
+        market.uncovered_bad_debt -= recovered;
+
+        emit!(AutoDeleveraged {
+            position: position.key(),
+            owner: position.owner,
+            symbol: position.symbol.clone(),
+            size_reduced: amount,
+            price,
+            bankruptcy_price: market.bankruptcy_price,
+            recovered,
+            remaining_bad_debt: market.uncovered_bad_debt,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        emit_position_modified(position.key(), position, size_before, collateral_before)
+    }
+
+    pub fn create_margin_account(ctx: Context<CreateMarginAccount>) -> Result<()> {
+        let margin_account = &mut ctx.accounts.margin_account;
+        margin_account.owner = ctx.accounts.owner.key();
+        margin_account.collateral = 0;
+        margin_account.positions = Vec::new();
+        margin_account.deposits = Vec::new();
+        Ok(())
+    }
+
+    pub fn deposit_margin(ctx: Context<DepositMargin>, amount: u64) -> Result<()> {
+        require!(amount > 0, LiquidationError::InvalidAmount);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    to: ctx.accounts.margin_vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let margin_account = &mut ctx.accounts.margin_account;
+        margin_account.collateral = math::add(margin_account.collateral, amount)?;
+
+        emit!(MarginDeposited {
+            margin_account: margin_account.key(),
+            owner: margin_account.owner,
+            mint: ctx.accounts.margin_vault.mint,
+            amount,
+            balance: margin_account.collateral,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Withdraws shared collateral as long as the account still covers the
+    /// initial margin of every position. `remaining_accounts` as described in
+    /// `cross_margin`.
+    pub fn withdraw_margin(ctx: Context<WithdrawMargin>, amount: u64) -> Result<()> {
+        require!(amount > 0, LiquidationError::InvalidAmount);
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let margin_account = &mut ctx.accounts.margin_account;
+
+        require!(amount <= margin_account.collateral, LiquidationError::InitialMarginBreached);
+        margin_account.collateral -= amount;
+
+        let health = cross_margin::account_health(margin_account, ctx.remaining_accounts)?;
+        require!(
+            health.equity >= health.initial_requirement as i128,
+            LiquidationError::InitialMarginBreached
+        );
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.margin_vault,
+            &ctx.accounts.owner_token_account,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            amount,
+        )?;
+
+        emit!(MarginWithdrawn {
+            margin_account: margin_account.key(),
+            owner: margin_account.owner,
+            mint: ctx.accounts.margin_vault.mint,
+            amount,
+            balance: margin_account.collateral,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Opens a position on `symbol` at the oracle price, margined by the
+    /// account's shared collateral. `remaining_accounts` as described in
+    /// `cross_margin`, including the new position last.
+    pub fn open_cross_position(
+        ctx: Context<OpenCrossPosition>,
+        symbol: String,
+        is_long: bool,
+        size: u64,
+        leverage: u16,
+    ) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        market.check_leverage(leverage)?;
+
+        let margin_account = &mut ctx.accounts.margin_account;
+        require!(
+            margin_account.positions.len() < MAX_CROSS_POSITIONS,
+            LiquidationError::TooManyCrossPositions
+        );
+        require!(
+            margin_account.positions.iter().all(|position| position.symbol != symbol),
+            LiquidationError::CrossPositionExists
+        );
+
+        let price = oracle::load_market_price(&ctx.accounts.oracle, market)?;
+        market.accrue_funding(price, Clock::get()?.unix_timestamp)?;
+        market.admit_position(is_long, size)?;
+        // The health check reads this market from `remaining_accounts`
+        cross_margin::store_market(&market.to_account_info(), market)?;
+        margin_account.positions.push(CrossPosition {
+            symbol: symbol.clone(),
+            is_long,
+            size,
+            entry_price: price,
+            leverage,
+            last_funding_index: market.funding_index(is_long),
+        });
+
+        let health = cross_margin::account_health(margin_account, ctx.remaining_accounts)?;
+        require!(
+            health.equity >= health.initial_requirement as i128,
+            LiquidationError::InitialMarginBreached
+        );
+
+        emit!(CrossPositionOpened {
+            margin_account: margin_account.key(),
+            owner: margin_account.owner,
+            symbol,
+            is_long,
+            size,
+            entry_price: price,
+            leverage,
+            last_funding_index: market.funding_index(is_long),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Closes the account's position on `symbol` at the oracle price,
+    /// realizing its PnL and funding into the shared collateral. The PnL is
+    /// paid out of or into the market's vault, like an isolated position's.
+    pub fn close_cross_position(ctx: Context<CloseCrossPosition>, symbol: String) -> Result<()> {
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let margin_account = &mut ctx.accounts.margin_account;
+        let index = margin_account
+            .positions
+            .iter()
+            .position(|position| position.symbol == symbol)
+            .ok_or(LiquidationError::CrossPositionNotFound)?;
+
+        let price = oracle::load_market_price(&ctx.accounts.oracle, &ctx.accounts.market)?;
+        let market = &mut ctx.accounts.market;
+        market.accrue_funding(price, Clock::get()?.unix_timestamp)?;
+        let position = margin_account.positions.remove(index);
+        let risk = cross_margin::position_risk(&position, (**market).clone(), price)?;
+
+        // A loss the collateral cannot absorb has to go through liquidation
+        let collateral = margin_account.collateral as i128 + risk.pnl;
+        require!(collateral >= 0, LiquidationError::PositionUnderwater);
+        margin_account.collateral = math::to_u64(collateral)?;
+        market.remove_open_interest(position.is_long, position.size);
+
+        emit!(CrossPositionClosed {
+            margin_account: margin_account.key(),
+            owner: margin_account.owner,
+            symbol,
+            is_long: position.is_long,
+            closed_size: position.size,
+            close_price: price,
+            realized_pnl: math::to_i64(risk.pnl)?,
+            collateral: margin_account.collateral,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        settle_cross_pnl(
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault,
+            &ctx.accounts.margin_vault,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            risk.pnl,
+        )
+    }
+
+    /// Liquidates a margin account whose equity is below the sum of its
+    /// positions' maintenance requirements. Closes the position with the
+    /// largest requirement at the oracle price for the market's liquidation
+    /// fee. When that is the last position, or the collateral cannot absorb
+    /// its loss and fee, every position is closed instead and any shortfall
+    /// is covered by the insurance fund; equity left after the fee stays in
+    /// the account. Closed positions' PnL settles against their markets'
+    /// vaults. `remaining_accounts` as described in `cross_margin`, with the
+    /// markets writable, followed by every position's market vault in
+    /// position order.
+    pub fn liquidate_margin_account<'info>(
+        ctx: Context<'_, '_, '_, 'info, LiquidateMarginAccount<'info>>,
+    ) -> Result<()> {
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let margin_account = &mut ctx.accounts.margin_account;
+        let insurance_fund = &mut ctx.accounts.insurance_fund;
+
+        let health_len = ctx.remaining_accounts.len().saturating_sub(margin_account.positions.len());
+        let (health_accounts, market_vaults) = ctx.remaining_accounts.split_at(health_len);
+        cross_margin::accrue_funding(margin_account, health_accounts, Clock::get()?.unix_timestamp)?;
+        let mut health = cross_margin::account_health(margin_account, health_accounts)?;
+        require!(health.is_liquidatable(), LiquidationError::NotLiquidatable);
+
+        // 1. Pick the riskiest position, escalating to the whole account when
+        // closing it alone would leave the collateral negative
+        let target = health
+            .positions
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, risk)| risk.maintenance_requirement)
+            .map(|(index, _)| index)
+            .ok_or(LiquidationError::NotLiquidatable)?;
+        // Fees scale with the whole account's shortfall, on each market's curve
+        let fee_bps: Vec<u16> = health.positions.iter().map(|risk| health.liquidation_fee_bps(&risk.market)).collect();
+        let target_risk = &health.positions[target];
+        let target_reward = math::apply_bps(target_risk.notional, fee_bps[target])?;
+        let collateral_after =
+            margin_account.collateral as i128 + target_risk.pnl - target_reward as i128;
+        let is_full = margin_account.positions.len() == 1 || collateral_after < 0;
+        let closing: Vec<usize> = if is_full {
+            (0..margin_account.positions.len()).collect()
+        } else {
+            vec![target]
+        };
+
+        let token_program = &ctx.accounts.token_program;
+        let margin_vault = &ctx.accounts.margin_vault;
+        let insurance_vault = &ctx.accounts.insurance_vault;
+        let vault_authority = &ctx.accounts.vault_authority;
+        let liquidator_token_account = &ctx.accounts.liquidator_token_account;
+
+        // 2. Take the closed positions out of their markets' open interest
+        // and realize their PnL against the market vaults
+        let mut symbols = Vec::with_capacity(closing.len());
+        let mut liquidated_notional = 0u64;
+        let mut reward = 0u64;
+        let mut insurance_fee = 0u64;
+        for &index in &closing {
+            let position = &margin_account.positions[index];
+            let risk = &mut health.positions[index];
+            let (position_reward, position_fee) =
+                risk.market.split_liquidation_fee(math::apply_bps(risk.notional, fee_bps[index])?)?;
+            reward = math::add(reward, position_reward)?;
+            insurance_fee = math::add(insurance_fee, position_fee)?;
+            liquidated_notional = math::add(liquidated_notional, risk.notional)?;
+            risk.market.remove_open_interest(position.is_long, position.size);
+            cross_margin::store_market(&health_accounts[2 * index], &risk.market)?;
+            let market_vault = load_market_vault(&market_vaults[index], &position.symbol)?;
+            settle_cross_pnl(token_program, &market_vault, margin_vault, vault_authority, vault_authority_bump, risk.pnl)?;
+            symbols.push(position.symbol.clone());
+        }
+
+        // 3. Settle the reward, fee and any bad debt
+
+        let (liquidator_reward, insurance_fee, bad_debt, equity_after) = if is_full {
+            // Deposits stay with the owner, so only the quote collateral
+            // settles; any bad debt must wait until they are liquidated
+            let quote_equity = health.equity - health.collateral_value as i128;
+            require!(
+                margin_account.deposits.is_empty() || quote_equity >= reward as i128,
+                LiquidationError::CollateralNotSeized
+            );
+            let balance_before = insurance_fund.balance;
+            let settlement = settle_full_liquidation(quote_equity, reward, insurance_fee, insurance_fund)?;
+            emit_insurance_fund_changed(insurance_fund, balance_before)?;
+            transfer_from_vault(token_program, margin_vault, liquidator_token_account, vault_authority, vault_authority_bump, settlement.reward_from_collateral)?;
+            transfer_from_vault(token_program, margin_vault, insurance_vault, vault_authority, vault_authority_bump, settlement.insurance_fee)?;
+            transfer_from_vault(token_program, insurance_vault, liquidator_token_account, vault_authority, vault_authority_bump, settlement.fund_to_liquidator)?;
+            transfer_from_vault(token_program, insurance_vault, margin_vault, vault_authority, vault_authority_bump, settlement.fund_to_vault)?;
+
+            margin_account.collateral = settlement.surplus;
+            margin_account.positions.clear();
+            let equity_after = settlement.surplus as i128 + health.collateral_value as i128;
+            (settlement.liquidator_reward()?, settlement.insurance_fee, settlement.bad_debt, equity_after)
+        } else {
+            margin_account.collateral = math::to_u64(collateral_after)?;
+            margin_account.positions.remove(target);
+            transfer_from_vault(token_program, margin_vault, liquidator_token_account, vault_authority, vault_authority_bump, reward)?;
+            transfer_from_vault(token_program, margin_vault, insurance_vault, vault_authority, vault_authority_bump, insurance_fee)?;
+            collect_insurance_fee(insurance_fund, insurance_fee)?;
+            // Realizing PnL into collateral leaves equity unchanged; only the fee leaves
+            (reward, insurance_fee, 0, health.equity - target_reward as i128)
+        };
+
+        emit!(MarginAccountLiquidated {
+            owner: margin_account.owner,
+            liquidator: ctx.accounts.liquidator.key(),
+            symbols,
+            liquidated_notional,
+            equity_before: math::to_i64(health.equity)?,
+            maintenance_requirement: health.maintenance_requirement,
+            equity_after: math::to_i64(equity_after)?,
+            liquidator_reward,
+            insurance_fee,
+            bad_debt,
+            timestamp: Clock::get()?.unix_timestamp,
+            is_full,
+        });
+        Ok(())
+    }
+
+    /// Accepts `mint` as margin account collateral, valued through `symbol`'s
+    /// oracle feed, and creates the vault holding its deposits.
+    pub fn register_collateral_asset(
+        ctx: Context<RegisterCollateralAsset>,
+        symbol: String,
+        params: CollateralAssetParams,
+    ) -> Result<()> {
+        require!(symbol.len() <= MAX_SYMBOL_LEN, LiquidationError::InvalidCollateralParams);
+        require!(
+            ctx.accounts.mint.decimals <= MAX_COLLATERAL_DECIMALS,
+            LiquidationError::InvalidCollateralParams
+        );
+        params.validate()?;
+        let asset = &mut ctx.accounts.collateral_asset;
+        asset.mint = ctx.accounts.mint.key();
+        asset.symbol = symbol;
+        asset.decimals = ctx.accounts.mint.decimals;
+        asset.apply(&params);
+        Ok(())
+    }
+
+    pub fn update_collateral_asset(
+        ctx: Context<UpdateCollateralAsset>,
+        params: CollateralAssetParams,
+    ) -> Result<()> {
+        params.validate()?;
+        ctx.accounts.collateral_asset.apply(&params);
+        Ok(())
+    }
+
+    pub fn deposit_collateral_asset(ctx: Context<DepositCollateralAsset>, amount: u64) -> Result<()> {
+        require!(amount > 0, LiquidationError::InvalidAmount);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    to: ctx.accounts.collateral_vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let mint = ctx.accounts.collateral_asset.mint;
+        let margin_account = &mut ctx.accounts.margin_account;
+        match margin_account.deposits.iter_mut().find(|deposit| deposit.mint == mint) {
+            Some(deposit) => deposit.amount = math::add(deposit.amount, amount)?,
+            None => {
+                require!(
+                    margin_account.deposits.len() < MAX_COLLATERAL_ASSETS,
+                    LiquidationError::TooManyCollateralAssets
+                );
+                margin_account.deposits.push(CollateralBalance { mint, amount });
+            }
+        }
+
+        emit!(MarginDeposited {
+            margin_account: margin_account.key(),
+            owner: margin_account.owner,
+            mint,
+            amount,
+            balance: deposit_balance(margin_account, &mint),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Withdraws a collateral deposit as long as the account still covers the
+    /// initial margin of every position. `remaining_accounts` as described in
+    /// `cross_margin`; a deposit withdrawn in full is no longer listed.
+    pub fn withdraw_collateral_asset(ctx: Context<WithdrawCollateralAsset>, amount: u64) -> Result<()> {
+        require!(amount > 0, LiquidationError::InvalidAmount);
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let margin_account = &mut ctx.accounts.margin_account;
+        debit_deposit(margin_account, &ctx.accounts.collateral_asset.mint, amount)?;
+
+        let health = cross_margin::account_health(margin_account, ctx.remaining_accounts)?;
+        require!(
+            health.equity >= health.initial_requirement as i128,
+            LiquidationError::InitialMarginBreached
+        );
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.collateral_vault,
+            &ctx.accounts.owner_token_account,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            amount,
+        )?;
+
+        let mint = ctx.accounts.collateral_asset.mint;
+        emit!(MarginWithdrawn {
+            margin_account: margin_account.key(),
+            owner: margin_account.owner,
+            mint,
+            amount,
+            balance: deposit_balance(margin_account, &mint),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Sells `amount` of a liquidatable account's deposit to the liquidator
+    /// at the oracle price less the asset's liquidation discount. The quote
+    /// tokens paid are credited to the account's collateral. A discount of at
+    /// most the haircut means the sale never lowers the account's health.
+    /// `remaining_accounts` as described in `cross_margin`.
+    pub fn liquidate_collateral(ctx: Context<LiquidateCollateral>, amount: u64) -> Result<()> {
+        let vault_authority_bump = *ctx.bumps.get("vault_authority").unwrap();
+        let margin_account = &mut ctx.accounts.margin_account;
+        let asset = &ctx.accounts.collateral_asset;
+
+        let health = cross_margin::account_health(margin_account, ctx.remaining_accounts)?;
+        require!(health.is_liquidatable(), LiquidationError::NotLiquidatable);
+
+        require!(amount > 0, LiquidationError::InvalidLiquidationAmount);
+        debit_deposit(margin_account, &asset.mint, amount)?;
+        let price = oracle::load_collateral_price(&ctx.accounts.oracle, asset)?;
+        let value = asset.value(amount, price)?;
+        let payment = value - math::apply_bps(value, asset.liquidation_discount_bps)?;
+        require!(payment > 0, LiquidationError::InvalidLiquidationAmount);
+        margin_account.collateral = math::add(margin_account.collateral, payment)?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.liquidator_quote_account.to_account_info(),
+                    to: ctx.accounts.margin_vault.to_account_info(),
+                    authority: ctx.accounts.liquidator.to_account_info(),
+                },
+            ),
+            payment,
+        )?;
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.collateral_vault,
+            &ctx.accounts.liquidator_asset_account,
+            &ctx.accounts.vault_authority,
+            vault_authority_bump,
+            amount,
+        )?;
+
+        emit!(CollateralLiquidated {
+            owner: margin_account.owner,
+            liquidator: ctx.accounts.liquidator.key(),
+            mint: asset.mint,
+            amount,
+            price,
+            payment,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(symbol: String, is_long: bool)]
+pub struct OpenPosition<'info> {
+    #[account(
+        init, 
+        payer = owner, 
+        space = 8 + Position::INIT_SPACE,
+        seeds = [b"position", owner.key().as_ref(), symbol.as_bytes(), &[is_long as u8]], 
+        bump
+    )]
+    pub position: Account<'info, Position>,
+    #[account(mut, seeds = [MARKET_SEED, symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.opening_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, symbol.as_bytes()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = vault.mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddCollateral<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated
+    )]
+    pub position: Account<'info, Position>,
+    #[account(mut, seeds = [MARKET_SEED, position.symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, position.symbol.as_bytes()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = vault.mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawCollateral<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated
+    )]
+    pub position: Account<'info, Position>,
+    #[account(mut, seeds = [MARKET_SEED, position.symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.opening_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, position.symbol.as_bytes()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = vault.mint)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePosition<'info> {
+    /// CHECK: the baseline layout cannot be deserialized as `Position`; the
+    /// discriminator, layout and address are checked in the handler
+    #[account(mut, owner = crate::ID)]
+    pub position: UncheckedAccount<'info>,
+    /// Checked against the position's symbol in the handler
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReducePosition<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated
+    )]
+    pub position: Account<'info, Position>,
+    #[account(mut, seeds = [MARKET_SEED, position.symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        close = owner,
+        constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated
+    )]
+    pub position: Account<'info, Position>,
+    #[account(mut, seeds = [MARKET_SEED, position.symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, position.symbol.as_bytes()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = vault.mint)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeInsuranceFund<'info> {
+    #[account(
+        init, 
+        payer = authority, 
+        space = 8 + InsuranceFund::INIT_SPACE,
+        seeds = [b"insurance_fund"], 
+        bump
+    )]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [INSURANCE_VAULT_SEED],
+        bump,
+        token::mint = mint,
+        token::authority = vault_authority
+    )]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    #[account(constraint = mint.decimals == COLLATERAL_DECIMALS @ LiquidationError::InvalidCollateralMint)]
+    pub mint: Account<'info, Mint>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateInsuranceFund<'info> {
+    /// CHECK: the baseline layout cannot be deserialized as `InsuranceFund`;
+    /// the discriminator and layout are checked in the handler
+    #[account(mut, seeds = [b"insurance_fund"], bump, owner = crate::ID)]
+    pub insurance_fund: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [INSURANCE_VAULT_SEED],
+        bump,
+        token::mint = mint,
+        token::authority = vault_authority
+    )]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    #[account(constraint = mint.decimals == COLLATERAL_DECIMALS @ LiquidationError::InvalidCollateralMint)]
+    pub mint: Account<'info, Mint>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Checked against the fund's authority in the handler
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeInsuranceFundAuthority<'info> {
+    #[account(mut, seeds = [b"insurance_fund"], bump, has_one = authority)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptInsuranceFundAuthority<'info> {
+    #[account(mut, seeds = [b"insurance_fund"], bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    /// Checked against the pending authority in the handler
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ContributeToInsurance<'info> {
+    #[account(mut, seeds = [b"insurance_fund"], bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    #[account(mut, seeds = [INSURANCE_VAULT_SEED], bump)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = insurance_fund.mint,
+        token::authority = contributor
+    )]
+    pub contributor_token_account: Account<'info, TokenAccount>,
+    pub contributor: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFromInsurance<'info> {
+    #[account(mut, seeds = [b"insurance_fund"], bump, has_one = authority)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    #[account(mut, seeds = [INSURANCE_VAULT_SEED], bump)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = insurance_fund.mint)]
+    pub destination: Account<'info, TokenAccount>,
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CreateInsuranceStake<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + InsuranceStake::INIT_SPACE,
+        seeds = [INSURANCE_STAKE_SEED, owner.key().as_ref()],
+        bump
+    )]
+    pub stake: Account<'info, InsuranceStake>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeInsurance<'info> {
+    #[account(mut, seeds = [b"insurance_fund"], bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    #[account(mut, seeds = [INSURANCE_VAULT_SEED], bump)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [INSURANCE_STAKE_SEED, owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub stake: Account<'info, InsuranceStake>,
+    #[account(
+        mut,
+        token::mint = insurance_fund.mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RequestUnstake<'info> {
+    #[account(
+        mut,
+        seeds = [INSURANCE_STAKE_SEED, owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub stake: Account<'info, InsuranceStake>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnstakeInsurance<'info> {
+    #[account(mut, seeds = [b"insurance_fund"], bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    #[account(mut, seeds = [INSURANCE_VAULT_SEED], bump)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [INSURANCE_STAKE_SEED, owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub stake: Account<'info, InsuranceStake>,
+    #[account(mut, token::mint = insurance_fund.mint)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [CONFIG_SEED],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeConfigAuthority<'info> {
+    #[account(mut, seeds = [CONFIG_SEED], bump, has_one = authority)]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptConfigAuthority<'info> {
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: Account<'info, Config>,
+    /// Checked against the pending authority in the handler
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardian<'info> {
+    #[account(mut, seeds = [CONFIG_SEED], bump, has_one = authority)]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPauseState<'info> {
+    #[account(mut, seeds = [CONFIG_SEED], bump, has_one = guardian)]
+    pub config: Account<'info, Config>,
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(symbol: String)]
+pub struct InitializeMarket<'info> {
+    #[account(seeds = [CONFIG_SEED], bump, has_one = authority)]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Market::INIT_SPACE,
+        seeds = [MARKET_SEED, symbol.as_bytes()],
+        bump
+    )]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarket<'info> {
+    #[account(seeds = [CONFIG_SEED], bump, has_one = authority)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [MARKET_SEED, market.symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFunding<'info> {
+    #[account(mut, seeds = [MARKET_SEED, market.symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(symbol: String)]
+pub struct InitializeVault<'info> {
+    #[account(
+        seeds = [b"insurance_fund"],
+        bump,
+        has_one = authority,
+        has_one = mint
+    )]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [VAULT_SEED, symbol.as_bytes()],
+        bump,
+        token::mint = mint,
+        token::authority = vault_authority
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    pub mint: Account<'info, Mint>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidatePartial<'info> {
+    #[account(
+        mut,
+        constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated
+    )]
+    pub position: Account<'info, Position>,
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, position.symbol.as_bytes()],
+        bump,
+        constraint = market.liquidation_mode == LiquidationMode::Direct
+            @ LiquidationError::WrongLiquidationMode
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.liquidations_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [b"insurance_fund"], bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, position.symbol.as_bytes()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [INSURANCE_VAULT_SEED], bump)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = vault.mint)]
+    pub liquidator_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub liquidator: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidateFull<'info> {
+    #[account(
+        mut,
+        constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated
+    )]
+    pub position: Account<'info, Position>,
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, position.symbol.as_bytes()],
+        bump,
+        constraint = market.liquidation_mode == LiquidationMode::Direct
+            @ LiquidationError::WrongLiquidationMode
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.liquidations_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [b"insurance_fund"], bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, position.symbol.as_bytes()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [INSURANCE_VAULT_SEED], bump)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = vault.mint)]
+    pub liquidator_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub liquidator: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct StartAuction<'info> {
+    #[account(mut, constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated)]
+    pub position: Account<'info, Position>,
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, position.symbol.as_bytes()],
+        bump,
+        constraint = market.liquidation_mode == LiquidationMode::Auction
+            @ LiquidationError::WrongLiquidationMode
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.liquidations_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = keeper,
+        space = 8 + Auction::INIT_SPACE,
+        seeds = [AUCTION_SEED, position.key().as_ref()],
+        bump
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TakeAuction<'info> {
+    #[account(
+        mut,
+        constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated
+    )]
+    pub position: Account<'info, Position>,
+    #[account(
+        mut,
+        seeds = [AUCTION_SEED, position.key().as_ref()],
+        bump,
+        has_one = position,
+        has_one = market,
+        has_one = keeper
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [MARKET_SEED, position.symbol.as_bytes()],
+        bump,
+        constraint = market.liquidation_mode == LiquidationMode::Auction
+            @ LiquidationError::WrongLiquidationMode
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.liquidations_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [b"insurance_fund"], bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, position.symbol.as_bytes()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [INSURANCE_VAULT_SEED], bump)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = vault.mint)]
+    pub liquidator_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub liquidator: Signer<'info>,
+    /// CHECK: receives the auction rent once the position is fully taken
+    #[account(mut)]
+    pub keeper: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAuction<'info> {
+    #[account(
+        mut,
+        seeds = [AUCTION_SEED, auction.position.as_ref()],
+        bump,
+        has_one = market,
+        has_one = keeper,
+        close = keeper
+    )]
+    pub auction: Account<'info, Auction>,
+    /// CHECK: may already be closed; deserialized in the handler while it exists
+    #[account(address = auction.position)]
+    pub position: UncheckedAccount<'info>,
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    /// CHECK: receives the auction rent; must be the keeper that started it
+    #[account(mut)]
+    pub keeper: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetPositionHealth<'info> {
+    #[account(constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated)]
+    pub position: Account<'info, Position>,
+    #[account(seeds = [MARKET_SEED, position.symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AutoDeleverage<'info> {
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority,
+        constraint = !config.liquidations_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        constraint = position.version == POSITION_VERSION @ LiquidationError::PositionNotMigrated
+    )]
+    pub position: Account<'info, Position>,
+    #[account(mut, seeds = [MARKET_SEED, position.symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeMarginVault<'info> {
+    #[account(
+        seeds = [b"insurance_fund"],
+        bump,
+        has_one = authority,
+        has_one = mint
+    )]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [MARGIN_VAULT_SEED],
+        bump,
+        token::mint = mint,
+        token::authority = vault_authority
+    )]
+    pub margin_vault: Account<'info, TokenAccount>,
+    pub mint: Account<'info, Mint>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMarginAccount<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + MarginAccount::INIT_SPACE,
+        seeds = [MARGIN_ACCOUNT_SEED, owner.key().as_ref()],
+        bump
+    )]
+    pub margin_account: Account<'info, MarginAccount>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositMargin<'info> {
+    #[account(
+        mut,
+        seeds = [MARGIN_ACCOUNT_SEED, owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub margin_account: Account<'info, MarginAccount>,
+    #[account(mut, seeds = [MARGIN_VAULT_SEED], bump)]
+    pub margin_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = margin_vault.mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawMargin<'info> {
+    #[account(
+        mut,
+        seeds = [MARGIN_ACCOUNT_SEED, owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub margin_account: Account<'info, MarginAccount>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.opening_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [MARGIN_VAULT_SEED], bump)]
+    pub margin_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = margin_vault.mint)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(symbol: String)]
+pub struct OpenCrossPosition<'info> {
+    #[account(
+        mut,
+        seeds = [MARGIN_ACCOUNT_SEED, owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub margin_account: Account<'info, MarginAccount>,
+    #[account(mut, seeds = [MARKET_SEED, symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.opening_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(symbol: String)]
+pub struct CloseCrossPosition<'info> {
+    #[account(
+        mut,
+        seeds = [MARGIN_ACCOUNT_SEED, owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub margin_account: Account<'info, MarginAccount>,
+    #[account(mut, seeds = [MARKET_SEED, symbol.as_bytes()], bump)]
+    pub market: Account<'info, Market>,
+    /// CHECK: must be the market's feed; parsed and validated by `oracle::load_market_price`
+    #[account(address = market.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    #[account(mut, seeds = [VAULT_SEED, symbol.as_bytes()], bump)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [MARGIN_VAULT_SEED], bump)]
+    pub margin_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidateMarginAccount<'info> {
+    #[account(
+        mut,
+        seeds = [MARGIN_ACCOUNT_SEED, margin_account.owner.as_ref()],
+        bump
+    )]
+    pub margin_account: Account<'info, MarginAccount>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.liquidations_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [b"insurance_fund"], bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    #[account(mut, seeds = [MARGIN_VAULT_SEED], bump)]
+    pub margin_vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [INSURANCE_VAULT_SEED], bump)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = margin_vault.mint)]
+    pub liquidator_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub liquidator: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(symbol: String)]
+pub struct RegisterCollateralAsset<'info> {
+    #[account(seeds = [CONFIG_SEED], bump, has_one = authority)]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + CollateralAsset::INIT_SPACE,
+        seeds = [COLLATERAL_ASSET_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub collateral_asset: Account<'info, CollateralAsset>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [COLLATERAL_VAULT_SEED, mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = vault_authority
+    )]
+    pub collateral_vault: Account<'info, TokenAccount>,
+    pub mint: Account<'info, Mint>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCollateralAsset<'info> {
+    #[account(seeds = [CONFIG_SEED], bump, has_one = authority)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [COLLATERAL_ASSET_SEED, collateral_asset.mint.as_ref()], bump)]
+    pub collateral_asset: Account<'info, CollateralAsset>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DepositCollateralAsset<'info> {
+    #[account(
+        mut,
+        seeds = [MARGIN_ACCOUNT_SEED, owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub margin_account: Account<'info, MarginAccount>,
+    #[account(seeds = [COLLATERAL_ASSET_SEED, collateral_asset.mint.as_ref()], bump)]
+    pub collateral_asset: Account<'info, CollateralAsset>,
+    #[account(mut, seeds = [COLLATERAL_VAULT_SEED, collateral_asset.mint.as_ref()], bump)]
+    pub collateral_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = collateral_asset.mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawCollateralAsset<'info> {
+    #[account(
+        mut,
+        seeds = [MARGIN_ACCOUNT_SEED, owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub margin_account: Account<'info, MarginAccount>,
+    #[account(seeds = [COLLATERAL_ASSET_SEED, collateral_asset.mint.as_ref()], bump)]
+    pub collateral_asset: Account<'info, CollateralAsset>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.opening_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [COLLATERAL_VAULT_SEED, collateral_asset.mint.as_ref()], bump)]
+    pub collateral_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = collateral_asset.mint)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidateCollateral<'info> {
+    #[account(
+        mut,
+        seeds = [MARGIN_ACCOUNT_SEED, margin_account.owner.as_ref()],
+        bump
+    )]
+    pub margin_account: Account<'info, MarginAccount>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        constraint = !config.liquidations_paused @ LiquidationError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+    #[account(seeds = [COLLATERAL_ASSET_SEED, collateral_asset.mint.as_ref()], bump)]
+    pub collateral_asset: Account<'info, CollateralAsset>,
+    /// CHECK: must be the asset's feed; parsed and validated by `oracle::load_collateral_price`
+    #[account(address = collateral_asset.oracle @ LiquidationError::InvalidOracleAccount)]
+    pub oracle: UncheckedAccount<'info>,
+    #[account(mut, seeds = [COLLATERAL_VAULT_SEED, collateral_asset.mint.as_ref()], bump)]
+    pub collateral_vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [MARGIN_VAULT_SEED], bump)]
+    pub margin_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every program token vault; never read
+    #[account(seeds = [VAULT_AUTHORITY_SEED], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = collateral_asset.mint)]
+    pub liquidator_asset_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = margin_vault.mint,
+        token::authority = liquidator
+    )]
+    pub liquidator_quote_account: Account<'info, TokenAccount>,
+    pub liquidator: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const MARKET_SEED: &[u8] = b"market";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const INSURANCE_VAULT_SEED: &[u8] = b"insurance_vault";
+pub const VAULT_AUTHORITY_SEED: &[u8] = b"vault_authority";
+pub const AUCTION_SEED: &[u8] = b"auction";
+pub const MARGIN_ACCOUNT_SEED: &[u8] = b"margin_account";
+pub const MARGIN_VAULT_SEED: &[u8] = b"margin_vault";
+pub const INSURANCE_STAKE_SEED: &[u8] = b"insurance_stake";
+pub const COLLATERAL_ASSET_SEED: &[u8] = b"collateral_asset";
+pub const COLLATERAL_VAULT_SEED: &[u8] = b"collateral_vault";
+
+/// Current `Position` layout version.
+pub const POSITION_VERSION: u8 = 2;
+
+/// Collateral amounts share the fixed-point scale used for prices (e.g. USDC).
+const COLLATERAL_DECIMALS: u8 = math::DECIMALS as u8;
+
+/// Collateral assets' base units must fit `10^decimals` in a u64.
+const MAX_COLLATERAL_DECIMALS: u8 = 18;
+
+/// At most 10% of the insurance fund may be withdrawn per day.
+const INSURANCE_WITHDRAWAL_LIMIT_BPS: u16 = 1_000;
+const INSURANCE_WITHDRAWAL_WINDOW: i64 = 24 * 60 * 60;
+
+/// Time between requesting an unstake and redeeming it, so stakers cannot
+/// leave ahead of a loss they see coming.
+const INSURANCE_UNSTAKE_COOLDOWN: i64 = 7 * 24 * 60 * 60;
+/// How long after the cooldown an unstake request stays redeemable, so a
+/// matured request cannot be held open as a free option to exit.
+const INSURANCE_UNSTAKE_WINDOW: i64 = 2 * 24 * 60 * 60;
+
+/// `max_funding_rate_bps` is quoted per hour.
+const FUNDING_PERIOD: i64 = 60 * 60;
+
+/// Takes `amount` off the account's deposit of `mint`, dropping the entry
+/// once it is empty.
+fn debit_deposit(margin_account: &mut MarginAccount, mint: &Pubkey, amount: u64) -> Result<()> {
+    let index = margin_account
+        .deposits
+        .iter()
+        .position(|deposit| deposit.mint == *mint)
+        .ok_or(LiquidationError::InsufficientCollateralBalance)?;
+    let deposit = &mut margin_account.deposits[index];
+    require!(amount <= deposit.amount, LiquidationError::InsufficientCollateralBalance);
+    deposit.amount -= amount;
+    if deposit.amount == 0 {
+        margin_account.deposits.remove(index);
+    }
+    Ok(())
+}
+
+/// The account's deposit of `mint`; zero once withdrawn in full.
+fn deposit_balance(margin_account: &MarginAccount, mint: &Pubkey) -> u64 {
+    margin_account
+        .deposits
+        .iter()
+        .find(|deposit| deposit.mint == *mint)
+        .map_or(0, |deposit| deposit.amount)
+}
+
+/// Realizes a cross position's `pnl` against its market's vault, which holds
+/// the collateral of the positions on the other side.
+fn settle_cross_pnl<'info>(
+    token_program: &Program<'info, Token>,
+    market_vault: &Account<'info, TokenAccount>,
+    margin_vault: &Account<'info, TokenAccount>,
+    vault_authority: &UncheckedAccount<'info>,
+    vault_authority_bump: u8,
+    pnl: i128,
+) -> Result<()> {
+    if pnl >= 0 {
+        transfer_from_vault(token_program, market_vault, margin_vault, vault_authority, vault_authority_bump, math::to_u64(pnl)?)
+    } else {
+        transfer_from_vault(token_program, margin_vault, market_vault, vault_authority, vault_authority_bump, math::to_u64(-pnl)?)
+    }
+}
+
+/// `symbol`'s market vault, passed in `remaining_accounts`.
+fn load_market_vault<'info>(info: &AccountInfo<'info>, symbol: &str) -> Result<Account<'info, TokenAccount>> {
+    let (vault, _) = Pubkey::find_program_address(&[VAULT_SEED, symbol.as_bytes()], &crate::ID);
+    require_keys_eq!(info.key(), vault, LiquidationError::InvalidRemainingAccounts);
+    Account::try_from(info)
+}
+
+/// Moves `amount` out of a program-owned token vault, signing as the vault authority.
+fn transfer_from_vault<'info>(
+    token_program: &Program<'info, Token>,
+    from: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    vault_authority: &UncheckedAccount<'info>,
+    vault_authority_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let signer_seeds: &[&[u8]] = &[VAULT_AUTHORITY_SEED, &[vault_authority_bump]];
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: from.to_account_info(),
+                to: to.to_account_info(),
+                authority: vault_authority.to_account_info(),
+            },
+            &[signer_seeds],
+        ),
+        amount,
+    )
+}
+
+/// Reallocates a program account written in an older layout to `new_len`,
+/// zero-filling the new bytes, with `payer` topping up the rent.
+fn resize_account<'info>(
+    info: &AccountInfo<'info>,
+    new_len: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let rent_due = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(info.lamports());
+    if rent_due > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: info.clone(),
+                },
+            ),
+            rent_due,
+        )?;
+    }
+    info.realloc(new_len, true)?;
+    Ok(())
+}
+
+/// Second step of an authority handover: hands `authority` to `signer` if
+/// it is the pending proposal, and returns the previous authority.
+fn accept_pending_authority(authority: &mut Pubkey, pending_authority: &mut Pubkey, signer: Pubkey) -> Result<Pubkey> {
+    require!(
+        *pending_authority != Pubkey::default() && *pending_authority == signer,
+        LiquidationError::NotPendingAuthority
+    );
+    *pending_authority = Pubkey::default();
+    Ok(std::mem::replace(authority, signer))
+}
+
+fn emit_authority_proposed(account: Pubkey, authority: Pubkey, proposed_authority: Pubkey) -> Result<()> {
+    emit!(AuthorityProposed {
+        account,
+        authority,
+        proposed_authority,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+fn emit_authority_transferred(
+    account: Pubkey,
+    previous_authority: Pubkey,
+    new_authority: Pubkey,
+) -> Result<()> {
+    emit!(AuthorityTransferred {
+        account,
+        previous_authority,
+        new_authority,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+fn emit_pause_state(config: &Config, guardian: Pubkey) -> Result<()> {
+    emit!(PauseStateChanged {
+        guardian,
+        opening_paused: config.opening_paused,
+        liquidations_paused: config.liquidations_paused,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Emits the position's state after a change; `size_before` and
+/// `collateral_before` are taken before funding was settled.
+fn emit_position_modified(
+    key: Pubkey,
+    position: &Position,
+    size_before: u64,
+    collateral_before: u64,
+) -> Result<()> {
+    emit!(PositionModified {
+        position: key,
+        owner: position.owner,
+        symbol: position.symbol.clone(),
+        is_long: position.is_long,
+        size: position.size,
+        collateral: position.collateral,
+        maintenance_margin: position.maintenance_margin,
+        last_funding_index: position.last_funding_index,
+        size_reduced: math::sub(size_before, position.size)?,
+        collateral_change: math::to_i64(position.collateral as i128 - collateral_before as i128)?,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Credits the fund's share of a liquidation penalty, already transferred to
+/// the insurance vault.
+fn collect_insurance_fee(insurance_fund: &mut InsuranceFund, fee: u64) -> Result<()> {
+    let balance_before = insurance_fund.balance;
+    insurance_fund.balance = math::add(insurance_fund.balance, fee)?;
+    insurance_fund.total_contributions = math::add(insurance_fund.total_contributions, fee)?;
+    emit_insurance_fund_changed(insurance_fund, balance_before)
+}
+
+fn emit_insurance_fund_changed(insurance_fund: &InsuranceFund, balance_before: u64) -> Result<()> {
+    if insurance_fund.balance == balance_before {
+        return Ok(());
+    }
+    emit!(InsuranceFundChanged {
+        balance: insurance_fund.balance,
+        change: math::to_i64(insurance_fund.balance as i128 - balance_before as i128)?,
+        total_contributions: insurance_fund.total_contributions,
+        total_bad_debt_covered: insurance_fund.total_bad_debt_covered,
+        total_shares: insurance_fund.total_shares,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+fn unrealized_pnl(position: &Position, price: u64) -> Result<i128> {
+    math::pnl(position.size, position.entry_price, price, position.is_long)
+}
+
+/// Collateral plus unrealized PnL at `price`.
+fn margin_amount(position: &Position, price: u64) -> Result<i128> {
+    Ok(position.collateral as i128 + unrealized_pnl(position, price)?)
+}
+
+/// Closes `amount` of `position` at `price`: the slice's PnL is realized into
+/// collateral and the maintenance margin shrinks with the size.
+fn reduce_size(position: &mut Position, amount: u64, price: u64) -> Result<()> {
+    require!(amount <= position.size, LiquidationError::InvalidAmount);
+    let realized_pnl = unrealized_pnl(position, price)?
+        .checked_mul(amount as i128)
+        .ok_or(LiquidationError::MathOverflow)?
+        / position.size as i128;
+    let collateral_after = position.collateral as i128 + realized_pnl;
+    require!(collateral_after >= 0, LiquidationError::PositionUnderwater);
+
+    let remaining_size = position.size - amount;
+    position.maintenance_margin =
+        math::mul_div(position.maintenance_margin, remaining_size, position.size)?;
+    position.size = remaining_size;
+    position.collateral = math::to_u64(collateral_after)?;
+    Ok(())
+}
+
+/// Funding the position owes since its last settlement (negative when it is
+/// owed funding). A side pays while its index rises.
+fn pending_funding(position: &Position, market: &Market) -> Result<i128> {
+    market.funding_owed(position.size, position.is_long, position.last_funding_index)
+}
+
+/// Moves pending funding into collateral and returns the part the
+/// collateral could not pay.
+fn apply_funding(position: &mut Position, market: &Market) -> Result<u64> {
+    let collateral = position.collateral as i128 - pending_funding(position, market)?;
+    position.collateral = math::to_u64(collateral.max(0))?;
+    position.last_funding_index = market.funding_index(position.is_long);
+    math::to_u64((-collateral).max(0))
+}
+
+/// Settles pending funding. The other side has already been credited what
+/// the position owes, so funding the collateral cannot pay is the vault's
+/// shortfall and becomes bad debt.
+fn settle_funding(position: &mut Position, market: &mut Market) -> Result<()> {
+    let unpaid = apply_funding(position, market)?;
+    market.record_unpaid_funding(unpaid)
+}
+
+/// Brings the market's funding indices up to date and settles the position
+/// against them. Called before any margin check on an existing position.
+fn touch_position(position: &mut Position, market: &mut Market, price: u64) -> Result<()> {
+    market.accrue_funding(price, Clock::get()?.unix_timestamp)?;
+    settle_funding(position, market)
+}
+
+/// Price at which the position's margin is exactly zero.
+fn bankruptcy_price(position: &Position) -> Result<u64> {
+    price_at_margin(position, 0)
+}
+
+/// Price at which the position's margin equals `margin_bps` of its notional;
+/// with the maintenance tier this is the liquidation price.
+fn price_at_margin(position: &Position, margin_bps: u16) -> Result<u64> {
+    let margin_per_unit = math::mul_div(position.collateral, math::SCALE as u64, position.size)?;
+    let denominator = math::BPS_DENOMINATOR as u64;
+    if position.is_long {
+        // collateral + (p - entry) * size = p * size * margin
+        let numerator = position.entry_price.saturating_sub(margin_per_unit);
+        math::mul_div(numerator, denominator, math::sub(denominator, margin_bps as u64)?)
+    } else {
+        // collateral + (entry - p) * size = p * size * margin
+        let numerator = math::add(position.entry_price, margin_per_unit)?;
+        math::mul_div(numerator, denominator, denominator + margin_bps as u64)
+    }
+}
+
+/// Where a fully liquidated position's remaining margin goes.
+struct FullSettlement {
+    /// Paid from the market vault to the liquidator.
+    reward_from_collateral: u64,
+    /// The fund's share of the penalty, paid from the market vault to the
+    /// insurance vault.
+    insurance_fee: u64,
+    /// Margin left after the reward and fee. It stays with the owner: as
+    /// position collateral, or margin account collateral.
+    surplus: u64,
+    /// Unpaid reward covered by the insurance vault.
+    fund_to_liquidator: u64,
+    /// Negative equity refilled into the market vault by the insurance vault.
+    fund_to_vault: u64,
+    bad_debt: u64,
+    /// Negative equity the insurance fund could not refill; the market vault
+    /// is short by this much until it is recovered by auto-deleveraging.
+    vault_shortfall: u64,
+}
+
+impl FullSettlement {
+    fn liquidator_reward(&self) -> Result<u64> {
+        math::add(self.reward_from_collateral, self.fund_to_liquidator)
+    }
+}
+
+/// Pays `reward` out of `margin_before` first. Anything the position cannot
+/// cover (unpaid reward plus negative equity) is bad debt drawn from the
+/// insurance fund. The fund's `insurance_fee` is only charged against margin
+/// left after the reward; whatever remains after that is the owner's.
+fn settle_full_liquidation(
+    margin_before: i128,
+    reward: u64,
+    insurance_fee: u64,
+    insurance_fund: &mut InsuranceFund,
+) -> Result<FullSettlement> {
+    let reward_from_collateral = math::to_u64(margin_before.clamp(0, reward as i128))?;
+    let reward_shortfall = reward - reward_from_collateral;
+    let negative_equity = math::to_u64((-margin_before).max(0))?;
+    let bad_debt = math::add(reward_shortfall, negative_equity)?;
+
+    let covered = bad_debt.min(insurance_fund.balance);
+    insurance_fund.balance -= covered;
+    insurance_fund.total_bad_debt_covered = math::add(insurance_fund.total_bad_debt_covered, covered)?;
+
+    let margin_after_reward = math::to_u64((margin_before - reward as i128).max(0))?;
+    let insurance_fee = insurance_fee.min(margin_after_reward);
+    insurance_fund.balance = math::add(insurance_fund.balance, insurance_fee)?;
+    insurance_fund.total_contributions = math::add(insurance_fund.total_contributions, insurance_fee)?;
+
+    let surplus = margin_after_reward - insurance_fee;
+
+    let fund_to_liquidator = reward_shortfall.min(covered);
+    let fund_to_vault = covered - fund_to_liquidator;
+    Ok(FullSettlement {
+        reward_from_collateral,
+        insurance_fee,
+        surplus,
+        fund_to_liquidator,
+        fund_to_vault,
+        bad_debt,
+        vault_shortfall: negative_equity - fund_to_vault,
+    })
+}
+
+/// Largest amount one partial liquidation paying `fee_bps` may close: at most
+/// `close_factor_bps` of the size, and no more than needed to bring the
+/// position back up to `target_margin_bps`.
+fn max_partial_liquidation(position: &Position, market: &Market, price: u64, fee_bps: u16) -> Result<u64> {
+    let close_factor_cap = math::apply_bps(position.size, market.close_factor_bps)?;
+    Ok(match size_to_target_margin(position, market, price, fee_bps)? {
+        Some(size_needed) => close_factor_cap.min(size_needed).min(position.size),
+        None => close_factor_cap,
+    })
+}
+
+/// Size a liquidation paying `fee_bps` must close to bring the position back
+/// up to `target_margin_bps`, or `None` when no amount can because the fee
+/// costs more margin than closing frees.
+fn size_to_target_margin(position: &Position, market: &Market, price: u64, fee_bps: u16) -> Result<Option<u64>> {
+    let target = math::apply_bps(math::notional(position.size, price)?, market.target_margin_bps)?;
+    let deficit = target as i128 - margin_amount(position, price)?;
+    if deficit <= 0 {
+        return Ok(Some(0));
+    }
+    if market.target_margin_bps <= fee_bps {
+        return Ok(None);
+    }
+
+    // Each unit of notional closed lowers the target requirement by
+    // `target_margin_bps` but costs the margin `fee_bps` in reward
+    let improvement_bps = (market.target_margin_bps - fee_bps) as u64;
+    let notional_needed = math::mul_div_ceil(
+        math::to_u64(deficit)?,
+        math::BPS_DENOMINATOR as u64,
+        improvement_bps,
+    )?;
+    Ok(Some(math::mul_div_ceil(notional_needed, math::SCALE as u64, price)?))
+}
+
+/// Whether `liquidate_full` may close the whole position: it is at or below
+/// the market's minimum size, bankrupt, or no partial liquidation within the
+/// close factor can restore the target margin and leave at least the minimum
+/// size behind.
+fn full_liquidation_allowed(position: &Position, market: &Market, price: u64, fee_bps: u16) -> Result<bool> {
+    if position.size <= market.min_position_size || margin_amount(position, price)? <= 0 {
+        return Ok(true);
+    }
+    let close_factor_cap = math::apply_bps(position.size, market.close_factor_bps)?;
+    Ok(match size_to_target_margin(position, market, price, fee_bps)? {
+        Some(size_needed) => {
+            size_needed > close_factor_cap || position.size - size_needed < market.min_position_size
+        }
+        None => true,
+    })
+}
+
+fn check_partial_liquidation(
+    position: &Position,
+    market: &Market,
+    price: u64,
+    amount: u64,
+    fee_bps: u16,
+) -> Result<()> {
+    require!(amount > 0, LiquidationError::InvalidLiquidationAmount);
+    require!(
+        amount <= max_partial_liquidation(position, market, price, fee_bps)?,
+        LiquidationError::LiquidationAmountTooLarge
+    );
+    require!(
+        position.size - amount >= market.min_position_size,
+        LiquidationError::RemainingSizeBelowMinimum
+    );
+    Ok(())
+}
+
+/// Liquidation fee rate for `position` at `price`, scaled by how far its
+/// margin ratio is below maintenance.
+fn liquidation_fee_bps(position: &Position, market: &Market, price: u64) -> Result<u16> {
+    let notional = math::notional(position.size, price)?;
+    if notional == 0 {
+        return Ok(market.liquidation_fee_bps);
+    }
+    let margin_ratio_bps = margin_amount(position, price)? * math::BPS_DENOMINATOR as i128 / notional as i128;
+    Ok(market.liquidation_fee_bps_at(margin_ratio_bps, market.maintenance_margin_bps(position.leverage)))
+}
+
+/// A position is liquidatable once its margin at `price` drops below the
+/// market's maintenance requirement for its leverage tier.
+fn is_liquidatable(position: &Position, market: &Market, price: u64) -> Result<bool> {
+    let notional = math::notional(position.size, price)?;
+    if notional == 0 {
+        return Ok(false);
+    }
+    let requirement = math::apply_bps(notional, market.maintenance_margin_bps(position.leverage))?;
+    Ok(margin_amount(position, price)? < requirement as i128)
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Position {
+    /// Layout version, bumped whenever fields are added. Accounts written
+    /// by the originally deployed program, before this field existed, are
+    /// upgraded by `migrate_position`.
+    pub version: u8,
+    pub owner: Pubkey,
+    #[max_len(32)]
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub collateral: u64,
+    pub entry_price: u64,
+    pub leverage: u16,
+    pub maintenance_margin: u64,
+    /// Its side's market funding index as of the last settlement.
+    pub last_funding_index: i128,
+}
+
+/// Shared collateral backing several positions, margined together.
+#[account]
+#[derive(InitSpace)]
+pub struct MarginAccount {
+    pub owner: Pubkey,
+    pub collateral: u64,
+    /// At most one per market.
+    #[max_len(8)]
+    pub positions: Vec<CrossPosition>,
+    /// Registered collateral assets held besides `collateral`, at most one
+    /// per mint.
+    #[max_len(4)]
+    pub deposits: Vec<CollateralBalance>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct CollateralBalance {
+    pub mint: Pubkey,
+    /// In the mint's base units.
+    pub amount: u64,
+}
+
+/// A token margin accounts may post as collateral besides the quote token,
+/// counted at its oracle value less a haircut.
+#[account]
+#[derive(InitSpace)]
+pub struct CollateralAsset {
+    pub mint: Pubkey,
+    /// Identifies the asset's price off-chain.
+    #[max_len(32)]
+    pub symbol: String,
+    pub decimals: u8,
+    pub oracle: Pubkey,
+    pub oracle_program: Pubkey,
+    pub max_staleness_slots: u64,
+    pub max_confidence_bps: u16,
+    pub weight_bps: u16,
+    pub liquidation_discount_bps: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CollateralAssetParams {
+    pub oracle: Pubkey,
+    /// Program that must own `oracle`.
+    pub oracle_program: Pubkey,
+    pub max_staleness_slots: u64,
+    pub max_confidence_bps: u16,
+    /// Share of the oracle value counted as collateral; the rest is the
+    /// haircut.
+    pub weight_bps: u16,
+    /// Discount to the oracle price at which liquidators buy the asset from
+    /// liquidatable accounts. At most the haircut.
+    pub liquidation_discount_bps: u16,
+}
+
+impl CollateralAssetParams {
+    fn validate(&self) -> Result<()> {
+        require!(
+            self.max_staleness_slots > 0
+                && self.max_confidence_bps > 0
+                && self.max_confidence_bps as u128 <= math::BPS_DENOMINATOR,
+            LiquidationError::InvalidCollateralParams
+        );
+        require!(
+            self.weight_bps as u128 <= math::BPS_DENOMINATOR
+                && self.liquidation_discount_bps as u128 <= math::BPS_DENOMINATOR - self.weight_bps as u128,
+            LiquidationError::InvalidCollateralParams
+        );
+        Ok(())
+    }
+}
+
+impl CollateralAsset {
+    fn apply(&mut self, params: &CollateralAssetParams) {
+        self.oracle = params.oracle;
+        self.oracle_program = params.oracle_program;
+        self.max_staleness_slots = params.max_staleness_slots;
+        self.max_confidence_bps = params.max_confidence_bps;
+        self.weight_bps = params.weight_bps;
+        self.liquidation_discount_bps = params.liquidation_discount_bps;
+    }
+
+    /// Quote value of `amount` base units at `price`.
+    pub fn value(&self, amount: u64, price: u64) -> Result<u64> {
+        math::mul_div(amount, price, 10u64.pow(self.decimals as u32))
+    }
+
+    /// Value counted towards an account's margin, after the haircut.
+    pub fn weighted_value(&self, amount: u64, price: u64) -> Result<u64> {
+        math::apply_bps(self.value(amount, price)?, self.weight_bps)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct CrossPosition {
+    #[max_len(32)]
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub entry_price: u64,
+    /// Selects the market's maintenance margin tier.
+    pub leverage: u16,
+    pub last_funding_index: i128,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct InsuranceFund {
+    pub authority: Pubkey,
+    pub mint: Pubkey,
+    pub balance: u64,
+    pub total_contributions: u64,
+    pub total_bad_debt_covered: u64,
+    pub utilization_ratio: u64, // basis points
+    pub withdrawal_window_start: i64,
+    pub withdrawn_in_window: u64,
+    pub pending_authority: Pubkey,
+    /// Shares outstanding against `balance`, including the protocol's.
+    pub total_shares: u64,
+    /// Shares backing the balance that predates staking and what the
+    /// authority may withdraw.
+    pub protocol_shares: u64,
+}
+
+impl InsuranceFund {
+    /// Before the first stake the whole balance belongs to the protocol;
+    /// it is credited as protocol shares so stakers only own what they add.
+    fn credit_protocol_shares(&mut self) {
+        if self.total_shares == 0 {
+            self.total_shares = self.balance;
+            self.protocol_shares = self.balance;
+        }
+    }
+
+    /// Shares minted for depositing `amount` at the current share price.
+    fn shares_for_deposit(&self, amount: u64) -> Result<u64> {
+        if self.total_shares == 0 {
+            return Ok(amount);
+        }
+        // Bad debt wiped out the fund; outstanding shares are worthless
+        require!(self.balance > 0, LiquidationError::InsuranceFundDepleted);
+        math::mul_div(amount, self.total_shares, self.balance)
+    }
+
+    /// Value of `shares` at the current share price, rounded down.
+    fn value_of_shares(&self, shares: u64) -> Result<u64> {
+        math::mul_div(shares, self.balance, self.total_shares)
+    }
+
+    /// Takes `amount` out of the balance for the authority at `now`, within
+    /// `INSURANCE_WITHDRAWAL_LIMIT_BPS` of the balance per withdrawal window.
+    fn record_withdrawal(&mut self, amount: u64, now: i64) -> Result<()> {
+        if now - self.withdrawal_window_start >= INSURANCE_WITHDRAWAL_WINDOW {
+            self.withdrawal_window_start = now;
+            self.withdrawn_in_window = 0;
+        }
+
+        // Limit is measured against the balance at the start of the window
+        let window_balance = math::add(self.balance, self.withdrawn_in_window)?;
+        let limit = math::apply_bps(window_balance, INSURANCE_WITHDRAWAL_LIMIT_BPS)?;
+        let withdrawn_in_window = math::add(self.withdrawn_in_window, amount)?;
+        require!(withdrawn_in_window <= limit, LiquidationError::WithdrawalLimitExceeded);
+
+        // Once stakers are in, the authority can only take out the protocol's share
+        if self.total_shares > 0 {
+            let shares = math::mul_div_ceil(amount, self.total_shares, self.balance)?;
+            require!(shares <= self.protocol_shares, LiquidationError::InsufficientShares);
+            self.protocol_shares -= shares;
+            self.total_shares -= shares;
+        }
+
+        self.balance = math::sub(self.balance, amount)?;
+        self.withdrawn_in_window = withdrawn_in_window;
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct InsuranceStake {
+    pub owner: Pubkey,
+    pub shares: u64,
+    /// Part of `shares` requested for unstaking, redeemable once the
+    /// cooldown from `unstake_requested_at` has passed and until the
+    /// redemption window after it closes.
+    pub unstake_shares: u64,
+    pub unstake_requested_at: i64,
+}
+
+impl InsuranceStake {
+    /// Shares the pending request lets the owner redeem at `now`.
+    fn redeemable_shares(&self, now: i64) -> Result<u64> {
+        require!(self.unstake_shares > 0, LiquidationError::InvalidAmount);
+        let available_at = self.unstake_requested_at + INSURANCE_UNSTAKE_COOLDOWN;
+        require!(now >= available_at, LiquidationError::UnstakeCooldownActive);
+        require!(
+            now <= available_at + INSURANCE_UNSTAKE_WINDOW,
+            LiquidationError::UnstakeRequestExpired
+        );
+        Ok(self.unstake_shares)
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub authority: Pubkey,
+    pub guardian: Pubkey,
+    /// Halts opening positions and withdrawing collateral, everything that
+    /// adds risk at the oracle price.
+    pub opening_paused: bool,
+    pub liquidations_paused: bool,
+    pub pending_authority: Pubkey,
+}
+
+pub const MARGIN_TIER_COUNT: usize = 4;
+pub const MAX_SYMBOL_LEN: usize = 32;
+pub const MAX_CROSS_POSITIONS: usize = 8;
+pub const MAX_COLLATERAL_ASSETS: usize = 4;
+
+/// Positions with leverage up to `max_leverage` must keep
+/// `maintenance_margin_bps` of their notional as margin.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct MarginTier {
+    pub max_leverage: u16,
+    pub maintenance_margin_bps: u16,
+}
+
+/// Returned by `get_position_health`. Amounts and prices use 6 decimals.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PositionHealth {
+    pub price: u64,
+    /// Collateral after settling funding.
+    pub collateral: u64,
+    pub unrealized_pnl: i64,
+    /// Collateral plus unrealized PnL.
+    pub margin: i64,
+    pub maintenance_requirement: u64,
+    /// Margin over notional; `i64::MAX` for an empty position.
+    pub margin_ratio_bps: i64,
+    pub maintenance_margin_bps: u16,
+    /// Oracle price at which the position becomes liquidatable; 0 for an
+    /// empty position.
+    pub liquidation_price: u64,
+    pub is_liquidatable: bool,
+}
+
+/// How under-margined positions on a market are liquidated.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum LiquidationMode {
+    /// First caller of `liquidate_partial`/`liquidate_full` takes the fixed fee.
+    Direct,
+    /// `start_auction` opens a Dutch auction taken via `take_auction`.
+    Auction,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MarketParams {
+    pub oracle: Pubkey,
+    /// Program that must own `oracle` (the Pyth program on the cluster).
+    pub oracle_program: Pubkey,
+    /// Prices published more than this many slots ago are rejected.
+    pub max_staleness_slots: u64,
+    /// Prices whose confidence interval exceeds this share of the price are
+    /// rejected.
+    pub max_confidence_bps: u16,
+    pub max_leverage: u16,
+    /// Ordered by ascending `max_leverage`; the last tier must cover `max_leverage`.
+    /// Each tier's maintenance margin must be below the initial margin
+    /// (`1 / leverage`) at the highest leverage it allows.
+    pub margin_tiers: [MarginTier; MARGIN_TIER_COUNT],
+    /// Liquidation fee on positions right at the maintenance margin. Rises
+    /// linearly to `liquidation_fee_max_bps` as the margin ratio falls
+    /// `liquidation_fee_ramp_bps` further.
+    pub liquidation_fee_bps: u16,
+    pub liquidation_fee_max_bps: u16,
+    pub liquidation_fee_ramp_bps: u16,
+    /// Share of the liquidation fee paid to the insurance fund instead of the
+    /// liquidator. Auction takers keep their whole discount.
+    pub insurance_fund_share_bps: u16,
+    /// Max share of a position's size one partial liquidation may close.
+    pub close_factor_bps: u16,
+    /// Margin ratio a partial liquidation may restore a position to, and no
+    /// further. Must exceed every tier's maintenance margin and the reward.
+    pub target_margin_bps: u16,
+    /// Partials that would leave less than this size are rejected; the
+    /// position has to be liquidated in full instead.
+    pub min_position_size: u64,
+    pub liquidation_mode: LiquidationMode,
+    /// Auction discount grows linearly from the start to the max discount
+    /// over `auction_duration_slots`. Unused in `Direct` mode.
+    pub auction_start_discount_bps: u16,
+    pub auction_max_discount_bps: u16,
+    pub auction_duration_slots: u64,
+    /// Hourly funding rate, in bps of the price, paid by the heavier side
+    /// when open interest is entirely one-sided; scales with the skew.
+    pub max_funding_rate_bps: u16,
+    /// Collateral a new isolated position must post at least.
+    pub min_collateral: u64,
+    /// Largest size a single new position may open with.
+    pub max_position_size: u64,
+    /// Cap on each side's aggregate open interest; opens beyond it are
+    /// rejected.
+    pub max_open_interest: u64,
+    /// Cleared to delist the market: existing positions can still be managed
+    /// and liquidated, but no new ones are opened.
+    pub opening_enabled: bool,
+}
+
+impl MarketParams {
+    fn validate(&self) -> Result<()> {
+        require!(self.max_leverage >= 1, LiquidationError::InvalidMarketParams);
+        require!(
+            self.max_staleness_slots > 0
+                && self.max_confidence_bps > 0
+                && self.max_confidence_bps as u128 <= math::BPS_DENOMINATOR,
+            LiquidationError::InvalidMarketParams
+        );
+        require!(
+            self.liquidation_fee_bps <= self.liquidation_fee_max_bps
+                && self.liquidation_fee_max_bps as u128 <= math::BPS_DENOMINATOR
+                && self.liquidation_fee_ramp_bps > 0
+                && self.insurance_fund_share_bps as u128 <= math::BPS_DENOMINATOR,
+            LiquidationError::InvalidMarketParams
+        );
+
+        let mut previous_leverage = 0;
+        for tier in self.margin_tiers.iter() {
+            // Positions must open above maintenance: the initial margin at
+            // the tier's highest usable leverage is the smallest it allows
+            let max_leverage = tier.max_leverage.min(self.max_leverage);
+            require!(
+                tier.max_leverage > previous_leverage
+                    && tier.maintenance_margin_bps > 0
+                    && (tier.maintenance_margin_bps as u128) * (max_leverage as u128) < math::BPS_DENOMINATOR,
+                LiquidationError::InvalidMarketParams
+            );
+            previous_leverage = tier.max_leverage;
+        }
+        require!(previous_leverage >= self.max_leverage, LiquidationError::InvalidMarketParams);
+
+        require!(
+            self.close_factor_bps > 0 && self.close_factor_bps as u128 <= math::BPS_DENOMINATOR,
+            LiquidationError::InvalidMarketParams
+        );
+        let max_reward_bps = match self.liquidation_mode {
+            LiquidationMode::Direct => self.liquidation_fee_max_bps,
+            LiquidationMode::Auction => self.auction_max_discount_bps,
+        };
+        require!(
+            self.target_margin_bps > max_reward_bps
+                && self.target_margin_bps as u128 <= math::BPS_DENOMINATOR
+                && self
+                    .margin_tiers
+                    .iter()
+                    .all(|tier| self.target_margin_bps > tier.maintenance_margin_bps),
+            LiquidationError::InvalidMarketParams
+        );
+
+        require!(
+            self.max_funding_rate_bps as u128 <= math::BPS_DENOMINATOR,
+            LiquidationError::InvalidMarketParams
+        );
+        require!(
+            self.max_position_size > 0
+                && self.min_position_size <= self.max_position_size
+                && self.max_position_size <= self.max_open_interest,
+            LiquidationError::InvalidMarketParams
+        );
+
+        if self.liquidation_mode == LiquidationMode::Auction {
+            require!(
+                self.auction_start_discount_bps <= self.auction_max_discount_bps
+                    && self.auction_max_discount_bps as u128 <= math::BPS_DENOMINATOR
+                    && self.auction_duration_slots > 0,
+                LiquidationError::InvalidMarketParams
+            );
+        }
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Market {
+    #[max_len(32)]
+    pub symbol: String,
+    pub oracle: Pubkey,
+    pub oracle_program: Pubkey,
+    pub max_staleness_slots: u64,
+    pub max_confidence_bps: u16,
+    pub max_leverage: u16,
+    pub margin_tiers: [MarginTier; MARGIN_TIER_COUNT],
+    pub liquidation_fee_bps: u16,
+    pub liquidation_fee_max_bps: u16,
+    pub liquidation_fee_ramp_bps: u16,
+    pub insurance_fund_share_bps: u16,
+    pub close_factor_bps: u16,
+    pub target_margin_bps: u16,
+    pub min_position_size: u64,
+    pub liquidation_mode: LiquidationMode,
+    pub auction_start_discount_bps: u16,
+    pub auction_max_discount_bps: u16,
+    pub auction_duration_slots: u64,
+    pub max_funding_rate_bps: u16,
+    pub min_collateral: u64,
+    pub max_position_size: u64,
+    pub max_open_interest: u64,
+    pub opening_enabled: bool,
+    /// Funding paid per unit of long size since market creation, in price
+    /// units. Rises while longs outweigh shorts and falls while they are
+    /// paid.
+    pub long_funding_index: i128,
+    /// The same for short size. Each period the receiving side's index moves
+    /// by the paying side's total spread over its own open interest, so
+    /// payments net to zero.
+    pub short_funding_index: i128,
+    pub last_funding_update: i64,
+    pub open_interest_long: u64,
+    pub open_interest_short: u64,
+    /// Negative equity of bankrupt positions that the insurance fund could
+    /// not refill, and funding positions could not pay, awaiting recovery by
+    /// `auto_deleverage`.
+    pub uncovered_bad_debt: u64,
+    /// Bankruptcy price and side of the latest bankrupt position that added
+    /// to `uncovered_bad_debt`; positions on the other side are deleveraged.
+    pub bankruptcy_price: u64,
+    pub bankrupt_is_long: bool,
+}
+
+impl Market {
+    fn apply(&mut self, params: &MarketParams) {
+        self.oracle = params.oracle;
+        self.oracle_program = params.oracle_program;
+        self.max_staleness_slots = params.max_staleness_slots;
+        self.max_confidence_bps = params.max_confidence_bps;
+        self.max_leverage = params.max_leverage;
+        self.margin_tiers = params.margin_tiers;
+        self.liquidation_fee_bps = params.liquidation_fee_bps;
+        self.liquidation_fee_max_bps = params.liquidation_fee_max_bps;
+        self.liquidation_fee_ramp_bps = params.liquidation_fee_ramp_bps;
+        self.insurance_fund_share_bps = params.insurance_fund_share_bps;
+        self.close_factor_bps = params.close_factor_bps;
+        self.target_margin_bps = params.target_margin_bps;
+        self.min_position_size = params.min_position_size;
+        self.liquidation_mode = params.liquidation_mode;
+        self.auction_start_discount_bps = params.auction_start_discount_bps;
+        self.auction_max_discount_bps = params.auction_max_discount_bps;
+        self.auction_duration_slots = params.auction_duration_slots;
+        self.max_funding_rate_bps = params.max_funding_rate_bps;
+        self.min_collateral = params.min_collateral;
+        self.max_position_size = params.max_position_size;
+        self.max_open_interest = params.max_open_interest;
+        self.opening_enabled = params.opening_enabled;
+    }
+
+    pub fn maintenance_margin_bps(&self, leverage: u16) -> u16 {
+        self.margin_tiers
+            .iter()
+            .find(|tier| leverage <= tier.max_leverage)
+            .unwrap_or(&self.margin_tiers[MARGIN_TIER_COUNT - 1])
+            .maintenance_margin_bps
+    }
+
+    /// Liquidation fee rate for a position `margin_ratio_bps` of notional
+    /// against a maintenance margin of `maintenance_margin_bps`.
+    pub fn liquidation_fee_bps_at(&self, margin_ratio_bps: i128, maintenance_margin_bps: u16) -> u16 {
+        let shortfall_bps = (maintenance_margin_bps as i128 - margin_ratio_bps).max(0) as u128;
+        math::ramp_bps(
+            self.liquidation_fee_bps,
+            self.liquidation_fee_max_bps,
+            shortfall_bps,
+            self.liquidation_fee_ramp_bps,
+        )
+    }
+
+    /// Splits a liquidation penalty into the liquidator's reward and the
+    /// insurance fund's fee.
+    pub fn split_liquidation_fee(&self, penalty: u64) -> Result<(u64, u64)> {
+        let insurance_fee = math::apply_bps(penalty, self.insurance_fund_share_bps)?;
+        Ok((penalty - insurance_fee, insurance_fee))
+    }
+
+    pub fn funding_index(&self, is_long: bool) -> i128 {
+        if is_long {
+            self.long_funding_index
+        } else {
+            self.short_funding_index
+        }
+    }
+
+    /// Advances the funding indices to `now` at a rate proportional to the
+    /// open interest skew over the elapsed time. The heavier side pays; no
+    /// funding accrues while either side is empty, as nobody would receive
+    /// it.
+    fn accrue_funding(&mut self, price: u64, now: i64) -> Result<()> {
+        let elapsed = now.saturating_sub(self.last_funding_update);
+        if elapsed <= 0 {
+            return Ok(());
+        }
+        self.last_funding_update = now;
+
+        let (long, short) = (self.open_interest_long as i128, self.open_interest_short as i128);
+        if long == 0 || short == 0 {
+            return Ok(());
+        }
+        let longs_pay = long >= short;
+        let (paying, receiving) = if longs_pay { (long, short) } else { (short, long) };
+        // Full-skew funding for the period first, then scaled by the skew, so
+        // the intermediate stays well inside i128
+        let full_skew_funding = (price as i128 * self.max_funding_rate_bps as i128 * elapsed as i128)
+            / (math::BPS_DENOMINATOR as i128 * FUNDING_PERIOD as i128);
+        let paid_per_unit = full_skew_funding
+            .checked_mul(paying - receiving)
+            .ok_or(LiquidationError::MathOverflow)?
+            / (long + short);
+        // Rounded down, so receivers never get more than was paid
+        let received_per_unit = paid_per_unit
+            .checked_mul(paying)
+            .ok_or(LiquidationError::MathOverflow)?
+            / receiving;
+
+        let (paying_index, receiving_index) = if longs_pay {
+            (&mut self.long_funding_index, &mut self.short_funding_index)
+        } else {
+            (&mut self.short_funding_index, &mut self.long_funding_index)
+        };
+        *paying_index = paying_index
+            .checked_add(paid_per_unit)
+            .ok_or(LiquidationError::MathOverflow)?;
+        *receiving_index = receiving_index
+            .checked_sub(received_per_unit)
+            .ok_or(LiquidationError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Funding owed by `size` on one side since that side's index stood at
+    /// `last_funding_index`; negative when it is owed funding.
+    pub fn funding_owed(&self, size: u64, is_long: bool, last_funding_index: i128) -> Result<i128> {
+        let index_delta = self
+            .funding_index(is_long)
+            .checked_sub(last_funding_index)
+            .ok_or(LiquidationError::MathOverflow)?;
+        Ok(index_delta
+            .checked_mul(size as i128)
+            .ok_or(LiquidationError::MathOverflow)?
+            / math::SCALE as i128)
+    }
+
+    /// Checks `leverage` is between 1x and the market's maximum.
+    fn check_leverage(&self, leverage: u16) -> Result<()> {
+        require!(
+            leverage >= 1 && leverage <= self.max_leverage,
+            LiquidationError::InvalidLeverage
+        );
+        Ok(())
+    }
+
+    /// Adds a new position to the open interest after checking it against
+    /// the market's listing, size bounds and open-interest cap.
+    fn admit_position(&mut self, is_long: bool, size: u64) -> Result<()> {
+        require!(self.opening_enabled, LiquidationError::MarketOpeningDisabled);
+        require!(
+            size > 0 && size >= self.min_position_size && size <= self.max_position_size,
+            LiquidationError::PositionSizeOutOfBounds
+        );
+        self.add_open_interest(is_long, size)?;
+        let open_interest = if is_long { self.open_interest_long } else { self.open_interest_short };
+        require!(open_interest <= self.max_open_interest, LiquidationError::OpenInterestCapExceeded);
+        Ok(())
+    }
+
+    fn add_open_interest(&mut self, is_long: bool, size: u64) -> Result<()> {
+        if is_long {
+            self.open_interest_long = math::add(self.open_interest_long, size)?;
+        } else {
+            self.open_interest_short = math::add(self.open_interest_short, size)?;
+        }
+        Ok(())
+    }
+
+    /// Adds vault shortfall left by a bankruptcy at `bankruptcy_price`. A
+    /// later bankruptcy replaces the price and side used for deleveraging.
+    fn record_bad_debt(&mut self, amount: u64, bankruptcy_price: u64, is_long: bool) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        self.uncovered_bad_debt = math::add(self.uncovered_bad_debt, amount)?;
+        self.bankruptcy_price = bankruptcy_price;
+        self.bankrupt_is_long = is_long;
+        Ok(())
+    }
+
+    /// Adds vault shortfall from funding a position could not pay. No
+    /// position went bankrupt at a price, so the price and side used for
+    /// deleveraging stay those of the last bankruptcy.
+    fn record_unpaid_funding(&mut self, amount: u64) -> Result<()> {
+        self.uncovered_bad_debt = math::add(self.uncovered_bad_debt, amount)?;
+        Ok(())
+    }
+
+    /// Saturates, so reducing or closing a position never fails on the
+    /// open-interest counters.
+    fn remove_open_interest(&mut self, is_long: bool, size: u64) {
+        if is_long {
+            self.open_interest_long = self.open_interest_long.saturating_sub(size);
+        } else {
+            self.open_interest_short = self.open_interest_short.saturating_sub(size);
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Auction {
+    pub position: Pubkey,
+    pub market: Pubkey,
+    /// Paid the rent and receives it back when the auction closes.
+    pub keeper: Pubkey,
+    pub start_slot: u64,
+    pub start_discount_bps: u16,
+    pub max_discount_bps: u16,
+    pub duration_slots: u64,
+}
+
+impl Auction {
+    /// Discount in effect at `slot`: linear from the start to the max
+    /// discount over `duration_slots`, then held at the max.
+    pub fn discount_bps(&self, slot: u64) -> u16 {
+        if self.duration_slots == 0 {
+            return self.max_discount_bps;
+        }
+        let elapsed = slot.saturating_sub(self.start_slot).min(self.duration_slots);
+        let range = (self.max_discount_bps - self.start_discount_bps) as u64;
+        self.start_discount_bps + (range * elapsed / self.duration_slots) as u16
+    }
+}
+
+#[event]
+pub struct LiquidationEvent {
+    pub position_owner: Pubkey,
+    pub liquidator: Pubkey,
+    pub symbol: String,
+    pub liquidated_size: u64,
+    pub liquidation_price: u64,
+    pub margin_before: i64,
+    pub margin_after: i64,
+    pub liquidator_reward: u64,
+    pub insurance_fee: u64,
+    pub bad_debt: u64,
+    pub timestamp: i64,
+    pub is_full: bool,
+}
+
+#[event]
+pub struct PositionOpened {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub collateral: u64,
+    pub entry_price: u64,
+    pub leverage: u16,
+    pub maintenance_margin: u64,
+    pub last_funding_index: i128,
+    pub timestamp: i64,
+}
+
+/// Emitted whenever a position's size, collateral or funding index changes,
+/// with its full state afterwards.
+#[event]
+pub struct PositionModified {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub collateral: u64,
+    pub maintenance_margin: u64,
+    pub last_funding_index: i128,
+    pub size_reduced: u64,
+    pub collateral_change: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PositionClosed {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub is_long: bool,
+    pub closed_size: u64,
+    pub close_price: u64,
+    pub collateral_returned: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted on every change to the insurance fund balance.
+#[event]
+pub struct InsuranceFundChanged {
+    pub balance: u64,
+    pub change: i64,
+    pub total_contributions: u64,
+    pub total_bad_debt_covered: u64,
+    pub total_shares: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InsuranceStaked {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub total_shares: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UnstakeRequested {
+    pub owner: Pubkey,
+    pub shares: u64,
+    pub available_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InsuranceUnstaked {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub total_shares: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ContributionEvent {
+    pub contributor: Pubkey,
+    pub amount: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InsuranceWithdrawalEvent {
+    pub authority: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseStateChanged {
+    pub guardian: Pubkey,
+    pub opening_paused: bool,
+    pub liquidations_paused: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityProposed {
+    pub account: Pubkey,
+    pub authority: Pubkey,
+    pub proposed_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub account: Pubkey,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarginAccountLiquidated {
+    pub owner: Pubkey,
+    pub liquidator: Pubkey,
+    pub symbols: Vec<String>,
+    pub liquidated_notional: u64,
+    pub equity_before: i64,
+    pub maintenance_requirement: u64,
+    pub equity_after: i64,
+    pub liquidator_reward: u64,
+    pub insurance_fee: u64,
+    pub bad_debt: u64,
+    pub timestamp: i64,
+    pub is_full: bool,
+}
+
+#[event]
+pub struct CrossPositionOpened {
+    pub margin_account: Pubkey,
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub entry_price: u64,
+    pub leverage: u16,
+    pub last_funding_index: i128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CrossPositionClosed {
+    pub margin_account: Pubkey,
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub is_long: bool,
+    pub closed_size: u64,
+    pub close_price: u64,
+    /// PnL net of funding, realized into the account's collateral.
+    pub realized_pnl: i64,
+    pub collateral: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when quote collateral (the margin vault's mint) or a registered
+/// collateral asset is deposited into a margin account.
+#[event]
+pub struct MarginDeposited {
+    pub margin_account: Pubkey,
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    /// The account's balance of `mint` afterwards.
+    pub balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarginWithdrawn {
+    pub margin_account: Pubkey,
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    /// The account's balance of `mint` afterwards.
+    pub balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CollateralLiquidated {
+    pub owner: Pubkey,
+    pub liquidator: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub price: u64,
+    /// Quote tokens the liquidator paid into the account's collateral.
+    pub payment: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FundingUpdated {
+    pub symbol: String,
+    pub long_funding_index: i128,
+    pub short_funding_index: i128,
+    pub open_interest_long: u64,
+    pub open_interest_short: u64,
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuctionStarted {
+    pub position: Pubkey,
+    pub keeper: Pubkey,
+    pub symbol: String,
+    pub start_slot: u64,
+    pub start_discount_bps: u16,
+    pub max_discount_bps: u16,
+    pub duration_slots: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AutoDeleveraged {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub size_reduced: u64,
+    pub price: u64,
+    pub bankruptcy_price: u64,
+    pub recovered: u64,
+    pub remaining_bad_debt: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuctionCancelled {
+    pub position: Pubkey,
+    pub keeper: Pubkey,
+    pub timestamp: i64,
+}
+
+#[error_code]
+pub enum LiquidationError {
+    #[msg("Position is not liquidatable")]
+    NotLiquidatable,
+    #[msg("Invalid liquidation amount")]
+    InvalidLiquidationAmount,
+    #[msg("Oracle account is not a valid price feed")]
+    InvalidOracleAccount,
+    #[msg("Collateral mint must use 6 decimals")]
+    InvalidCollateralMint,
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Insurance fund withdrawal limit exceeded")]
+    WithdrawalLimitExceeded,
+    #[msg("Leverage is outside the market's allowed range")]
+    InvalidLeverage,
+    #[msg("Invalid market parameters")]
+    InvalidMarketParams,
+    #[msg("Remaining margin would fall below the initial margin requirement")]
+    InitialMarginBreached,
+    #[msg("Position equity is negative and must be liquidated")]
+    PositionUnderwater,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("This operation is paused")]
+    ProgramPaused,
+    #[msg("Position uses an old account layout; call migrate_position first")]
+    PositionNotMigrated,
+    #[msg("Position already uses the current account layout")]
+    PositionAlreadyMigrated,
+    #[msg("Instruction is not available in this market's liquidation mode")]
+    WrongLiquidationMode,
+    #[msg("Auctioned position is still liquidatable")]
+    AuctionStillActive,
+    #[msg("Liquidation amount exceeds the close factor or target margin")]
+    LiquidationAmountTooLarge,
+    #[msg("Partial liquidation would leave a position below the minimum size")]
+    RemainingSizeBelowMinimum,
+    #[msg("Market does not match the position's symbol")]
+    MarketMismatch,
+    #[msg("Remaining accounts must list each position's market and oracle, and its vault when settling")]
+    InvalidRemainingAccounts,
+    #[msg("Margin account already has a position in this market")]
+    CrossPositionExists,
+    #[msg("Margin account has no position in this market")]
+    CrossPositionNotFound,
+    #[msg("Margin account holds the maximum number of positions")]
+    TooManyCrossPositions,
+    #[msg("Signer is not the pending authority")]
+    NotPendingAuthority,
+    #[msg("Oracle price is older than the market allows")]
+    StaleOracle,
+    #[msg("Oracle confidence interval is wider than the market allows")]
+    OracleConfidenceTooWide,
+    #[msg("Market has no bad debt recoverable at the current price")]
+    NoUncoveredBadDebt,
+    #[msg("Position is not a profitable position opposite the bankruptcy")]
+    NotAdlCounterparty,
+    #[msg("Insurance fund is depleted; outstanding shares are worthless")]
+    InsuranceFundDepleted,
+    #[msg("Not enough insurance fund shares")]
+    InsufficientShares,
+    #[msg("Unstake cooldown has not passed")]
+    UnstakeCooldownActive,
+    #[msg("Market is not open for new positions")]
+    MarketOpeningDisabled,
+    #[msg("Position size is outside the market's bounds")]
+    PositionSizeOutOfBounds,
+    #[msg("Collateral is below the market minimum")]
+    CollateralBelowMinimum,
+    #[msg("Open interest cap reached for this side of the market")]
+    OpenInterestCapExceeded,
+    #[msg("Oracle price is worse than the limit price")]
+    SlippageExceeded,
+    #[msg("Invalid collateral asset parameters")]
+    InvalidCollateralParams,
+    #[msg("Collateral asset account does not match the deposit")]
+    CollateralAssetMismatch,
+    #[msg("Margin account holds the maximum number of collateral assets")]
+    TooManyCollateralAssets,
+    #[msg("Not enough of this collateral asset deposited")]
+    InsufficientCollateralBalance,
+    #[msg("Collateral assets must be liquidated before the account's bad debt")]
+    CollateralNotSeized,
+    #[msg("Insurance fund already uses the current account layout")]
+    InsuranceFundAlreadyMigrated,
+    #[msg("A partial liquidation can restore this position; full liquidation is not allowed")]
+    PartialLiquidationRequired,
+    #[msg("Unstake request was not redeemed in time; request it again")]
+    UnstakeRequestExpired,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insurance_fund(balance: u64) -> InsuranceFund {
+        InsuranceFund {
+            authority: Pubkey::default(),
+            mint: Pubkey::default(),
+            balance,
+            total_contributions: 0,
+            total_bad_debt_covered: 0,
+            utilization_ratio: 0,
+            withdrawal_window_start: 0,
+            withdrawn_in_window: 0,
+            pending_authority: Pubkey::default(),
+            total_shares: 0,
+            protocol_shares: 0,
+        }
+    }
+
+    /// 100 units long from $100 with $500 collateral, on a market closing at
+    /// most half a position per liquidation and restoring 5% margin.
+    fn partially_liquidatable() -> (Position, Market) {
+        let position = Position {
+            version: POSITION_VERSION,
+            owner: Pubkey::default(),
+            symbol: "SOL/USD".to_string(),
+            is_long: true,
+            size: 100_000_000,
+            collateral: 500_000_000,
+            entry_price: 100_000_000,
+            leverage: 20,
+            maintenance_margin: 250_000_000,
+            last_funding_index: 0,
+        };
+        let market = Market {
+            symbol: "SOL/USD".to_string(),
+            oracle: Pubkey::default(),
+            oracle_program: Pubkey::default(),
+            max_staleness_slots: 0,
+            max_confidence_bps: 0,
+            max_leverage: 20,
+            margin_tiers: [MarginTier { max_leverage: 20, maintenance_margin_bps: 250 }; MARGIN_TIER_COUNT],
+            liquidation_fee_bps: 100,
+            liquidation_fee_max_bps: 100,
+            liquidation_fee_ramp_bps: 0,
+            insurance_fund_share_bps: 0,
+            close_factor_bps: 5_000,
+            target_margin_bps: 500,
+            min_position_size: 0,
+            liquidation_mode: LiquidationMode::Direct,
+            auction_start_discount_bps: 0,
+            auction_max_discount_bps: 0,
+            auction_duration_slots: 0,
+            max_funding_rate_bps: 0,
+            min_collateral: 0,
+            max_position_size: 0,
+            max_open_interest: 0,
+            opening_enabled: true,
+            long_funding_index: 0,
+            short_funding_index: 0,
+            last_funding_update: 0,
+            open_interest_long: 0,
+            open_interest_short: 0,
+            uncovered_bad_debt: 0,
+            bankruptcy_price: 0,
+            bankrupt_is_long: false,
+        };
+        (position, market)
+    }
+
+    /// Valid parameters for a direct-liquidation market up to 20x.
+    fn market_params() -> MarketParams {
+        MarketParams {
+            oracle: Pubkey::default(),
+            oracle_program: Pubkey::default(),
+            max_staleness_slots: 25,
+            max_confidence_bps: 200,
+            max_leverage: 20,
+            margin_tiers: [
+                MarginTier { max_leverage: 5, maintenance_margin_bps: 500 },
+                MarginTier { max_leverage: 10, maintenance_margin_bps: 400 },
+                MarginTier { max_leverage: 15, maintenance_margin_bps: 300 },
+                MarginTier { max_leverage: 20, maintenance_margin_bps: 250 },
+            ],
+            liquidation_fee_bps: 100,
+            liquidation_fee_max_bps: 300,
+            liquidation_fee_ramp_bps: 500,
+            insurance_fund_share_bps: 2_000,
+            close_factor_bps: 5_000,
+            target_margin_bps: 1_000,
+            min_position_size: 1_000_000,
+            liquidation_mode: LiquidationMode::Direct,
+            auction_start_discount_bps: 0,
+            auction_max_discount_bps: 0,
+            auction_duration_slots: 0,
+            max_funding_rate_bps: 10,
+            min_collateral: 10_000_000,
+            max_position_size: 1_000_000_000,
+            max_open_interest: 10_000_000_000,
+            opening_enabled: true,
+        }
+    }
+
+    #[test]
+    fn market_params_accepted() {
+        market_params().validate().unwrap();
+    }
+
+    #[test]
+    fn maintenance_at_initial_margin_rejected() {
+        // 5% maintenance at 20x would open positions already liquidatable
+        let mut params = market_params();
+        params.margin_tiers[3].maintenance_margin_bps = 500;
+        assert_eq!(params.validate().unwrap_err(), LiquidationError::InvalidMarketParams.into());
+        params.margin_tiers[3].maintenance_margin_bps = 499;
+        params.validate().unwrap();
+
+        // Only leverage the market allows counts against a tier
+        params.max_leverage = 10;
+        params.margin_tiers[3].maintenance_margin_bps = 900;
+        params.validate().unwrap();
+    }
+
+    #[test]
+    fn tiers_out_of_order_rejected() {
+        let mut params = market_params();
+        params.margin_tiers.swap(1, 2);
+        assert_eq!(params.validate().unwrap_err(), LiquidationError::InvalidMarketParams.into());
+
+        // Tier bounds must strictly increase
+        let mut params = market_params();
+        params.margin_tiers[2].max_leverage = 10;
+        assert_eq!(params.validate().unwrap_err(), LiquidationError::InvalidMarketParams.into());
+
+        // The last tier must cover the market's max leverage
+        let mut params = market_params();
+        params.max_leverage = 25;
+        assert_eq!(params.validate().unwrap_err(), LiquidationError::InvalidMarketParams.into());
+    }
+
+    #[test]
+    fn zero_close_factor_rejected() {
+        let mut params = market_params();
+        params.close_factor_bps = 0;
+        assert_eq!(params.validate().unwrap_err(), LiquidationError::InvalidMarketParams.into());
+    }
+
+    #[test]
+    fn maintenance_margin_from_leverage_tier() {
+        let (_, mut market) = partially_liquidatable();
+        market.apply(&market_params());
+        assert_eq!(market.maintenance_margin_bps(1), 500);
+        assert_eq!(market.maintenance_margin_bps(5), 500);
+        assert_eq!(market.maintenance_margin_bps(6), 400);
+        assert_eq!(market.maintenance_margin_bps(15), 300);
+        assert_eq!(market.maintenance_margin_bps(20), 250);
+        // Beyond the last tier, the last tier applies
+        assert_eq!(market.maintenance_margin_bps(50), 250);
+    }
+
+    #[test]
+    fn full_settlement_leaves_positive_equity_with_owner() {
+        // $100 margin, $5 reward, $2 insurance fee
+        let mut fund = insurance_fund(1_000_000);
+        let settlement = settle_full_liquidation(100_000_000, 5_000_000, 2_000_000, &mut fund).unwrap();
+        assert_eq!(settlement.reward_from_collateral, 5_000_000);
+        assert_eq!(settlement.insurance_fee, 2_000_000);
+        assert_eq!(settlement.surplus, 93_000_000);
+        assert_eq!(settlement.bad_debt, 0);
+        assert_eq!(settlement.fund_to_liquidator, 0);
+        assert_eq!(settlement.fund_to_vault, 0);
+        assert_eq!(settlement.vault_shortfall, 0);
+        assert_eq!(fund.balance, 3_000_000);
+        assert_eq!(fund.total_contributions, 2_000_000);
+    }
+
+    #[test]
+    fn full_settlement_covers_reward_shortfall_from_fund() {
+        // $3 margin cannot pay the $5 reward, so no fee is charged either
+        let mut fund = insurance_fund(10_000_000);
+        let settlement = settle_full_liquidation(3_000_000, 5_000_000, 2_000_000, &mut fund).unwrap();
+        assert_eq!(settlement.reward_from_collateral, 3_000_000);
+        assert_eq!(settlement.fund_to_liquidator, 2_000_000);
+        assert_eq!(settlement.liquidator_reward().unwrap(), 5_000_000);
+        assert_eq!(settlement.insurance_fee, 0);
+        assert_eq!(settlement.surplus, 0);
+        assert_eq!(settlement.bad_debt, 2_000_000);
+        assert_eq!(settlement.fund_to_vault, 0);
+        assert_eq!(settlement.vault_shortfall, 0);
+        assert_eq!(fund.balance, 8_000_000);
+        assert_eq!(fund.total_bad_debt_covered, 2_000_000);
+        assert_eq!(fund.total_contributions, 0);
+    }
+
+    #[test]
+    fn full_settlement_refills_negative_equity() {
+        // -$4 margin: the whole reward and the hole in the vault are bad debt
+        let mut fund = insurance_fund(100_000_000);
+        let settlement = settle_full_liquidation(-4_000_000, 5_000_000, 2_000_000, &mut fund).unwrap();
+        assert_eq!(settlement.reward_from_collateral, 0);
+        assert_eq!(settlement.bad_debt, 9_000_000);
+        assert_eq!(settlement.fund_to_liquidator, 5_000_000);
+        assert_eq!(settlement.fund_to_vault, 4_000_000);
+        assert_eq!(settlement.vault_shortfall, 0);
+        assert_eq!(settlement.insurance_fee, 0);
+        assert_eq!(settlement.surplus, 0);
+        assert_eq!(fund.balance, 91_000_000);
+        assert_eq!(fund.total_bad_debt_covered, 9_000_000);
+    }
+
+    #[test]
+    fn full_settlement_records_vault_shortfall_when_fund_depleted() {
+        // The fund pays the liquidator first, then refills what it can
+        let mut fund = insurance_fund(6_000_000);
+        let settlement = settle_full_liquidation(-4_000_000, 5_000_000, 2_000_000, &mut fund).unwrap();
+        assert_eq!(settlement.bad_debt, 9_000_000);
+        assert_eq!(settlement.fund_to_liquidator, 5_000_000);
+        assert_eq!(settlement.fund_to_vault, 1_000_000);
+        assert_eq!(settlement.vault_shortfall, 3_000_000);
+        assert_eq!(fund.balance, 0);
+        assert_eq!(fund.total_bad_debt_covered, 6_000_000);
+    }
+
+    #[test]
+    fn long_liquidatable_only_below_maintenance() {
+        // $445 of collateral: at $98 the $245 margin is exactly the 2.5%
+        // maintenance margin on $9,800 of notional
+        let (mut position, market) = partially_liquidatable();
+        position.collateral = 445_000_000;
+        assert!(!is_liquidatable(&position, &market, 98_000_001).unwrap());
+        assert!(!is_liquidatable(&position, &market, 98_000_000).unwrap());
+        assert!(is_liquidatable(&position, &market, 97_999_999).unwrap());
+    }
+
+    #[test]
+    fn short_liquidatable_only_below_maintenance() {
+        // $455 of collateral: at $102 the $255 margin is exactly the 2.5%
+        // maintenance margin on $10,200 of notional
+        let (mut position, market) = partially_liquidatable();
+        position.is_long = false;
+        position.collateral = 455_000_000;
+        assert!(!is_liquidatable(&position, &market, 101_999_999).unwrap());
+        assert!(!is_liquidatable(&position, &market, 102_000_000).unwrap());
+        assert!(is_liquidatable(&position, &market, 102_000_001).unwrap());
+    }
+
+    #[test]
+    fn partial_liquidation_capped_by_close_factor() {
+        // At $97 margin is $200 against a $485 target: restoring it would
+        // take ~73 units, more than the 50% close factor allows
+        let (position, market) = partially_liquidatable();
+        assert_eq!(max_partial_liquidation(&position, &market, 97_000_000, 100).unwrap(), 50_000_000);
+    }
+
+    #[test]
+    fn partial_liquidation_capped_by_target_margin() {
+        // At $99 margin is $400 against a $495 target. Each unit of notional
+        // closed frees 5% of requirement and costs 1% in fee, so $2,375 of
+        // notional (~24 units) restores the target
+        let (position, market) = partially_liquidatable();
+        assert_eq!(max_partial_liquidation(&position, &market, 99_000_000, 100).unwrap(), 23_989_899);
+    }
+
+    #[test]
+    fn partial_liquidation_falls_back_to_close_factor_when_fee_exceeds_target() {
+        let (position, market) = partially_liquidatable();
+        assert_eq!(max_partial_liquidation(&position, &market, 99_000_000, 500).unwrap(), 50_000_000);
+    }
+
+    #[test]
+    fn partial_liquidation_closes_nothing_above_target() {
+        let (position, market) = partially_liquidatable();
+        assert_eq!(max_partial_liquidation(&position, &market, 100_000_000, 100).unwrap(), 0);
+    }
+
+    #[test]
+    fn full_liquidation_rejected_when_a_partial_restores_target() {
+        // At $99 ~24 units restore the target, within the close factor
+        let (position, market) = partially_liquidatable();
+        assert!(!full_liquidation_allowed(&position, &market, 99_000_000, 100).unwrap());
+    }
+
+    #[test]
+    fn full_liquidation_allowed_beyond_close_factor() {
+        // At $97 restoring the target takes ~73 units, and a 5% fee never can
+        let (position, market) = partially_liquidatable();
+        assert!(full_liquidation_allowed(&position, &market, 97_000_000, 100).unwrap());
+        assert!(full_liquidation_allowed(&position, &market, 99_000_000, 500).unwrap());
+    }
+
+    #[test]
+    fn full_liquidation_allowed_for_small_or_bankrupt_positions() {
+        let (position, mut market) = partially_liquidatable();
+        // At $94 the $600 loss exceeds the $500 collateral
+        assert!(full_liquidation_allowed(&position, &market, 94_000_000, 100).unwrap());
+
+        // Closing the ~24 units would leave less than the minimum behind
+        market.min_position_size = 80_000_000;
+        assert!(full_liquidation_allowed(&position, &market, 99_000_000, 100).unwrap());
+
+        market.min_position_size = position.size;
+        assert!(full_liquidation_allowed(&position, &market, 99_000_000, 100).unwrap());
+    }
+
+    #[test]
+    fn pending_authority_accepts_handover() {
+        let (old, new) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut fund = insurance_fund(0);
+        fund.authority = old;
+        fund.pending_authority = new;
+        let previous = accept_pending_authority(&mut fund.authority, &mut fund.pending_authority, new).unwrap();
+        assert_eq!(previous, old);
+        assert_eq!(fund.authority, new);
+        assert_eq!(fund.pending_authority, Pubkey::default());
+    }
+
+    #[test]
+    fn wrong_signer_cannot_accept_authority() {
+        let (old, new) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut fund = insurance_fund(0);
+        fund.authority = old;
+        fund.pending_authority = new;
+        for signer in [old, Pubkey::new_unique()] {
+            assert_eq!(
+                accept_pending_authority(&mut fund.authority, &mut fund.pending_authority, signer).unwrap_err(),
+                LiquidationError::NotPendingAuthority.into()
+            );
+        }
+        assert_eq!(fund.authority, old);
+        assert_eq!(fund.pending_authority, new);
+    }
+
+    #[test]
+    fn accept_without_proposal_rejected() {
+        let old = Pubkey::new_unique();
+        let mut fund = insurance_fund(0);
+        fund.authority = old;
+        for signer in [old, Pubkey::default()] {
+            assert_eq!(
+                accept_pending_authority(&mut fund.authority, &mut fund.pending_authority, signer).unwrap_err(),
+                LiquidationError::NotPendingAuthority.into()
+            );
+        }
+        assert_eq!(fund.authority, old);
+    }
+
+    #[test]
+    fn newer_proposal_replaces_older() {
+        let (old, first, second) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut fund = insurance_fund(0);
+        fund.authority = old;
+        fund.pending_authority = first;
+        fund.pending_authority = second;
+        assert_eq!(
+            accept_pending_authority(&mut fund.authority, &mut fund.pending_authority, first).unwrap_err(),
+            LiquidationError::NotPendingAuthority.into()
+        );
+        accept_pending_authority(&mut fund.authority, &mut fund.pending_authority, second).unwrap();
+        assert_eq!(fund.authority, second);
+    }
+
+    #[test]
+    fn withdrawal_up_to_limit_allowed() {
+        // 10% of a $1,000 fund per window, in any number of withdrawals
+        let mut fund = insurance_fund(1_000_000_000);
+        fund.record_withdrawal(60_000_000, 100_000).unwrap();
+        fund.record_withdrawal(40_000_000, 101_000).unwrap();
+        assert_eq!(fund.balance, 900_000_000);
+        assert_eq!(fund.withdrawn_in_window, 100_000_000);
+        assert_eq!(fund.withdrawal_window_start, 100_000);
+    }
+
+    #[test]
+    fn withdrawal_over_limit_rejected() {
+        let mut fund = insurance_fund(1_000_000_000);
+        assert_eq!(
+            fund.record_withdrawal(100_000_001, 100_000).unwrap_err(),
+            LiquidationError::WithdrawalLimitExceeded.into()
+        );
+
+        // The limit is against the balance at the start of the window, not
+        // what is left after earlier withdrawals
+        fund.record_withdrawal(90_000_000, 100_000).unwrap();
+        assert_eq!(
+            fund.record_withdrawal(10_000_001, 100_000).unwrap_err(),
+            LiquidationError::WithdrawalLimitExceeded.into()
+        );
+        assert_eq!(fund.balance, 910_000_000);
+        assert_eq!(fund.withdrawn_in_window, 90_000_000);
+    }
+
+    #[test]
+    fn withdrawal_limit_resets_after_window() {
+        let mut fund = insurance_fund(1_000_000_000);
+        fund.record_withdrawal(100_000_000, 100_000).unwrap();
+        let window_end = 100_000 + INSURANCE_WITHDRAWAL_WINDOW;
+        assert_eq!(
+            fund.record_withdrawal(1, window_end - 1).unwrap_err(),
+            LiquidationError::WithdrawalLimitExceeded.into()
+        );
+
+        // A new window allows 10% of the $900 left
+        fund.record_withdrawal(90_000_000, window_end).unwrap();
+        assert_eq!(fund.withdrawal_window_start, window_end);
+        assert_eq!(fund.withdrawn_in_window, 90_000_000);
+        assert_eq!(fund.balance, 810_000_000);
+    }
+
+    /// 2% rising to 10% over 100 slots from slot 1,000.
+    fn auction() -> Auction {
+        Auction {
+            position: Pubkey::default(),
+            market: Pubkey::default(),
+            keeper: Pubkey::default(),
+            start_slot: 1_000,
+            start_discount_bps: 200,
+            max_discount_bps: 1_000,
+            duration_slots: 100,
+        }
+    }
+
+    #[test]
+    fn auction_discount_starts_at_start_discount() {
+        assert_eq!(auction().discount_bps(1_000), 200);
+    }
+
+    #[test]
+    fn auction_discount_rises_linearly() {
+        let auction = auction();
+        assert_eq!(auction.discount_bps(1_050), 600);
+        // 8 bps per slot
+        assert_eq!(auction.discount_bps(1_001), 208);
+        assert_eq!(auction.discount_bps(1_099), 992);
+    }
+
+    #[test]
+    fn auction_discount_held_at_max_after_duration() {
+        let auction = auction();
+        assert_eq!(auction.discount_bps(1_100), 1_000);
+        assert_eq!(auction.discount_bps(5_000), 1_000);
+        assert_eq!(auction.discount_bps(u64::MAX), 1_000);
+    }
+
+    #[test]
+    fn auction_discount_before_start_is_start_discount() {
+        assert_eq!(auction().discount_bps(999), 200);
+        assert_eq!(auction().discount_bps(0), 200);
+    }
+
+    #[test]
+    fn liquidation_fee_split_by_insurance_share() {
+        let (_, mut market) = partially_liquidatable();
+        market.insurance_fund_share_bps = 2_000;
+        assert_eq!(market.split_liquidation_fee(10_000_000).unwrap(), (8_000_000, 2_000_000));
+    }
+
+    #[test]
+    fn liquidation_fee_split_rounds_in_favour_of_liquidator() {
+        // 30% of 7 base units is 2.1: the fund gets 2, the liquidator 5
+        let (_, mut market) = partially_liquidatable();
+        market.insurance_fund_share_bps = 3_000;
+        assert_eq!(market.split_liquidation_fee(7).unwrap(), (5, 2));
+        // Nothing is lost to rounding
+        let (reward, insurance_fee) = market.split_liquidation_fee(1_234_567).unwrap();
+        assert_eq!(reward + insurance_fee, 1_234_567);
+        assert_eq!(insurance_fee, 370_370);
+    }
+
+    #[test]
+    fn liquidation_fee_split_at_share_bounds() {
+        let (_, mut market) = partially_liquidatable();
+        market.insurance_fund_share_bps = 0;
+        assert_eq!(market.split_liquidation_fee(1_234_567).unwrap(), (1_234_567, 0));
+        market.insurance_fund_share_bps = 10_000;
+        assert_eq!(market.split_liquidation_fee(1_234_567).unwrap(), (0, 1_234_567));
+        assert_eq!(market.split_liquidation_fee(0).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn first_stake_into_empty_fund_mints_one_to_one() {
+        let mut fund = insurance_fund(0);
+        fund.credit_protocol_shares();
+        assert_eq!(fund.protocol_shares, 0);
+        assert_eq!(fund.shares_for_deposit(100_000_000).unwrap(), 100_000_000);
+    }
+
+    #[test]
+    fn first_stake_credits_existing_balance_to_protocol() {
+        let mut fund = insurance_fund(1_000_000_000);
+        fund.credit_protocol_shares();
+        assert_eq!(fund.total_shares, 1_000_000_000);
+        assert_eq!(fund.protocol_shares, 1_000_000_000);
+        assert_eq!(fund.shares_for_deposit(500_000_000).unwrap(), 500_000_000);
+
+        // Only the first stake credits the protocol
+        fund.balance = 1_500_000_000;
+        fund.total_shares = 1_500_000_000;
+        fund.credit_protocol_shares();
+        assert_eq!(fund.protocol_shares, 1_000_000_000);
+    }
+
+    #[test]
+    fn share_math_rounds_in_favour_of_the_fund() {
+        // Share price 1.5 after fees
+        let mut fund = insurance_fund(3_000_000);
+        fund.total_shares = 2_000_000;
+        assert_eq!(fund.shares_for_deposit(2).unwrap(), 1);
+        assert_eq!(fund.shares_for_deposit(1).unwrap(), 0);
+        assert_eq!(fund.value_of_shares(1).unwrap(), 1);
+        assert_eq!(fund.value_of_shares(2_000_000).unwrap(), 3_000_000);
+    }
+
+    #[test]
+    fn stake_into_depleted_fund_fails() {
+        let mut fund = insurance_fund(0);
+        fund.total_shares = 1_000_000;
+        assert!(fund.shares_for_deposit(1_000_000).is_err());
+        assert_eq!(fund.value_of_shares(1_000_000).unwrap(), 0);
+    }
+
+    fn unstake_request(shares: u64, requested_at: i64) -> InsuranceStake {
+        InsuranceStake {
+            owner: Pubkey::default(),
+            shares,
+            unstake_shares: shares,
+            unstake_requested_at: requested_at,
+        }
+    }
+
+    #[test]
+    fn unstake_before_cooldown_is_rejected() {
+        let stake = unstake_request(100, 1_000);
+        assert_eq!(
+            stake.redeemable_shares(1_000 + INSURANCE_UNSTAKE_COOLDOWN - 1).unwrap_err(),
+            LiquidationError::UnstakeCooldownActive.into()
+        );
+    }
+
+    #[test]
+    fn unstake_redeemable_within_window() {
+        let stake = unstake_request(100, 1_000);
+        let available_at = 1_000 + INSURANCE_UNSTAKE_COOLDOWN;
+        assert_eq!(stake.redeemable_shares(available_at).unwrap(), 100);
+        assert_eq!(stake.redeemable_shares(available_at + INSURANCE_UNSTAKE_WINDOW).unwrap(), 100);
+    }
+
+    #[test]
+    fn unstake_request_lapses_after_window() {
+        let stake = unstake_request(100, 1_000);
+        let expired_at = 1_000 + INSURANCE_UNSTAKE_COOLDOWN + INSURANCE_UNSTAKE_WINDOW + 1;
+        assert_eq!(
+            stake.redeemable_shares(expired_at).unwrap_err(),
+            LiquidationError::UnstakeRequestExpired.into()
+        );
+        assert_eq!(
+            unstake_request(0, 1_000).redeemable_shares(1_000 + INSURANCE_UNSTAKE_COOLDOWN).unwrap_err(),
+            LiquidationError::InvalidAmount.into()
+        );
+    }
+
+    #[test]
+    fn funding_payments_net_to_zero() {
+        let (_, mut market) = partially_liquidatable();
+        market.max_funding_rate_bps = 100;
+        market.open_interest_long = 300_000_000;
+        market.open_interest_short = 100_000_000;
+
+        // $1 per unit at full skew; half skewed, longs pay $0.50 per unit
+        market.accrue_funding(100_000_000, FUNDING_PERIOD).unwrap();
+        assert_eq!(market.long_funding_index, 500_000);
+        assert_eq!(market.short_funding_index, -1_500_000);
+        let paid = market.funding_owed(300_000_000, true, 0).unwrap();
+        let received = market.funding_owed(100_000_000, false, 0).unwrap();
+        assert_eq!(paid, 150_000_000);
+        assert_eq!(received, -150_000_000);
+
+        // Uneven sides round against the receivers, never creating collateral
+        market.open_interest_short = 70_000_000;
+        market.accrue_funding(100_000_000, 2 * FUNDING_PERIOD).unwrap();
+        let paid = market.funding_owed(300_000_000, true, 500_000).unwrap();
+        let received = market.funding_owed(70_000_000, false, -1_500_000).unwrap();
+        assert!(paid > 0 && paid + received >= 0 && paid + received <= 1);
+    }
+
+    #[test]
+    fn one_sided_market_accrues_no_funding() {
+        let (_, mut market) = partially_liquidatable();
+        market.max_funding_rate_bps = 100;
+        market.open_interest_long = 300_000_000;
+
+        market.accrue_funding(100_000_000, FUNDING_PERIOD).unwrap();
+        assert_eq!(market.long_funding_index, 0);
+        assert_eq!(market.short_funding_index, 0);
+        assert_eq!(market.last_funding_update, FUNDING_PERIOD);
+    }
+
+    #[test]
+    fn unpaid_funding_becomes_bad_debt() {
+        let (mut position, mut market) = partially_liquidatable();
+        // $6 per unit owed on 100 units against $500 of collateral
+        market.long_funding_index = 6_000_000;
+
+        // An earlier short went bankrupt at $110
+        market.bankruptcy_price = 110_000_000;
+        market.bankrupt_is_long = false;
+
+        settle_funding(&mut position, &mut market).unwrap();
+        assert_eq!(position.collateral, 0);
+        assert_eq!(position.last_funding_index, 6_000_000);
+        assert_eq!(market.uncovered_bad_debt, 100_000_000);
+        // No price was bankrupt here; deleveraging still targets the short's
+        assert_eq!(market.bankruptcy_price, 110_000_000);
+        assert!(!market.bankrupt_is_long);
+
+        // Settled funding is not charged again
+        settle_funding(&mut position, &mut market).unwrap();
+        assert_eq!(market.uncovered_bad_debt, 100_000_000);
+    }
+
+    #[test]
+    fn margin_account_fee_scales_with_account_shortfall() {
+        let (_, mut market) = partially_liquidatable();
+        market.liquidation_fee_max_bps = 300;
+        market.liquidation_fee_ramp_bps = 200;
+        let risk = |notional| cross_margin::PositionRisk {
+            market: market.clone(),
+            notional,
+            pnl: 0,
+            maintenance_requirement: notional / 40,
+            initial_requirement: notional / 20,
+        };
+        // $10,000 of notional across two positions: a 2.5% maintenance ratio
+        let mut health = cross_margin::AccountHealth {
+            equity: 250_000_000,
+            collateral_value: 0,
+            maintenance_requirement: 250_000_000,
+            initial_requirement: 500_000_000,
+            positions: vec![risk(6_000_000_000), risk(4_000_000_000)],
+        };
+        assert_eq!(health.liquidation_fee_bps(&market), 100);
+
+        // 1% below maintenance is halfway along the ramp
+        health.equity = 150_000_000;
+        assert_eq!(health.liquidation_fee_bps(&market), 200);
+
+        // Past the ramp, and bankrupt, the fee is capped
+        health.equity = 0;
+        assert_eq!(health.liquidation_fee_bps(&market), 300);
+        health.equity = -100_000_000;
+        assert_eq!(health.liquidation_fee_bps(&market), 300);
+    }
+
+    #[test]
+    fn leverage_must_be_between_one_and_market_max() {
+        let (_, market) = partially_liquidatable();
+        assert_eq!(market.check_leverage(0).unwrap_err(), LiquidationError::InvalidLeverage.into());
+        assert!(market.check_leverage(1).is_ok());
+        assert!(market.check_leverage(20).is_ok());
+        assert_eq!(market.check_leverage(21).unwrap_err(), LiquidationError::InvalidLeverage.into());
+    }
+
+    #[test]
+    fn admission_checks_position_size_bounds() {
+        let (_, mut market) = partially_liquidatable();
+        market.min_position_size = 1_000_000;
+        market.max_position_size = 50_000_000;
+        market.max_open_interest = u64::MAX;
+        let out_of_bounds: Error = LiquidationError::PositionSizeOutOfBounds.into();
+        assert_eq!(market.admit_position(true, 0).unwrap_err(), out_of_bounds);
+        assert_eq!(market.admit_position(true, 999_999).unwrap_err(), out_of_bounds);
+        assert_eq!(market.admit_position(true, 50_000_001).unwrap_err(), out_of_bounds);
+        assert_eq!(market.open_interest_long, 0);
+
+        assert!(market.admit_position(true, 1_000_000).is_ok());
+        assert!(market.admit_position(true, 50_000_000).is_ok());
+        assert_eq!(market.open_interest_long, 51_000_000);
+    }
+
+    #[test]
+    fn admission_caps_open_interest_per_side() {
+        let (_, mut market) = partially_liquidatable();
+        market.max_position_size = 100_000_000;
+        market.max_open_interest = 150_000_000;
+        assert!(market.admit_position(true, 100_000_000).is_ok());
+        assert!(market.admit_position(true, 50_000_000).is_ok());
+        assert_eq!(
+            market.admit_position(true, 1).unwrap_err(),
+            LiquidationError::OpenInterestCapExceeded.into()
+        );
+        // The short side has its own cap.
+        assert!(market.admit_position(false, 100_000_000).is_ok());
+        assert_eq!(market.open_interest_short, 100_000_000);
+    }
+
+    #[test]
+    fn admission_rejects_delisted_markets() {
+        let (_, mut market) = partially_liquidatable();
+        market.max_position_size = 100_000_000;
+        market.max_open_interest = 100_000_000;
+        market.opening_enabled = false;
+        assert_eq!(
+            market.admit_position(true, 1_000_000).unwrap_err(),
+            LiquidationError::MarketOpeningDisabled.into()
+        );
+        assert_eq!(market.open_interest_long, 0);
+    }
+
+    /// Counts 80% of the oracle value and sells to liquidators at 5% off.
+    fn collateral_asset_params() -> CollateralAssetParams {
+        CollateralAssetParams {
+            oracle: Pubkey::default(),
+            oracle_program: Pubkey::default(),
+            max_staleness_slots: 25,
+            max_confidence_bps: 200,
+            weight_bps: 8_000,
+            liquidation_discount_bps: 500,
+        }
+    }
+
+    fn collateral_asset(decimals: u8) -> CollateralAsset {
+        let mut asset = CollateralAsset {
+            mint: Pubkey::default(),
+            symbol: "SOL/USD".to_string(),
+            decimals,
+            oracle: Pubkey::default(),
+            oracle_program: Pubkey::default(),
+            max_staleness_slots: 0,
+            max_confidence_bps: 0,
+            weight_bps: 0,
+            liquidation_discount_bps: 0,
+        };
+        asset.apply(&collateral_asset_params());
+        asset
+    }
+
+    #[test]
+    fn collateral_asset_params_accepted() {
+        assert!(collateral_asset_params().validate().is_ok());
+
+        // The discount may take up the whole haircut, and a full-weight asset
+        // needs no haircut at all.
+        let mut params = collateral_asset_params();
+        params.liquidation_discount_bps = 2_000;
+        assert!(params.validate().is_ok());
+        params.weight_bps = 10_000;
+        params.liquidation_discount_bps = 0;
+        assert!(params.validate().is_ok());
+    }
+
+    #[test]
+    fn collateral_asset_params_rejected() {
+        let invalid: Error = LiquidationError::InvalidCollateralParams.into();
+        let cases: [fn(&mut CollateralAssetParams); 6] = [
+            |p| p.weight_bps = 10_001,
+            |p| p.liquidation_discount_bps = 2_001,
+            |p| {
+                p.weight_bps = 10_000;
+                p.liquidation_discount_bps = 1;
+            },
+            |p| p.max_staleness_slots = 0,
+            |p| p.max_confidence_bps = 0,
+            |p| p.max_confidence_bps = 10_001,
+        ];
+        for break_params in cases {
+            let mut params = collateral_asset_params();
+            break_params(&mut params);
+            assert_eq!(params.validate().unwrap_err(), invalid);
+        }
+    }
+
+    #[test]
+    fn collateral_value_scales_by_mint_decimals() {
+        // 2.5 SOL (9 decimals) at $100.
+        let asset = collateral_asset(9);
+        assert_eq!(asset.value(2_500_000_000, 100_000_000).unwrap(), 250_000_000);
+        assert_eq!(asset.weighted_value(2_500_000_000, 100_000_000).unwrap(), 200_000_000);
+
+        // The same amount of a 6-decimal token.
+        let asset = collateral_asset(6);
+        assert_eq!(asset.value(2_500_000, 100_000_000).unwrap(), 250_000_000);
+    }
+
+    #[test]
+    fn collateral_value_rounds_down() {
+        // 1 lamport at $100 is worth 1e-7 quote units: nothing.
+        let asset = collateral_asset(9);
+        assert_eq!(asset.value(1, 100_000_000).unwrap(), 0);
+        // 19 lamports at $100 are 1.9 units, counted as 1.
+        assert_eq!(asset.value(19, 100_000_000).unwrap(), 1);
+
+        // The haircut rounds down again: 80% of 9 units is 7.2, counted as 7.
+        let asset = collateral_asset(6);
+        assert_eq!(asset.value(9, 1_000_000).unwrap(), 9);
+        assert_eq!(asset.weighted_value(9, 1_000_000).unwrap(), 7);
+    }
+
+    #[test]
+    fn collateral_value_overflow_is_an_error() {
+        let asset = collateral_asset(0);
+        assert_eq!(
+            asset.value(u64::MAX, 2).unwrap_err(),
+            LiquidationError::MathOverflow.into()
+        );
+    }
 }
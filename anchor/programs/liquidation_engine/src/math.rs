@@ -0,0 +1,163 @@
+//! Checked fixed-point arithmetic.
+//!
+//! Sizes, prices and collateral amounts are all unsigned integers with
+//! `DECIMALS` implied decimals. Products are formed in u128/i128 and only
+//! narrowed back to 64 bits through the checked conversions below, so every
+//! overflow surfaces as `LiquidationError::MathOverflow` instead of a panic or
+//! a silent wrap.
+
+use anchor_lang::prelude::*;
+
+use crate::LiquidationError;
+
+pub const DECIMALS: u32 = 6;
+pub const SCALE: u128 = 10u128.pow(DECIMALS);
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+pub fn add(a: u64, b: u64) -> Result<u64> {
+    Ok(a.checked_add(b).ok_or(LiquidationError::MathOverflow)?)
+}
+
+pub fn sub(a: u64, b: u64) -> Result<u64> {
+    Ok(a.checked_sub(b).ok_or(LiquidationError::MathOverflow)?)
+}
+
+/// `value * numerator / denominator`, rounded down.
+pub fn mul_div(value: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    require!(denominator != 0, LiquidationError::MathOverflow);
+    to_u64(value as u128 * numerator as u128 / denominator as u128)
+}
+
+/// `value * numerator / denominator`, rounded up.
+pub fn mul_div_ceil(value: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    require!(denominator != 0, LiquidationError::MathOverflow);
+    to_u64((value as u128 * numerator as u128).div_ceil(denominator as u128))
+}
+
+/// `amount * bps / 10_000`, rounded down.
+pub fn apply_bps(amount: u64, bps: u16) -> Result<u64> {
+    mul_div(amount, bps as u64, BPS_DENOMINATOR as u64)
+}
+
+/// Interpolates linearly from `floor_bps` at a zero `shortfall_bps` to
+/// `cap_bps` once the shortfall reaches `range_bps`.
+pub fn ramp_bps(floor_bps: u16, cap_bps: u16, shortfall_bps: u128, range_bps: u16) -> u16 {
+    if range_bps == 0 || cap_bps <= floor_bps {
+        return floor_bps;
+    }
+    let progress = shortfall_bps.min(range_bps as u128);
+    floor_bps + ((cap_bps - floor_bps) as u128 * progress / range_bps as u128) as u16
+}
+
+/// Quote value of `size` base units at `price`.
+pub fn notional(size: u64, price: u64) -> Result<u64> {
+    mul_div(size, price, SCALE as u64)
+}
+
+/// PnL of `size` opened at `entry_price` and marked at `price`. Longs gain
+/// when the price rises above entry, shorts when it falls below.
+pub fn pnl(size: u64, entry_price: u64, price: u64, is_long: bool) -> Result<i128> {
+    let price_delta = if is_long {
+        price as i128 - entry_price as i128
+    } else {
+        entry_price as i128 - price as i128
+    };
+    let value = price_delta
+        .checked_mul(size as i128)
+        .ok_or(LiquidationError::MathOverflow)?;
+    Ok(value / SCALE as i128)
+}
+
+/// Narrows a signed intermediate to u64, rejecting negatives and overflow.
+pub fn to_u64<T: TryInto<u64>>(value: T) -> Result<u64> {
+    Ok(value.try_into().map_err(|_| LiquidationError::MathOverflow)?)
+}
+
+pub fn to_i64(value: i128) -> Result<i64> {
+    Ok(i64::try_from(value).map_err(|_| LiquidationError::MathOverflow)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notional_scales_by_decimals() {
+        // 100 units at $20 = $2,000
+        assert_eq!(notional(100_000_000, 20_000_000).unwrap(), 2_000_000_000);
+        assert_eq!(notional(0, u64::MAX).unwrap(), 0);
+        assert_eq!(notional(1, 999_999).unwrap(), 0);
+    }
+
+    #[test]
+    fn notional_handles_max_operands() {
+        // u64::MAX * SCALE / SCALE fits exactly
+        assert_eq!(notional(u64::MAX, SCALE as u64).unwrap(), u64::MAX);
+        // but u64::MAX * u64::MAX / SCALE does not
+        assert!(notional(u64::MAX, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn mul_div_rejects_zero_denominator() {
+        assert!(mul_div(1, 1, 0).is_err());
+        assert_eq!(mul_div(u64::MAX, 3, 3).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn mul_div_ceil_rounds_up() {
+        assert_eq!(mul_div_ceil(10, 1, 3).unwrap(), 4);
+        assert_eq!(mul_div_ceil(9, 1, 3).unwrap(), 3);
+        assert_eq!(mul_div_ceil(0, 1, 3).unwrap(), 0);
+        assert_eq!(mul_div_ceil(u64::MAX, 1, 1).unwrap(), u64::MAX);
+        assert!(mul_div_ceil(u64::MAX, 2, 1).is_err());
+        assert!(mul_div_ceil(1, 1, 0).is_err());
+    }
+
+    #[test]
+    fn apply_bps_rounds_down() {
+        assert_eq!(apply_bps(1_000_000, 250).unwrap(), 25_000);
+        assert_eq!(apply_bps(39, 250).unwrap(), 0);
+        assert_eq!(apply_bps(u64::MAX, 10_000).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn ramp_bps_is_clamped_between_floor_and_cap() {
+        assert_eq!(ramp_bps(100, 500, 0, 200), 100);
+        assert_eq!(ramp_bps(100, 500, 50, 200), 200);
+        assert_eq!(ramp_bps(100, 500, 200, 200), 500);
+        assert_eq!(ramp_bps(100, 500, u128::MAX, 200), 500);
+        assert_eq!(ramp_bps(100, 100, 50, 200), 100);
+        assert_eq!(ramp_bps(100, 500, 50, 0), 100);
+    }
+
+    #[test]
+    fn pnl_follows_direction() {
+        let size = 100_000_000;
+        assert_eq!(pnl(size, 20_000_000, 18_000_000, true).unwrap(), -200_000_000);
+        assert_eq!(pnl(size, 20_000_000, 18_000_000, false).unwrap(), 200_000_000);
+        assert_eq!(pnl(size, 20_000_000, 20_000_000, true).unwrap(), 0);
+    }
+
+    #[test]
+    fn pnl_overflow_is_reported() {
+        assert!(pnl(u64::MAX, 0, u64::MAX, true).is_err());
+        let large = pnl(u64::MAX, 0, u32::MAX as u64, false).unwrap();
+        assert_eq!(large, -(u64::MAX as i128 * u32::MAX as i128 / SCALE as i128));
+    }
+
+    #[test]
+    fn add_and_sub_are_checked() {
+        assert!(add(u64::MAX, 1).is_err());
+        assert!(sub(0, 1).is_err());
+        assert_eq!(sub(5, 5).unwrap(), 0);
+    }
+
+    #[test]
+    fn narrowing_conversions_are_checked() {
+        assert!(to_u64(-1i128).is_err());
+        assert!(to_u64(u64::MAX as i128 + 1).is_err());
+        assert_eq!(to_u64(u64::MAX as i128).unwrap(), u64::MAX);
+        assert!(to_i64(i64::MIN as i128 - 1).is_err());
+        assert_eq!(to_i64(i64::MIN as i128).unwrap(), i64::MIN);
+    }
+}
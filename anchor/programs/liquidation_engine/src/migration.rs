@@ -0,0 +1,124 @@
+//! Layouts of accounts written by the originally deployed program, kept only
+//! so `migrate_position` and `migrate_insurance_fund` can read them back.
+
+use anchor_lang::prelude::*;
+
+/// `Position` as first deployed: no version, direction or funding index,
+/// and sized exactly to its symbol, so the account length identifies it.
+#[derive(AnchorDeserialize)]
+pub struct BaselinePosition {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub size: u64,
+    pub collateral: u64,
+    pub entry_price: u64,
+    pub leverage: u16,
+    pub maintenance_margin: u64,
+}
+
+/// `InsuranceFund` as first deployed, before it had a mint and a vault.
+#[derive(AnchorDeserialize)]
+pub struct BaselineInsuranceFund {
+    pub authority: Pubkey,
+    pub balance: u64,
+    pub total_contributions: u64,
+    pub total_bad_debt_covered: u64,
+    pub utilization_ratio: u64,
+}
+
+// owner + symbol prefix + size, collateral, entry_price, leverage, maintenance_margin
+const BASELINE_POSITION_FIXED_LEN: usize = 32 + 4 + 8 + 8 + 8 + 2 + 8;
+const SYMBOL_LEN_OFFSET: usize = 32;
+
+// authority + balance, total_contributions, total_bad_debt_covered, utilization_ratio
+const BASELINE_INSURANCE_FUND_LEN: usize = 32 + 8 + 8 + 8 + 8;
+
+/// Decodes `data` (discriminator stripped) if it has the baseline layout.
+pub fn decode_baseline_position(data: &[u8]) -> Option<BaselinePosition> {
+    let symbol_len = data.get(SYMBOL_LEN_OFFSET..SYMBOL_LEN_OFFSET + 4)?;
+    let symbol_len = u32::from_le_bytes(symbol_len.try_into().unwrap()) as usize;
+    if data.len() != BASELINE_POSITION_FIXED_LEN + symbol_len {
+        return None;
+    }
+    BaselinePosition::try_from_slice(data).ok()
+}
+
+/// Address baseline positions were created at: their seeds had no side.
+pub fn baseline_position_address(owner: &Pubkey, symbol: &str) -> Pubkey {
+    Pubkey::find_program_address(&[b"position", owner.as_ref(), symbol.as_bytes()], &crate::ID).0
+}
+
+/// Decodes `data` (discriminator stripped) if it has the baseline layout.
+pub fn decode_baseline_insurance_fund(data: &[u8]) -> Option<BaselineInsuranceFund> {
+    if data.len() != BASELINE_INSURANCE_FUND_LEN {
+        return None;
+    }
+    BaselineInsuranceFund::try_from_slice(data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Baseline bytes: no direction, sized exactly to the symbol.
+    fn baseline_position(symbol: &str) -> Vec<u8> {
+        let mut data = vec![3u8; 32]; // owner
+        data.extend_from_slice(&(symbol.len() as u32).to_le_bytes());
+        data.extend_from_slice(symbol.as_bytes());
+        data.extend_from_slice(&100_000_000u64.to_le_bytes()); // size
+        data.extend_from_slice(&1_000_000_000u64.to_le_bytes()); // collateral
+        data.extend_from_slice(&20_000_000u64.to_le_bytes()); // entry_price
+        data.extend_from_slice(&10u16.to_le_bytes()); // leverage
+        data.extend_from_slice(&50_000_000u64.to_le_bytes()); // maintenance_margin
+        data
+    }
+
+    #[test]
+    fn baseline_positions_decode() {
+        let data = baseline_position("SOL");
+        assert_eq!(data.len(), 70 + 3);
+        let position = decode_baseline_position(&data).unwrap();
+        assert_eq!(position.owner, Pubkey::new_from_array([3; 32]));
+        assert_eq!(position.symbol, "SOL");
+        assert_eq!(position.size, 100_000_000);
+        assert_eq!(position.collateral, 1_000_000_000);
+        assert_eq!(position.entry_price, 20_000_000);
+        assert_eq!(position.leverage, 10);
+        assert_eq!(position.maintenance_margin, 50_000_000);
+    }
+
+    #[test]
+    fn current_positions_are_not_baseline() {
+        let position = crate::Position {
+            version: crate::POSITION_VERSION,
+            owner: Pubkey::new_from_array([3; 32]),
+            symbol: "SOL".to_string(),
+            is_long: true,
+            size: 100_000_000,
+            collateral: 1_000_000_000,
+            entry_price: 20_000_000,
+            leverage: 10,
+            maintenance_margin: 50_000_000,
+            last_funding_index: 0,
+        };
+        let mut data = position.try_to_vec().unwrap();
+        assert!(decode_baseline_position(&data).is_none());
+        data.resize(crate::Position::INIT_SPACE, 0);
+        assert!(decode_baseline_position(&data).is_none());
+    }
+
+    #[test]
+    fn baseline_insurance_fund_decodes_by_length() {
+        let mut data = vec![7u8; 32]; // authority
+        for value in [500u64, 700, 200, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let fund = decode_baseline_insurance_fund(&data).unwrap();
+        assert_eq!(fund.authority, Pubkey::new_from_array([7; 32]));
+        assert_eq!(fund.balance, 500);
+        assert_eq!(fund.total_contributions, 700);
+        assert_eq!(fund.total_bad_debt_covered, 200);
+
+        assert!(decode_baseline_insurance_fund(&[0u8; crate::InsuranceFund::INIT_SPACE]).is_none());
+    }
+}
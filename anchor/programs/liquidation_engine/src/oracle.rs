@@ -0,0 +1,268 @@
+use anchor_lang::prelude::*;
+
+use crate::{math, CollateralAsset, LiquidationError, Market};
+
+// Pyth v2 price account layout (little endian).
+const MAGIC: u32 = 0xa1b2c3d4;
+const VERSION_2: u32 = 2;
+const ACCOUNT_TYPE_PRICE: u32 = 3;
+const STATUS_TRADING: u32 = 1;
+
+const OFFSET_MAGIC: usize = 0;
+const OFFSET_VERSION: usize = 4;
+const OFFSET_ACCOUNT_TYPE: usize = 8;
+const OFFSET_EXPONENT: usize = 20;
+const OFFSET_AGG_PRICE: usize = 208;
+const OFFSET_AGG_CONF: usize = 216;
+const OFFSET_AGG_STATUS: usize = 224;
+const OFFSET_AGG_PUB_SLOT: usize = 232;
+const PRICE_ACCOUNT_MIN_LEN: usize = 240;
+
+/// Prices handed to the rest of the program are scaled by 10^PRICE_DECIMALS,
+/// the same scale used for `Position.entry_price`.
+pub const PRICE_DECIMALS: i32 = 6;
+
+pub struct OraclePrice {
+    pub price: u64,
+    pub conf: u64,
+    pub publish_slot: u64,
+}
+
+/// Reads the aggregate price from a Pyth price account and rescales it to
+/// `PRICE_DECIMALS`. Rejects accounts that are not price feeds or whose
+/// aggregate is not currently trading.
+pub fn load_price(oracle: &AccountInfo) -> Result<OraclePrice> {
+    let data = oracle.try_borrow_data()?;
+    require!(data.len() >= PRICE_ACCOUNT_MIN_LEN, LiquidationError::InvalidOracleAccount);
+    require!(read_u32(&data, OFFSET_MAGIC) == MAGIC, LiquidationError::InvalidOracleAccount);
+    require!(read_u32(&data, OFFSET_VERSION) == VERSION_2, LiquidationError::InvalidOracleAccount);
+    require!(
+        read_u32(&data, OFFSET_ACCOUNT_TYPE) == ACCOUNT_TYPE_PRICE,
+        LiquidationError::InvalidOracleAccount
+    );
+    require!(
+        read_u32(&data, OFFSET_AGG_STATUS) == STATUS_TRADING,
+        LiquidationError::InvalidOracleAccount
+    );
+
+    let exponent = read_i32(&data, OFFSET_EXPONENT);
+    let raw_price = read_i64(&data, OFFSET_AGG_PRICE);
+    require!(raw_price > 0, LiquidationError::InvalidOracleAccount);
+
+    let price = rescale(raw_price as u64, exponent)?;
+    let conf = rescale(read_u64(&data, OFFSET_AGG_CONF), exponent)?;
+    require!(price > 0, LiquidationError::InvalidOracleAccount);
+
+    Ok(OraclePrice {
+        price,
+        conf,
+        publish_slot: read_u64(&data, OFFSET_AGG_PUB_SLOT),
+    })
+}
+
+/// Loads the price of `market`'s feed. On top of `load_price`, rejects an
+/// account that is not the market's feed or not owned by its oracle program
+/// (`InvalidOracleAccount`), a price published more than
+/// `max_staleness_slots` ago (`StaleOracle`) and a confidence interval wider
+/// than `max_confidence_bps` of the price (`OracleConfidenceTooWide`).
+pub fn load_market_price(oracle: &AccountInfo, market: &Market) -> Result<u64> {
+    load_checked_price(
+        oracle,
+        &market.oracle,
+        &market.oracle_program,
+        market.max_staleness_slots,
+        market.max_confidence_bps,
+    )
+}
+
+/// Loads the price of a collateral asset's feed, with the same checks as
+/// `load_market_price` against the asset's own limits.
+pub fn load_collateral_price(oracle: &AccountInfo, asset: &CollateralAsset) -> Result<u64> {
+    load_checked_price(
+        oracle,
+        &asset.oracle,
+        &asset.oracle_program,
+        asset.max_staleness_slots,
+        asset.max_confidence_bps,
+    )
+}
+
+fn load_checked_price(
+    oracle: &AccountInfo,
+    feed: &Pubkey,
+    oracle_program: &Pubkey,
+    max_staleness_slots: u64,
+    max_confidence_bps: u16,
+) -> Result<u64> {
+    require_keys_eq!(oracle.key(), *feed, LiquidationError::InvalidOracleAccount);
+    require_keys_eq!(*oracle.owner, *oracle_program, LiquidationError::InvalidOracleAccount);
+    let price = load_price(oracle)?;
+    check_price(&price, Clock::get()?.slot, max_staleness_slots, max_confidence_bps)
+}
+
+/// Rejects a price published more than `max_staleness_slots` before
+/// `current_slot` or whose confidence interval is wider than
+/// `max_confidence_bps` of the price.
+fn check_price(
+    price: &OraclePrice,
+    current_slot: u64,
+    max_staleness_slots: u64,
+    max_confidence_bps: u16,
+) -> Result<u64> {
+    let age = current_slot.saturating_sub(price.publish_slot);
+    require!(age <= max_staleness_slots, LiquidationError::StaleOracle);
+    require!(
+        price.conf as u128 * math::BPS_DENOMINATOR
+            <= price.price as u128 * max_confidence_bps as u128,
+        LiquidationError::OracleConfidenceTooWide
+    );
+    Ok(price.price)
+}
+
+fn rescale(value: u64, exponent: i32) -> Result<u64> {
+    let shift = exponent + PRICE_DECIMALS;
+    let factor = 10u64
+        .checked_pow(shift.unsigned_abs())
+        .ok_or(LiquidationError::InvalidOracleAccount)?;
+    if shift >= 0 {
+        Ok(value.checked_mul(factor).ok_or(LiquidationError::InvalidOracleAccount)?)
+    } else {
+        Ok(value / factor)
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_i32(data: &[u8], offset: usize) -> i32 {
+    i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_i64(data: &[u8], offset: usize) -> i64 {
+    i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A trading price account: $20.50 with 5c confidence at exponent -8,
+    /// published at slot 1_000.
+    fn price_account() -> Vec<u8> {
+        let mut data = vec![0u8; PRICE_ACCOUNT_MIN_LEN];
+        data[OFFSET_MAGIC..OFFSET_MAGIC + 4].copy_from_slice(&MAGIC.to_le_bytes());
+        data[OFFSET_VERSION..OFFSET_VERSION + 4].copy_from_slice(&VERSION_2.to_le_bytes());
+        data[OFFSET_ACCOUNT_TYPE..OFFSET_ACCOUNT_TYPE + 4].copy_from_slice(&ACCOUNT_TYPE_PRICE.to_le_bytes());
+        data[OFFSET_EXPONENT..OFFSET_EXPONENT + 4].copy_from_slice(&(-8i32).to_le_bytes());
+        data[OFFSET_AGG_PRICE..OFFSET_AGG_PRICE + 8].copy_from_slice(&2_050_000_000i64.to_le_bytes());
+        data[OFFSET_AGG_CONF..OFFSET_AGG_CONF + 8].copy_from_slice(&5_000_000u64.to_le_bytes());
+        data[OFFSET_AGG_STATUS..OFFSET_AGG_STATUS + 4].copy_from_slice(&STATUS_TRADING.to_le_bytes());
+        data[OFFSET_AGG_PUB_SLOT..OFFSET_AGG_PUB_SLOT + 8].copy_from_slice(&1_000u64.to_le_bytes());
+        data
+    }
+
+    fn load(key: &Pubkey, owner: &Pubkey, data: &mut [u8]) -> Result<OraclePrice> {
+        let mut lamports = 0;
+        let info = AccountInfo::new(key, false, false, &mut lamports, data, owner, false, 0);
+        load_price(&info)
+    }
+
+    fn load_feed(key: &Pubkey, owner: &Pubkey, feed: &Pubkey, oracle_program: &Pubkey) -> Result<u64> {
+        let mut data = price_account();
+        let mut lamports = 0;
+        let info = AccountInfo::new(key, false, false, &mut lamports, &mut data, owner, false, 0);
+        // Both keys are checked before the clock is read
+        load_checked_price(&info, feed, oracle_program, 0, 0)
+    }
+
+    fn set_exponent(data: &mut [u8], exponent: i32) {
+        data[OFFSET_EXPONENT..OFFSET_EXPONENT + 4].copy_from_slice(&exponent.to_le_bytes());
+    }
+
+    #[test]
+    fn valid_price_is_rescaled_to_six_decimals() {
+        let price = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut price_account()).unwrap();
+        assert_eq!(price.price, 20_500_000);
+        assert_eq!(price.conf, 50_000);
+        assert_eq!(price.publish_slot, 1_000);
+    }
+
+    #[test]
+    fn exponents_above_the_price_scale_multiply() {
+        let mut data = price_account();
+        set_exponent(&mut data, -2);
+        let price = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut data).unwrap();
+        assert_eq!(price.price, 2_050_000_000 * 10_000);
+
+        set_exponent(&mut data, 1);
+        let price = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut data).unwrap();
+        assert_eq!(price.price, 2_050_000_000 * 10_000_000);
+        assert_eq!(price.conf, 5_000_000 * 10_000_000);
+    }
+
+    #[test]
+    fn exponents_below_the_price_scale_round_down() {
+        let mut data = price_account();
+        set_exponent(&mut data, -12);
+        let price = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut data).unwrap();
+        assert_eq!(price.price, 2_050);
+
+        // Too small to show at six decimals
+        set_exponent(&mut data, -16);
+        let result = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut data);
+        assert_eq!(result.err().unwrap(), LiquidationError::InvalidOracleAccount.into());
+    }
+
+    #[test]
+    fn non_trading_status_is_rejected() {
+        let mut data = price_account();
+        data[OFFSET_AGG_STATUS..OFFSET_AGG_STATUS + 4].copy_from_slice(&2u32.to_le_bytes());
+        let result = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut data);
+        assert_eq!(result.err().unwrap(), LiquidationError::InvalidOracleAccount.into());
+    }
+
+    #[test]
+    fn short_or_foreign_accounts_are_rejected() {
+        let mut data = price_account();
+        data.truncate(PRICE_ACCOUNT_MIN_LEN - 1);
+        let result = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut data);
+        assert_eq!(result.err().unwrap(), LiquidationError::InvalidOracleAccount.into());
+
+        let mut data = price_account();
+        data[OFFSET_MAGIC] ^= 0xff;
+        let result = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut data);
+        assert_eq!(result.err().unwrap(), LiquidationError::InvalidOracleAccount.into());
+    }
+
+    #[test]
+    fn wrong_feed_or_owner_is_rejected() {
+        let (feed, oracle_program) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let wrong_feed = load_feed(&Pubkey::new_unique(), &oracle_program, &feed, &oracle_program);
+        assert_eq!(wrong_feed.unwrap_err(), LiquidationError::InvalidOracleAccount.into());
+        let wrong_owner = load_feed(&feed, &Pubkey::new_unique(), &feed, &oracle_program);
+        assert_eq!(wrong_owner.unwrap_err(), LiquidationError::InvalidOracleAccount.into());
+    }
+
+    #[test]
+    fn stale_price_is_rejected() {
+        let price = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut price_account()).unwrap();
+        assert_eq!(check_price(&price, 1_025, 25, 100).unwrap(), 20_500_000);
+        assert_eq!(check_price(&price, 1_026, 25, 100).unwrap_err(), LiquidationError::StaleOracle.into());
+    }
+
+    #[test]
+    fn wide_confidence_is_rejected() {
+        // 5c on $20.50 is ~24 bps
+        let price = load(&Pubkey::new_unique(), &Pubkey::new_unique(), &mut price_account()).unwrap();
+        assert_eq!(check_price(&price, 1_000, 0, 25).unwrap(), 20_500_000);
+        assert_eq!(
+            check_price(&price, 1_000, 0, 24).unwrap_err(),
+            LiquidationError::OracleConfidenceTooWide.into()
+        );
+    }
+}
@@ -0,0 +1,1511 @@
+//! Checks the services' client against the program's own types: every
+//! account and event it decodes is written by the program's serializer, and
+//! every instruction it builds and error code it names must match the
+//! program's. A field or account added on one side only fails here.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, AccountSerialize, Event, InstructionData};
+use liquidation_engine::{
+    accounts as ix_accounts, instruction as ix, Auction, CollateralAsset, CollateralAssetParams,
+    CollateralBalance, Config, ContributionEvent, CrossPosition, InsuranceFund, InsuranceFundChanged,
+    InsuranceStake, InsuranceStaked, InsuranceUnstaked, InsuranceWithdrawalEvent, LiquidationError, LiquidationEvent,
+    LiquidationMode, MarginAccount, MarginAccountLiquidated, MarginTier, Market, MarketParams, Position,
+    PositionClosed, PositionHealth, PositionModified, PositionOpened, POSITION_VERSION,
+};
+use liquidation_engine_client::accounts::{
+    LiquidationMode as ClientLiquidationMode, OnChainAuction, OnChainCollateralAsset, OnChainCollateralBalance,
+    OnChainConfig, OnChainCrossPosition, OnChainInsuranceFund, OnChainInsuranceStake, OnChainMarginAccount,
+    OnChainMarginTier, OnChainMarket, OnChainPosition, ProgramAccount,
+};
+use liquidation_engine_client::errors::LiquidationError as ClientError;
+use liquidation_engine_client::events::{self as client_events, decode_event, ProgramEvent};
+use liquidation_engine_client::instructions::{self as client, OnChainPositionHealth, OpenPositionArgs};
+use liquidation_engine_client::{pda, TOKEN_PROGRAM_ID};
+
+const SYMBOL: &str = "SOL-PERP";
+
+fn serialize(account: &impl AccountSerialize) -> Vec<u8> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+/// Asserts `built` is the program's instruction `data` over `accounts`, and
+/// returns the remaining accounts appended after them.
+fn assert_instruction(built: Instruction, data: impl InstructionData, accounts: impl ToAccountMetas) -> Vec<AccountMeta> {
+    assert_eq!(built.program_id, liquidation_engine::ID);
+    assert_eq!(built.data, data.data());
+    let metas = accounts.to_account_metas(None);
+    assert_eq!(built.accounts[..metas.len()], metas[..]);
+    built.accounts[metas.len()..].to_vec()
+}
+
+fn decode(event: &impl Event) -> ProgramEvent {
+    decode_event(&event.data()).expect("client does not decode this event")
+}
+
+fn margin_tiers() -> ([MarginTier; 4], [OnChainMarginTier; 4]) {
+    let tiers = [(10, 500), (20, 250), (50, 100), (100, 50)];
+    (
+        tiers.map(|(max_leverage, maintenance_margin_bps)| MarginTier { max_leverage, maintenance_margin_bps }),
+        tiers.map(|(max_leverage, maintenance_margin_bps)| OnChainMarginTier { max_leverage, maintenance_margin_bps }),
+    )
+}
+
+fn assert_tiers(decoded: [OnChainMarginTier; 4], expected: [MarginTier; 4]) {
+    for (decoded, expected) in decoded.iter().zip(expected.iter()) {
+        let OnChainMarginTier { max_leverage, maintenance_margin_bps } = *decoded;
+        assert_eq!(max_leverage, expected.max_leverage);
+        assert_eq!(maintenance_margin_bps, expected.maintenance_margin_bps);
+    }
+}
+
+#[test]
+fn config_decodes() {
+    let config = Config {
+        authority: Pubkey::new_unique(),
+        guardian: Pubkey::new_unique(),
+        opening_paused: true,
+        liquidations_paused: false,
+        pending_authority: Pubkey::new_unique(),
+    };
+    let OnChainConfig { authority, guardian, opening_paused, liquidations_paused, pending_authority } =
+        OnChainConfig::decode(&serialize(&config)).unwrap();
+    assert_eq!(authority, config.authority);
+    assert_eq!(guardian, config.guardian);
+    assert_eq!(opening_paused, config.opening_paused);
+    assert_eq!(liquidations_paused, config.liquidations_paused);
+    assert_eq!(pending_authority, config.pending_authority);
+}
+
+#[test]
+fn insurance_fund_decodes() {
+    let fund = InsuranceFund {
+        authority: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        balance: 1,
+        total_contributions: 2,
+        total_bad_debt_covered: 3,
+        utilization_ratio: 4,
+        withdrawal_window_start: 5,
+        withdrawn_in_window: 6,
+        pending_authority: Pubkey::new_unique(),
+        total_shares: 7,
+        protocol_shares: 8,
+    };
+    let OnChainInsuranceFund {
+        authority,
+        mint,
+        balance,
+        total_contributions,
+        total_bad_debt_covered,
+        utilization_ratio,
+        withdrawal_window_start,
+        withdrawn_in_window,
+        pending_authority,
+        total_shares,
+        protocol_shares,
+    } = OnChainInsuranceFund::decode(&serialize(&fund)).unwrap();
+    assert_eq!(authority, fund.authority);
+    assert_eq!(mint, fund.mint);
+    assert_eq!(balance, fund.balance);
+    assert_eq!(total_contributions, fund.total_contributions);
+    assert_eq!(total_bad_debt_covered, fund.total_bad_debt_covered);
+    assert_eq!(utilization_ratio, fund.utilization_ratio);
+    assert_eq!(withdrawal_window_start, fund.withdrawal_window_start);
+    assert_eq!(withdrawn_in_window, fund.withdrawn_in_window);
+    assert_eq!(pending_authority, fund.pending_authority);
+    assert_eq!(total_shares, fund.total_shares);
+    assert_eq!(protocol_shares, fund.protocol_shares);
+}
+
+#[test]
+fn insurance_stake_decodes() {
+    let stake = InsuranceStake {
+        owner: Pubkey::new_unique(),
+        shares: 1,
+        unstake_shares: 2,
+        unstake_requested_at: 3,
+    };
+    let OnChainInsuranceStake { owner, shares, unstake_shares, unstake_requested_at } =
+        OnChainInsuranceStake::decode(&serialize(&stake)).unwrap();
+    assert_eq!(owner, stake.owner);
+    assert_eq!(shares, stake.shares);
+    assert_eq!(unstake_shares, stake.unstake_shares);
+    assert_eq!(unstake_requested_at, stake.unstake_requested_at);
+}
+
+#[test]
+fn market_decodes() {
+    let (program_tiers, _) = margin_tiers();
+    let market = Market {
+        symbol: SYMBOL.to_string(),
+        oracle: Pubkey::new_unique(),
+        oracle_program: Pubkey::new_unique(),
+        max_staleness_slots: 1,
+        max_confidence_bps: 2,
+        max_leverage: 3,
+        margin_tiers: program_tiers,
+        liquidation_fee_bps: 4,
+        liquidation_fee_max_bps: 5,
+        liquidation_fee_ramp_bps: 6,
+        insurance_fund_share_bps: 7,
+        close_factor_bps: 8,
+        target_margin_bps: 9,
+        min_position_size: 10,
+        liquidation_mode: LiquidationMode::Auction,
+        auction_start_discount_bps: 11,
+        auction_max_discount_bps: 12,
+        auction_duration_slots: 13,
+        max_funding_rate_bps: 14,
+        min_collateral: 15,
+        max_position_size: 16,
+        max_open_interest: 17,
+        opening_enabled: true,
+        long_funding_index: -18,
+        short_funding_index: 18,
+        last_funding_update: 19,
+        open_interest_long: 20,
+        open_interest_short: 21,
+        uncovered_bad_debt: 22,
+        bankruptcy_price: 23,
+        bankrupt_is_long: true,
+    };
+    let OnChainMarket {
+        symbol,
+        oracle,
+        oracle_program,
+        max_staleness_slots,
+        max_confidence_bps,
+        max_leverage,
+        margin_tiers,
+        liquidation_fee_bps,
+        liquidation_fee_max_bps,
+        liquidation_fee_ramp_bps,
+        insurance_fund_share_bps,
+        close_factor_bps,
+        target_margin_bps,
+        min_position_size,
+        liquidation_mode,
+        auction_start_discount_bps,
+        auction_max_discount_bps,
+        auction_duration_slots,
+        max_funding_rate_bps,
+        min_collateral,
+        max_position_size,
+        max_open_interest,
+        opening_enabled,
+        long_funding_index,
+        short_funding_index,
+        last_funding_update,
+        open_interest_long,
+        open_interest_short,
+        uncovered_bad_debt,
+        bankruptcy_price,
+        bankrupt_is_long,
+    } = OnChainMarket::decode(&serialize(&market)).unwrap();
+    assert_eq!(symbol, market.symbol);
+    assert_eq!(oracle, market.oracle);
+    assert_eq!(oracle_program, market.oracle_program);
+    assert_eq!(max_staleness_slots, market.max_staleness_slots);
+    assert_eq!(max_confidence_bps, market.max_confidence_bps);
+    assert_eq!(max_leverage, market.max_leverage);
+    assert_tiers(margin_tiers, market.margin_tiers);
+    assert_eq!(liquidation_fee_bps, market.liquidation_fee_bps);
+    assert_eq!(liquidation_fee_max_bps, market.liquidation_fee_max_bps);
+    assert_eq!(liquidation_fee_ramp_bps, market.liquidation_fee_ramp_bps);
+    assert_eq!(insurance_fund_share_bps, market.insurance_fund_share_bps);
+    assert_eq!(close_factor_bps, market.close_factor_bps);
+    assert_eq!(target_margin_bps, market.target_margin_bps);
+    assert_eq!(min_position_size, market.min_position_size);
+    assert_eq!(liquidation_mode, ClientLiquidationMode::Auction);
+    assert_eq!(auction_start_discount_bps, market.auction_start_discount_bps);
+    assert_eq!(auction_max_discount_bps, market.auction_max_discount_bps);
+    assert_eq!(auction_duration_slots, market.auction_duration_slots);
+    assert_eq!(max_funding_rate_bps, market.max_funding_rate_bps);
+    assert_eq!(min_collateral, market.min_collateral);
+    assert_eq!(max_position_size, market.max_position_size);
+    assert_eq!(max_open_interest, market.max_open_interest);
+    assert_eq!(opening_enabled, market.opening_enabled);
+    assert_eq!(long_funding_index, market.long_funding_index);
+    assert_eq!(short_funding_index, market.short_funding_index);
+    assert_eq!(last_funding_update, market.last_funding_update);
+    assert_eq!(open_interest_long, market.open_interest_long);
+    assert_eq!(open_interest_short, market.open_interest_short);
+    assert_eq!(uncovered_bad_debt, market.uncovered_bad_debt);
+    assert_eq!(bankruptcy_price, market.bankruptcy_price);
+    assert_eq!(bankrupt_is_long, market.bankrupt_is_long);
+}
+
+#[test]
+fn position_decodes() {
+    let position = Position {
+        version: POSITION_VERSION,
+        owner: Pubkey::new_unique(),
+        symbol: SYMBOL.to_string(),
+        is_long: true,
+        size: 1,
+        collateral: 2,
+        entry_price: 3,
+        leverage: 4,
+        maintenance_margin: 5,
+        last_funding_index: -6,
+    };
+    let OnChainPosition {
+        owner,
+        symbol,
+        is_long,
+        size,
+        collateral,
+        entry_price,
+        leverage,
+        maintenance_margin,
+        last_funding_index,
+    } = OnChainPosition::decode(&serialize(&position)).unwrap();
+    assert_eq!(owner, position.owner);
+    assert_eq!(symbol, position.symbol);
+    assert_eq!(is_long, position.is_long);
+    assert_eq!(size, position.size);
+    assert_eq!(collateral, position.collateral);
+    assert_eq!(entry_price, position.entry_price);
+    assert_eq!(leverage, position.leverage);
+    assert_eq!(maintenance_margin, position.maintenance_margin);
+    assert_eq!(last_funding_index, Some(position.last_funding_index));
+}
+
+#[test]
+fn margin_account_decodes() {
+    let account = MarginAccount {
+        owner: Pubkey::new_unique(),
+        collateral: 1,
+        positions: vec![CrossPosition {
+            symbol: SYMBOL.to_string(),
+            is_long: false,
+            size: 2,
+            entry_price: 3,
+            leverage: 4,
+            last_funding_index: -5,
+        }],
+        deposits: vec![CollateralBalance { mint: Pubkey::new_unique(), amount: 6 }],
+    };
+    let OnChainMarginAccount { owner, collateral, positions, deposits } =
+        OnChainMarginAccount::decode(&serialize(&account)).unwrap();
+    assert_eq!(owner, account.owner);
+    assert_eq!(collateral, account.collateral);
+
+    assert_eq!(positions.len(), account.positions.len());
+    let OnChainCrossPosition { symbol, is_long, size, entry_price, leverage, last_funding_index } =
+        positions[0].clone();
+    let expected = &account.positions[0];
+    assert_eq!(symbol, expected.symbol);
+    assert_eq!(is_long, expected.is_long);
+    assert_eq!(size, expected.size);
+    assert_eq!(entry_price, expected.entry_price);
+    assert_eq!(leverage, expected.leverage);
+    assert_eq!(last_funding_index, expected.last_funding_index);
+
+    assert_eq!(deposits.len(), account.deposits.len());
+    let OnChainCollateralBalance { mint, amount } = deposits[0].clone();
+    assert_eq!(mint, account.deposits[0].mint);
+    assert_eq!(amount, account.deposits[0].amount);
+}
+
+#[test]
+fn collateral_asset_decodes() {
+    let asset = CollateralAsset {
+        mint: Pubkey::new_unique(),
+        symbol: "wBTC".to_string(),
+        decimals: 8,
+        oracle: Pubkey::new_unique(),
+        oracle_program: Pubkey::new_unique(),
+        max_staleness_slots: 1,
+        max_confidence_bps: 2,
+        weight_bps: 3,
+        liquidation_discount_bps: 4,
+    };
+    let OnChainCollateralAsset {
+        mint,
+        symbol,
+        decimals,
+        oracle,
+        oracle_program,
+        max_staleness_slots,
+        max_confidence_bps,
+        weight_bps,
+        liquidation_discount_bps,
+    } = OnChainCollateralAsset::decode(&serialize(&asset)).unwrap();
+    assert_eq!(mint, asset.mint);
+    assert_eq!(symbol, asset.symbol);
+    assert_eq!(decimals, asset.decimals);
+    assert_eq!(oracle, asset.oracle);
+    assert_eq!(oracle_program, asset.oracle_program);
+    assert_eq!(max_staleness_slots, asset.max_staleness_slots);
+    assert_eq!(max_confidence_bps, asset.max_confidence_bps);
+    assert_eq!(weight_bps, asset.weight_bps);
+    assert_eq!(liquidation_discount_bps, asset.liquidation_discount_bps);
+}
+
+#[test]
+fn auction_decodes() {
+    let auction = Auction {
+        position: Pubkey::new_unique(),
+        market: Pubkey::new_unique(),
+        keeper: Pubkey::new_unique(),
+        start_slot: 1,
+        start_discount_bps: 2,
+        max_discount_bps: 3,
+        duration_slots: 4,
+    };
+    let OnChainAuction { position, market, keeper, start_slot, start_discount_bps, max_discount_bps, duration_slots } =
+        OnChainAuction::decode(&serialize(&auction)).unwrap();
+    assert_eq!(position, auction.position);
+    assert_eq!(market, auction.market);
+    assert_eq!(keeper, auction.keeper);
+    assert_eq!(start_slot, auction.start_slot);
+    assert_eq!(start_discount_bps, auction.start_discount_bps);
+    assert_eq!(max_discount_bps, auction.max_discount_bps);
+    assert_eq!(duration_slots, auction.duration_slots);
+}
+
+#[test]
+fn position_health_decodes() {
+    let health = PositionHealth {
+        price: 1,
+        collateral: 2,
+        unrealized_pnl: -3,
+        margin: -4,
+        maintenance_requirement: 5,
+        margin_ratio_bps: -6,
+        maintenance_margin_bps: 7,
+        liquidation_price: 8,
+        is_liquidatable: true,
+    };
+    let OnChainPositionHealth {
+        price,
+        collateral,
+        unrealized_pnl,
+        margin,
+        maintenance_requirement,
+        margin_ratio_bps,
+        maintenance_margin_bps,
+        liquidation_price,
+        is_liquidatable,
+    } = OnChainPositionHealth::decode(&health.try_to_vec().unwrap()).unwrap();
+    assert_eq!(price, health.price);
+    assert_eq!(collateral, health.collateral);
+    assert_eq!(unrealized_pnl, health.unrealized_pnl);
+    assert_eq!(margin, health.margin);
+    assert_eq!(maintenance_requirement, health.maintenance_requirement);
+    assert_eq!(margin_ratio_bps, health.margin_ratio_bps);
+    assert_eq!(maintenance_margin_bps, health.maintenance_margin_bps);
+    assert_eq!(liquidation_price, health.liquidation_price);
+    assert_eq!(is_liquidatable, health.is_liquidatable);
+}
+
+#[test]
+fn liquidation_event_decodes() {
+    let event = LiquidationEvent {
+        position_owner: Pubkey::new_unique(),
+        liquidator: Pubkey::new_unique(),
+        symbol: SYMBOL.to_string(),
+        liquidated_size: 1,
+        liquidation_price: 2,
+        margin_before: -3,
+        margin_after: -4,
+        liquidator_reward: 5,
+        insurance_fee: 6,
+        bad_debt: 7,
+        timestamp: 8,
+        is_full: true,
+    };
+    let ProgramEvent::Liquidation(client_events::LiquidationEvent {
+        position_owner,
+        liquidator,
+        symbol,
+        liquidated_size,
+        liquidation_price,
+        margin_before,
+        margin_after,
+        liquidator_reward,
+        insurance_fee,
+        bad_debt,
+        timestamp,
+        is_full,
+    }) = decode(&event)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(position_owner, event.position_owner);
+    assert_eq!(liquidator, event.liquidator);
+    assert_eq!(symbol, event.symbol);
+    assert_eq!(liquidated_size, event.liquidated_size);
+    assert_eq!(liquidation_price, event.liquidation_price);
+    assert_eq!(margin_before, event.margin_before);
+    assert_eq!(margin_after, event.margin_after);
+    assert_eq!(liquidator_reward, event.liquidator_reward);
+    assert_eq!(insurance_fee, event.insurance_fee);
+    assert_eq!(bad_debt, event.bad_debt);
+    assert_eq!(timestamp, event.timestamp);
+    assert_eq!(is_full, event.is_full);
+}
+
+#[test]
+fn margin_account_liquidated_decodes() {
+    let event = MarginAccountLiquidated {
+        owner: Pubkey::new_unique(),
+        liquidator: Pubkey::new_unique(),
+        symbols: vec![SYMBOL.to_string(), "BTC-PERP".to_string()],
+        liquidated_notional: 1,
+        equity_before: -2,
+        maintenance_requirement: 3,
+        equity_after: -4,
+        liquidator_reward: 5,
+        insurance_fee: 6,
+        bad_debt: 7,
+        timestamp: 8,
+        is_full: true,
+    };
+    let ProgramEvent::MarginAccountLiquidation(client_events::MarginAccountLiquidated {
+        owner,
+        liquidator,
+        symbols,
+        liquidated_notional,
+        equity_before,
+        maintenance_requirement,
+        equity_after,
+        liquidator_reward,
+        insurance_fee,
+        bad_debt,
+        timestamp,
+        is_full,
+    }) = decode(&event)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(owner, event.owner);
+    assert_eq!(liquidator, event.liquidator);
+    assert_eq!(symbols, event.symbols);
+    assert_eq!(liquidated_notional, event.liquidated_notional);
+    assert_eq!(equity_before, event.equity_before);
+    assert_eq!(maintenance_requirement, event.maintenance_requirement);
+    assert_eq!(equity_after, event.equity_after);
+    assert_eq!(liquidator_reward, event.liquidator_reward);
+    assert_eq!(insurance_fee, event.insurance_fee);
+    assert_eq!(bad_debt, event.bad_debt);
+    assert_eq!(timestamp, event.timestamp);
+    assert_eq!(is_full, event.is_full);
+}
+
+#[test]
+fn position_events_decode() {
+    let opened = PositionOpened {
+        position: Pubkey::new_unique(),
+        owner: Pubkey::new_unique(),
+        symbol: SYMBOL.to_string(),
+        is_long: true,
+        size: 1,
+        collateral: 2,
+        entry_price: 3,
+        leverage: 4,
+        maintenance_margin: 5,
+        last_funding_index: -6,
+        timestamp: 7,
+    };
+    let ProgramEvent::PositionOpened(client_events::PositionOpened {
+        position,
+        owner,
+        symbol,
+        is_long,
+        size,
+        collateral,
+        entry_price,
+        leverage,
+        maintenance_margin,
+        last_funding_index,
+        timestamp,
+    }) = decode(&opened)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(position, opened.position);
+    assert_eq!(owner, opened.owner);
+    assert_eq!(symbol, opened.symbol);
+    assert_eq!(is_long, opened.is_long);
+    assert_eq!(size, opened.size);
+    assert_eq!(collateral, opened.collateral);
+    assert_eq!(entry_price, opened.entry_price);
+    assert_eq!(leverage, opened.leverage);
+    assert_eq!(maintenance_margin, opened.maintenance_margin);
+    assert_eq!(last_funding_index, opened.last_funding_index);
+    assert_eq!(timestamp, opened.timestamp);
+
+    let modified = PositionModified {
+        position: Pubkey::new_unique(),
+        owner: Pubkey::new_unique(),
+        symbol: SYMBOL.to_string(),
+        is_long: false,
+        size: 1,
+        collateral: 2,
+        maintenance_margin: 3,
+        last_funding_index: -4,
+        size_reduced: 5,
+        collateral_change: -6,
+        timestamp: 7,
+    };
+    let ProgramEvent::PositionModified(client_events::PositionModified {
+        position,
+        owner,
+        symbol,
+        is_long,
+        size,
+        collateral,
+        maintenance_margin,
+        last_funding_index,
+        size_reduced,
+        collateral_change,
+        timestamp,
+    }) = decode(&modified)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(position, modified.position);
+    assert_eq!(owner, modified.owner);
+    assert_eq!(symbol, modified.symbol);
+    assert_eq!(is_long, modified.is_long);
+    assert_eq!(size, modified.size);
+    assert_eq!(collateral, modified.collateral);
+    assert_eq!(maintenance_margin, modified.maintenance_margin);
+    assert_eq!(last_funding_index, modified.last_funding_index);
+    assert_eq!(size_reduced, modified.size_reduced);
+    assert_eq!(collateral_change, modified.collateral_change);
+    assert_eq!(timestamp, modified.timestamp);
+
+    let closed = PositionClosed {
+        position: Pubkey::new_unique(),
+        owner: Pubkey::new_unique(),
+        symbol: SYMBOL.to_string(),
+        is_long: true,
+        closed_size: 1,
+        close_price: 2,
+        collateral_returned: 3,
+        timestamp: 4,
+    };
+    let ProgramEvent::PositionClosed(client_events::PositionClosed {
+        position,
+        owner,
+        symbol,
+        is_long,
+        closed_size,
+        close_price,
+        collateral_returned,
+        timestamp,
+    }) = decode(&closed)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(position, closed.position);
+    assert_eq!(owner, closed.owner);
+    assert_eq!(symbol, closed.symbol);
+    assert_eq!(is_long, closed.is_long);
+    assert_eq!(closed_size, closed.closed_size);
+    assert_eq!(close_price, closed.close_price);
+    assert_eq!(collateral_returned, closed.collateral_returned);
+    assert_eq!(timestamp, closed.timestamp);
+}
+
+#[test]
+fn insurance_events_decode() {
+    let changed = InsuranceFundChanged {
+        balance: 1,
+        change: -2,
+        total_contributions: 3,
+        total_bad_debt_covered: 4,
+        total_shares: 5,
+        timestamp: 6,
+    };
+    let ProgramEvent::InsuranceFundChanged(client_events::InsuranceFundChanged {
+        balance,
+        change,
+        total_contributions,
+        total_bad_debt_covered,
+        total_shares,
+        timestamp,
+    }) = decode(&changed)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(balance, changed.balance);
+    assert_eq!(change, changed.change);
+    assert_eq!(total_contributions, changed.total_contributions);
+    assert_eq!(total_bad_debt_covered, changed.total_bad_debt_covered);
+    assert_eq!(total_shares, changed.total_shares);
+    assert_eq!(timestamp, changed.timestamp);
+
+    let staked = InsuranceStaked {
+        owner: Pubkey::new_unique(),
+        amount: 1,
+        shares: 2,
+        total_shares: 3,
+        new_balance: 4,
+        timestamp: 5,
+    };
+    let ProgramEvent::Stake(client_events::InsuranceStaked { owner, amount, shares, total_shares, new_balance, timestamp }) =
+        decode(&staked)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(owner, staked.owner);
+    assert_eq!(amount, staked.amount);
+    assert_eq!(shares, staked.shares);
+    assert_eq!(total_shares, staked.total_shares);
+    assert_eq!(new_balance, staked.new_balance);
+    assert_eq!(timestamp, staked.timestamp);
+
+    let unstaked = InsuranceUnstaked {
+        owner: Pubkey::new_unique(),
+        amount: 1,
+        shares: 2,
+        total_shares: 3,
+        new_balance: 4,
+        timestamp: 5,
+    };
+    let ProgramEvent::Unstake(client_events::InsuranceUnstaked {
+        owner,
+        amount,
+        shares,
+        total_shares,
+        new_balance,
+        timestamp,
+    }) = decode(&unstaked)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(owner, unstaked.owner);
+    assert_eq!(amount, unstaked.amount);
+    assert_eq!(shares, unstaked.shares);
+    assert_eq!(total_shares, unstaked.total_shares);
+    assert_eq!(new_balance, unstaked.new_balance);
+    assert_eq!(timestamp, unstaked.timestamp);
+
+    let contribution = ContributionEvent {
+        contributor: Pubkey::new_unique(),
+        amount: 1,
+        new_balance: 2,
+        timestamp: 3,
+    };
+    let ProgramEvent::Contribution(client_events::ContributionEvent { contributor, amount, new_balance, timestamp }) =
+        decode(&contribution)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(contributor, contribution.contributor);
+    assert_eq!(amount, contribution.amount);
+    assert_eq!(new_balance, contribution.new_balance);
+    assert_eq!(timestamp, contribution.timestamp);
+
+    let withdrawal = InsuranceWithdrawalEvent {
+        authority: Pubkey::new_unique(),
+        destination: Pubkey::new_unique(),
+        amount: 1,
+        new_balance: 2,
+        timestamp: 3,
+    };
+    let ProgramEvent::InsuranceWithdrawal(client_events::InsuranceWithdrawalEvent {
+        authority,
+        destination,
+        amount,
+        new_balance,
+        timestamp,
+    }) = decode(&withdrawal)
+    else {
+        panic!("decoded as another event");
+    };
+    assert_eq!(authority, withdrawal.authority);
+    assert_eq!(destination, withdrawal.destination);
+    assert_eq!(amount, withdrawal.amount);
+    assert_eq!(new_balance, withdrawal.new_balance);
+    assert_eq!(timestamp, withdrawal.timestamp);
+}
+
+/// The same market parameters as the client and the program build them.
+fn market_params() -> (client::MarketParams, MarketParams) {
+    let (program_tiers, client_tiers) = margin_tiers();
+    let (oracle, oracle_program) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let client_params = client::MarketParams {
+        oracle,
+        oracle_program,
+        max_staleness_slots: 1,
+        max_confidence_bps: 2,
+        max_leverage: 3,
+        margin_tiers: client_tiers,
+        liquidation_fee_bps: 4,
+        liquidation_fee_max_bps: 5,
+        liquidation_fee_ramp_bps: 6,
+        insurance_fund_share_bps: 7,
+        close_factor_bps: 8,
+        target_margin_bps: 9,
+        min_position_size: 10,
+        liquidation_mode: ClientLiquidationMode::Auction,
+        auction_start_discount_bps: 11,
+        auction_max_discount_bps: 12,
+        auction_duration_slots: 13,
+        max_funding_rate_bps: 14,
+        min_collateral: 15,
+        max_position_size: 16,
+        max_open_interest: 17,
+        opening_enabled: true,
+    };
+    let program_params = MarketParams {
+        oracle,
+        oracle_program,
+        max_staleness_slots: 1,
+        max_confidence_bps: 2,
+        max_leverage: 3,
+        margin_tiers: program_tiers,
+        liquidation_fee_bps: 4,
+        liquidation_fee_max_bps: 5,
+        liquidation_fee_ramp_bps: 6,
+        insurance_fund_share_bps: 7,
+        close_factor_bps: 8,
+        target_margin_bps: 9,
+        min_position_size: 10,
+        liquidation_mode: LiquidationMode::Auction,
+        auction_start_discount_bps: 11,
+        auction_max_discount_bps: 12,
+        auction_duration_slots: 13,
+        max_funding_rate_bps: 14,
+        min_collateral: 15,
+        max_position_size: 16,
+        max_open_interest: 17,
+        opening_enabled: true,
+    };
+    (client_params, program_params)
+}
+
+fn collateral_asset_params() -> (client::CollateralAssetParams, CollateralAssetParams) {
+    let (oracle, oracle_program) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let client_params = client::CollateralAssetParams {
+        oracle,
+        oracle_program,
+        max_staleness_slots: 1,
+        max_confidence_bps: 2,
+        weight_bps: 3,
+        liquidation_discount_bps: 4,
+    };
+    let program_params = CollateralAssetParams {
+        oracle,
+        oracle_program,
+        max_staleness_slots: 1,
+        max_confidence_bps: 2,
+        weight_bps: 3,
+        liquidation_discount_bps: 4,
+    };
+    (client_params, program_params)
+}
+
+#[test]
+fn isolated_position_instructions_match() {
+    let (owner, owner_token_account, oracle) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let position = pda::position(&owner, SYMBOL, true);
+    let (market, vault, vault_authority) = (pda::market(SYMBOL), pda::vault(SYMBOL), pda::vault_authority());
+
+    let args = OpenPositionArgs {
+        symbol: SYMBOL.to_string(),
+        is_long: true,
+        size: 1,
+        collateral: 2,
+        limit_price: 3,
+        leverage: 4,
+    };
+    assert_instruction(
+        client::open_position(&owner, &owner_token_account, &oracle, args),
+        ix::OpenPosition { symbol: SYMBOL.to_string(), is_long: true, size: 1, collateral: 2, limit_price: 3, leverage: 4 },
+        ix_accounts::OpenPosition {
+            position,
+            market,
+            oracle,
+            config: pda::config(),
+            vault,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+        },
+    );
+    assert_instruction(
+        client::add_collateral(&owner, &position, SYMBOL, &owner_token_account, 5),
+        ix::AddCollateral { amount: 5 },
+        ix_accounts::AddCollateral {
+            position,
+            market,
+            vault,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    assert_instruction(
+        client::withdraw_collateral(&owner, &position, SYMBOL, &oracle, &owner_token_account, 6),
+        ix::WithdrawCollateral { amount: 6 },
+        ix_accounts::WithdrawCollateral {
+            position,
+            market,
+            oracle,
+            config: pda::config(),
+            vault,
+            vault_authority,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    let payer = Pubkey::new_unique();
+    assert_instruction(
+        client::migrate_position(&payer, &position, SYMBOL),
+        ix::MigratePosition {},
+        ix_accounts::MigratePosition { position, market, payer, system_program: system_program::ID },
+    );
+    assert_instruction(
+        client::reduce_position(&owner, &position, SYMBOL, &oracle, 7),
+        ix::ReducePosition { amount: 7 },
+        ix_accounts::ReducePosition { position, market, oracle, owner },
+    );
+    assert_instruction(
+        client::close_position(&owner, &position, SYMBOL, &oracle, &owner_token_account),
+        ix::ClosePosition {},
+        ix_accounts::ClosePosition {
+            position,
+            market,
+            oracle,
+            vault,
+            vault_authority,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+}
+
+#[test]
+fn insurance_instructions_match() {
+    let (authority, mint, destination) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let (insurance_fund, insurance_vault, vault_authority) =
+        (pda::insurance_fund(), pda::insurance_vault(), pda::vault_authority());
+
+    assert_instruction(
+        client::initialize_insurance_fund(&authority, &mint),
+        ix::InitializeInsuranceFund {},
+        ix_accounts::InitializeInsuranceFund {
+            insurance_fund,
+            insurance_vault,
+            mint,
+            vault_authority,
+            authority,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+    );
+    assert_instruction(
+        client::migrate_insurance_fund(&authority, &mint),
+        ix::MigrateInsuranceFund {},
+        ix_accounts::MigrateInsuranceFund {
+            insurance_fund,
+            insurance_vault,
+            mint,
+            vault_authority,
+            authority,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+    );
+    let new_authority = Pubkey::new_unique();
+    assert_instruction(
+        client::propose_insurance_fund_authority(&authority, &new_authority),
+        ix::ProposeInsuranceFundAuthority { new_authority },
+        ix_accounts::ProposeInsuranceFundAuthority { insurance_fund, authority },
+    );
+    assert_instruction(
+        client::accept_insurance_fund_authority(&new_authority),
+        ix::AcceptInsuranceFundAuthority {},
+        ix_accounts::AcceptInsuranceFundAuthority { insurance_fund, new_authority },
+    );
+    let (contributor, contributor_token_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+    assert_instruction(
+        client::contribute_to_insurance(&contributor, &contributor_token_account, 1),
+        ix::ContributeToInsurance { amount: 1 },
+        ix_accounts::ContributeToInsurance {
+            insurance_fund,
+            insurance_vault,
+            contributor_token_account,
+            contributor,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    assert_instruction(
+        client::withdraw_from_insurance(&authority, &destination, 2),
+        ix::WithdrawFromInsurance { amount: 2 },
+        ix_accounts::WithdrawFromInsurance {
+            insurance_fund,
+            insurance_vault,
+            vault_authority,
+            destination,
+            authority,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+
+    let (owner, owner_token_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let stake = pda::insurance_stake(&owner);
+    assert_instruction(
+        client::create_insurance_stake(&owner),
+        ix::CreateInsuranceStake {},
+        ix_accounts::CreateInsuranceStake { stake, owner, system_program: system_program::ID },
+    );
+    assert_instruction(
+        client::stake_insurance(&owner, &owner_token_account, 3),
+        ix::StakeInsurance { amount: 3 },
+        ix_accounts::StakeInsurance {
+            insurance_fund,
+            insurance_vault,
+            stake,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    assert_instruction(
+        client::request_unstake(&owner, 4),
+        ix::RequestUnstake { shares: 4 },
+        ix_accounts::RequestUnstake { stake, owner },
+    );
+    assert_instruction(
+        client::unstake_insurance(&owner, &owner_token_account),
+        ix::UnstakeInsurance {},
+        ix_accounts::UnstakeInsurance {
+            insurance_fund,
+            insurance_vault,
+            vault_authority,
+            stake,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+}
+
+#[test]
+fn config_instructions_match() {
+    let (authority, guardian, new_authority) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let config = pda::config();
+
+    assert_instruction(
+        client::initialize_config(&authority, &guardian),
+        ix::InitializeConfig { guardian },
+        ix_accounts::InitializeConfig { config, authority, system_program: system_program::ID },
+    );
+    assert_instruction(
+        client::propose_config_authority(&authority, &new_authority),
+        ix::ProposeConfigAuthority { new_authority },
+        ix_accounts::ProposeConfigAuthority { config, authority },
+    );
+    assert_instruction(
+        client::accept_config_authority(&new_authority),
+        ix::AcceptConfigAuthority {},
+        ix_accounts::AcceptConfigAuthority { config, new_authority },
+    );
+    assert_instruction(
+        client::set_guardian(&authority, &guardian),
+        ix::SetGuardian { guardian },
+        ix_accounts::SetGuardian { config, authority },
+    );
+    assert_instruction(
+        client::emergency_pause(&guardian, true, false),
+        ix::EmergencyPause { pause_opening: true, pause_liquidations: false },
+        ix_accounts::SetPauseState { config, guardian },
+    );
+    assert_instruction(
+        client::unpause(&guardian, false, true),
+        ix::Unpause { resume_opening: false, resume_liquidations: true },
+        ix_accounts::SetPauseState { config, guardian },
+    );
+}
+
+#[test]
+fn market_instructions_match() {
+    let (authority, mint, oracle) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let (config, market) = (pda::config(), pda::market(SYMBOL));
+
+    let (client_params, program_params) = market_params();
+    assert_instruction(
+        client::initialize_market(&authority, SYMBOL, client_params),
+        ix::InitializeMarket { symbol: SYMBOL.to_string(), params: program_params },
+        ix_accounts::InitializeMarket { config, market, authority, system_program: system_program::ID },
+    );
+    let (client_params, program_params) = market_params();
+    assert_instruction(
+        client::update_market(&authority, SYMBOL, client_params),
+        ix::UpdateMarket { params: program_params },
+        ix_accounts::UpdateMarket { config, market, authority },
+    );
+    assert_instruction(
+        client::update_funding(SYMBOL, &oracle),
+        ix::UpdateFunding {},
+        ix_accounts::UpdateFunding { market, oracle },
+    );
+    assert_instruction(
+        client::initialize_vault(&authority, SYMBOL, &mint),
+        ix::InitializeVault { _symbol: SYMBOL.to_string() },
+        ix_accounts::InitializeVault {
+            insurance_fund: pda::insurance_fund(),
+            vault: pda::vault(SYMBOL),
+            mint,
+            vault_authority: pda::vault_authority(),
+            authority,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+    );
+    assert_instruction(
+        client::initialize_margin_vault(&authority, &mint),
+        ix::InitializeMarginVault {},
+        ix_accounts::InitializeMarginVault {
+            insurance_fund: pda::insurance_fund(),
+            margin_vault: pda::margin_vault(),
+            mint,
+            vault_authority: pda::vault_authority(),
+            authority,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+    );
+}
+
+#[test]
+fn liquidation_instructions_match() {
+    let (liquidator, liquidator_token_account, oracle) =
+        (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let position = pda::position(&Pubkey::new_unique(), SYMBOL, false);
+    let (market, config, insurance_fund) = (pda::market(SYMBOL), pda::config(), pda::insurance_fund());
+    let (vault, insurance_vault, vault_authority) = (pda::vault(SYMBOL), pda::insurance_vault(), pda::vault_authority());
+
+    assert_instruction(
+        client::liquidate_partial(&liquidator, &position, SYMBOL, &oracle, &liquidator_token_account, 1),
+        ix::LiquidatePartial { liquidation_amount: 1 },
+        ix_accounts::LiquidatePartial {
+            position,
+            market,
+            config,
+            insurance_fund,
+            oracle,
+            vault,
+            insurance_vault,
+            vault_authority,
+            liquidator_token_account,
+            liquidator,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    assert_instruction(
+        client::liquidate_full(&liquidator, &position, SYMBOL, &oracle, &liquidator_token_account),
+        ix::LiquidateFull {},
+        ix_accounts::LiquidateFull {
+            position,
+            market,
+            config,
+            insurance_fund,
+            oracle,
+            vault,
+            insurance_vault,
+            vault_authority,
+            liquidator_token_account,
+            liquidator,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    assert_instruction(
+        client::get_position_health(&position, SYMBOL, &oracle),
+        ix::GetPositionHealth {},
+        ix_accounts::GetPositionHealth { position, market, oracle },
+    );
+
+    let keeper = Pubkey::new_unique();
+    let auction = pda::auction(&position);
+    assert_instruction(
+        client::start_auction(&keeper, &position, SYMBOL, &oracle),
+        ix::StartAuction {},
+        ix_accounts::StartAuction {
+            position,
+            market,
+            config,
+            oracle,
+            auction,
+            keeper,
+            system_program: system_program::ID,
+        },
+    );
+    assert_instruction(
+        client::take_auction(&liquidator, &position, SYMBOL, &oracle, &liquidator_token_account, &keeper, 2),
+        ix::TakeAuction { amount: 2 },
+        ix_accounts::TakeAuction {
+            position,
+            auction,
+            market,
+            config,
+            insurance_fund,
+            oracle,
+            vault,
+            insurance_vault,
+            vault_authority,
+            liquidator_token_account,
+            liquidator,
+            keeper,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    assert_instruction(
+        client::cancel_auction(&position, SYMBOL, &oracle, &keeper),
+        ix::CancelAuction {},
+        ix_accounts::CancelAuction { auction, position, market, oracle, keeper },
+    );
+
+    let authority = Pubkey::new_unique();
+    assert_instruction(
+        client::auto_deleverage(&authority, &position, SYMBOL, &oracle, 3),
+        ix::AutoDeleverage { amount: 3 },
+        ix_accounts::AutoDeleverage { config, position, market, oracle, authority },
+    );
+}
+
+#[test]
+fn margin_account_instructions_match() {
+    let (owner, owner_token_account, oracle) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let (margin_account, margin_vault, vault_authority) =
+        (pda::margin_account(&owner), pda::margin_vault(), pda::vault_authority());
+    let health_accounts = vec![
+        AccountMeta::new(pda::market(SYMBOL), false),
+        AccountMeta::new_readonly(oracle, false),
+    ];
+
+    assert_instruction(
+        client::create_margin_account(&owner),
+        ix::CreateMarginAccount {},
+        ix_accounts::CreateMarginAccount { margin_account, owner, system_program: system_program::ID },
+    );
+    assert_instruction(
+        client::deposit_margin(&owner, &owner_token_account, 1),
+        ix::DepositMargin { amount: 1 },
+        ix_accounts::DepositMargin {
+            margin_account,
+            margin_vault,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    let remaining = assert_instruction(
+        client::withdraw_margin(&owner, &owner_token_account, 2, health_accounts.clone()),
+        ix::WithdrawMargin { amount: 2 },
+        ix_accounts::WithdrawMargin {
+            margin_account,
+            config: pda::config(),
+            margin_vault,
+            vault_authority,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    assert_eq!(remaining, health_accounts);
+    let remaining = assert_instruction(
+        client::open_cross_position(&owner, SYMBOL, &oracle, true, 3, 4, health_accounts.clone()),
+        ix::OpenCrossPosition { symbol: SYMBOL.to_string(), is_long: true, size: 3, leverage: 4 },
+        ix_accounts::OpenCrossPosition {
+            margin_account,
+            market: pda::market(SYMBOL),
+            config: pda::config(),
+            oracle,
+            owner,
+        },
+    );
+    assert_eq!(remaining, health_accounts);
+    assert_instruction(
+        client::close_cross_position(&owner, SYMBOL, &oracle),
+        ix::CloseCrossPosition { symbol: SYMBOL.to_string() },
+        ix_accounts::CloseCrossPosition {
+            margin_account,
+            market: pda::market(SYMBOL),
+            oracle,
+            vault: pda::vault(SYMBOL),
+            margin_vault,
+            vault_authority,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+
+    let (liquidator, liquidator_token_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let account = OnChainMarginAccount {
+        owner,
+        collateral: 0,
+        positions: vec![OnChainCrossPosition {
+            symbol: SYMBOL.to_string(),
+            is_long: true,
+            size: 3,
+            entry_price: 5,
+            leverage: 4,
+            last_funding_index: 0,
+        }],
+        deposits: Vec::new(),
+    };
+    let remaining = assert_instruction(
+        client::liquidate_margin_account(
+            &liquidator,
+            &margin_account,
+            &account,
+            &liquidator_token_account,
+            health_accounts.clone(),
+        ),
+        ix::LiquidateMarginAccount {},
+        ix_accounts::LiquidateMarginAccount {
+            margin_account,
+            config: pda::config(),
+            insurance_fund: pda::insurance_fund(),
+            margin_vault,
+            insurance_vault: pda::insurance_vault(),
+            vault_authority,
+            liquidator_token_account,
+            liquidator,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    // Health accounts, then one market vault per position
+    let mut expected = health_accounts;
+    expected.push(AccountMeta::new(pda::vault(SYMBOL), false));
+    assert_eq!(remaining, expected);
+}
+
+#[test]
+fn collateral_asset_instructions_match() {
+    let (authority, owner, owner_token_account) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let (client_asset_params, program_asset_params) = collateral_asset_params();
+    let mint = Pubkey::new_unique();
+    let (config, collateral_asset, collateral_vault, vault_authority) = (
+        pda::config(),
+        pda::collateral_asset(&mint),
+        pda::collateral_vault(&mint),
+        pda::vault_authority(),
+    );
+
+    assert_instruction(
+        client::register_collateral_asset(&authority, &mint, "wBTC", client_asset_params),
+        ix::RegisterCollateralAsset { symbol: "wBTC".to_string(), params: program_asset_params },
+        ix_accounts::RegisterCollateralAsset {
+            config,
+            collateral_asset,
+            collateral_vault,
+            mint,
+            vault_authority,
+            authority,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+    );
+    let (client_asset_params, program_asset_params) = collateral_asset_params();
+    assert_instruction(
+        client::update_collateral_asset(&authority, &mint, client_asset_params),
+        ix::UpdateCollateralAsset { params: program_asset_params },
+        ix_accounts::UpdateCollateralAsset { config, collateral_asset, authority },
+    );
+
+    let margin_account = pda::margin_account(&owner);
+    assert_instruction(
+        client::deposit_collateral_asset(&owner, &mint, &owner_token_account, 1),
+        ix::DepositCollateralAsset { amount: 1 },
+        ix_accounts::DepositCollateralAsset {
+            margin_account,
+            collateral_asset,
+            collateral_vault,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    let health_accounts = vec![
+        AccountMeta::new_readonly(collateral_asset, false),
+        AccountMeta::new_readonly(Pubkey::new_unique(), false),
+    ];
+    let remaining = assert_instruction(
+        client::withdraw_collateral_asset(&owner, &mint, &owner_token_account, 2, health_accounts.clone()),
+        ix::WithdrawCollateralAsset { amount: 2 },
+        ix_accounts::WithdrawCollateralAsset {
+            margin_account,
+            collateral_asset,
+            config: pda::config(),
+            collateral_vault,
+            vault_authority,
+            owner_token_account,
+            owner,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    assert_eq!(remaining, health_accounts);
+
+    let asset = OnChainCollateralAsset {
+        mint,
+        symbol: "wBTC".to_string(),
+        decimals: 8,
+        oracle: Pubkey::new_unique(),
+        oracle_program: Pubkey::new_unique(),
+        max_staleness_slots: 1,
+        max_confidence_bps: 2,
+        weight_bps: 3,
+        liquidation_discount_bps: 4,
+    };
+    let (liquidator, liquidator_asset_account, liquidator_quote_account) =
+        (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let remaining = assert_instruction(
+        client::liquidate_collateral(
+            &liquidator,
+            &margin_account,
+            &asset,
+            &liquidator_asset_account,
+            &liquidator_quote_account,
+            3,
+            health_accounts.clone(),
+        ),
+        ix::LiquidateCollateral { amount: 3 },
+        ix_accounts::LiquidateCollateral {
+            margin_account,
+            config,
+            collateral_asset,
+            oracle: asset.oracle,
+            collateral_vault,
+            margin_vault: pda::margin_vault(),
+            vault_authority,
+            liquidator_asset_account,
+            liquidator_quote_account,
+            liquidator,
+            token_program: TOKEN_PROGRAM_ID,
+        },
+    );
+    assert_eq!(remaining, health_accounts);
+}
+
+/// Both matches are exhaustive, so a variant added on either side fails to
+/// compile until the other side has it too.
+fn client_error(error: LiquidationError) -> ClientError {
+    match error {
+        LiquidationError::NotLiquidatable => ClientError::NotLiquidatable,
+        LiquidationError::InvalidLiquidationAmount => ClientError::InvalidLiquidationAmount,
+        LiquidationError::InvalidOracleAccount => ClientError::InvalidOracleAccount,
+        LiquidationError::InvalidCollateralMint => ClientError::InvalidCollateralMint,
+        LiquidationError::InvalidAmount => ClientError::InvalidAmount,
+        LiquidationError::WithdrawalLimitExceeded => ClientError::WithdrawalLimitExceeded,
+        LiquidationError::InvalidLeverage => ClientError::InvalidLeverage,
+        LiquidationError::InvalidMarketParams => ClientError::InvalidMarketParams,
+        LiquidationError::InitialMarginBreached => ClientError::InitialMarginBreached,
+        LiquidationError::PositionUnderwater => ClientError::PositionUnderwater,
+        LiquidationError::MathOverflow => ClientError::MathOverflow,
+        LiquidationError::ProgramPaused => ClientError::ProgramPaused,
+        LiquidationError::PositionNotMigrated => ClientError::PositionNotMigrated,
+        LiquidationError::PositionAlreadyMigrated => ClientError::PositionAlreadyMigrated,
+        LiquidationError::WrongLiquidationMode => ClientError::WrongLiquidationMode,
+        LiquidationError::AuctionStillActive => ClientError::AuctionStillActive,
+        LiquidationError::LiquidationAmountTooLarge => ClientError::LiquidationAmountTooLarge,
+        LiquidationError::RemainingSizeBelowMinimum => ClientError::RemainingSizeBelowMinimum,
+        LiquidationError::MarketMismatch => ClientError::MarketMismatch,
+        LiquidationError::InvalidRemainingAccounts => ClientError::InvalidRemainingAccounts,
+        LiquidationError::CrossPositionExists => ClientError::CrossPositionExists,
+        LiquidationError::CrossPositionNotFound => ClientError::CrossPositionNotFound,
+        LiquidationError::TooManyCrossPositions => ClientError::TooManyCrossPositions,
+        LiquidationError::NotPendingAuthority => ClientError::NotPendingAuthority,
+        LiquidationError::StaleOracle => ClientError::StaleOracle,
+        LiquidationError::OracleConfidenceTooWide => ClientError::OracleConfidenceTooWide,
+        LiquidationError::NoUncoveredBadDebt => ClientError::NoUncoveredBadDebt,
+        LiquidationError::NotAdlCounterparty => ClientError::NotAdlCounterparty,
+        LiquidationError::InsuranceFundDepleted => ClientError::InsuranceFundDepleted,
+        LiquidationError::InsufficientShares => ClientError::InsufficientShares,
+        LiquidationError::UnstakeCooldownActive => ClientError::UnstakeCooldownActive,
+        LiquidationError::MarketOpeningDisabled => ClientError::MarketOpeningDisabled,
+        LiquidationError::PositionSizeOutOfBounds => ClientError::PositionSizeOutOfBounds,
+        LiquidationError::CollateralBelowMinimum => ClientError::CollateralBelowMinimum,
+        LiquidationError::OpenInterestCapExceeded => ClientError::OpenInterestCapExceeded,
+        LiquidationError::SlippageExceeded => ClientError::SlippageExceeded,
+        LiquidationError::InvalidCollateralParams => ClientError::InvalidCollateralParams,
+        LiquidationError::CollateralAssetMismatch => ClientError::CollateralAssetMismatch,
+        LiquidationError::TooManyCollateralAssets => ClientError::TooManyCollateralAssets,
+        LiquidationError::InsufficientCollateralBalance => ClientError::InsufficientCollateralBalance,
+        LiquidationError::CollateralNotSeized => ClientError::CollateralNotSeized,
+        LiquidationError::InsuranceFundAlreadyMigrated => ClientError::InsuranceFundAlreadyMigrated,
+        LiquidationError::PartialLiquidationRequired => ClientError::PartialLiquidationRequired,
+        LiquidationError::UnstakeRequestExpired => ClientError::UnstakeRequestExpired,
+    }
+}
+
+fn program_error(error: ClientError) -> LiquidationError {
+    match error {
+        ClientError::NotLiquidatable => LiquidationError::NotLiquidatable,
+        ClientError::InvalidLiquidationAmount => LiquidationError::InvalidLiquidationAmount,
+        ClientError::InvalidOracleAccount => LiquidationError::InvalidOracleAccount,
+        ClientError::InvalidCollateralMint => LiquidationError::InvalidCollateralMint,
+        ClientError::InvalidAmount => LiquidationError::InvalidAmount,
+        ClientError::WithdrawalLimitExceeded => LiquidationError::WithdrawalLimitExceeded,
+        ClientError::InvalidLeverage => LiquidationError::InvalidLeverage,
+        ClientError::InvalidMarketParams => LiquidationError::InvalidMarketParams,
+        ClientError::InitialMarginBreached => LiquidationError::InitialMarginBreached,
+        ClientError::PositionUnderwater => LiquidationError::PositionUnderwater,
+        ClientError::MathOverflow => LiquidationError::MathOverflow,
+        ClientError::ProgramPaused => LiquidationError::ProgramPaused,
+        ClientError::PositionNotMigrated => LiquidationError::PositionNotMigrated,
+        ClientError::PositionAlreadyMigrated => LiquidationError::PositionAlreadyMigrated,
+        ClientError::WrongLiquidationMode => LiquidationError::WrongLiquidationMode,
+        ClientError::AuctionStillActive => LiquidationError::AuctionStillActive,
+        ClientError::LiquidationAmountTooLarge => LiquidationError::LiquidationAmountTooLarge,
+        ClientError::RemainingSizeBelowMinimum => LiquidationError::RemainingSizeBelowMinimum,
+        ClientError::MarketMismatch => LiquidationError::MarketMismatch,
+        ClientError::InvalidRemainingAccounts => LiquidationError::InvalidRemainingAccounts,
+        ClientError::CrossPositionExists => LiquidationError::CrossPositionExists,
+        ClientError::CrossPositionNotFound => LiquidationError::CrossPositionNotFound,
+        ClientError::TooManyCrossPositions => LiquidationError::TooManyCrossPositions,
+        ClientError::NotPendingAuthority => LiquidationError::NotPendingAuthority,
+        ClientError::StaleOracle => LiquidationError::StaleOracle,
+        ClientError::OracleConfidenceTooWide => LiquidationError::OracleConfidenceTooWide,
+        ClientError::NoUncoveredBadDebt => LiquidationError::NoUncoveredBadDebt,
+        ClientError::NotAdlCounterparty => LiquidationError::NotAdlCounterparty,
+        ClientError::InsuranceFundDepleted => LiquidationError::InsuranceFundDepleted,
+        ClientError::InsufficientShares => LiquidationError::InsufficientShares,
+        ClientError::UnstakeCooldownActive => LiquidationError::UnstakeCooldownActive,
+        ClientError::MarketOpeningDisabled => LiquidationError::MarketOpeningDisabled,
+        ClientError::PositionSizeOutOfBounds => LiquidationError::PositionSizeOutOfBounds,
+        ClientError::CollateralBelowMinimum => LiquidationError::CollateralBelowMinimum,
+        ClientError::OpenInterestCapExceeded => LiquidationError::OpenInterestCapExceeded,
+        ClientError::SlippageExceeded => LiquidationError::SlippageExceeded,
+        ClientError::InvalidCollateralParams => LiquidationError::InvalidCollateralParams,
+        ClientError::CollateralAssetMismatch => LiquidationError::CollateralAssetMismatch,
+        ClientError::TooManyCollateralAssets => LiquidationError::TooManyCollateralAssets,
+        ClientError::InsufficientCollateralBalance => LiquidationError::InsufficientCollateralBalance,
+        ClientError::CollateralNotSeized => LiquidationError::CollateralNotSeized,
+        ClientError::InsuranceFundAlreadyMigrated => LiquidationError::InsuranceFundAlreadyMigrated,
+        ClientError::PartialLiquidationRequired => LiquidationError::PartialLiquidationRequired,
+        ClientError::UnstakeRequestExpired => LiquidationError::UnstakeRequestExpired,
+    }
+}
+
+#[test]
+fn error_codes_match() {
+    for error in ClientError::ALL {
+        let program = program_error(error);
+        assert_eq!(client_error(program), error);
+        assert_eq!(error.code(), u32::from(program));
+        assert_eq!(format!("{:?}", error), program.name());
+        assert_eq!(ClientError::from_code(error.code()), Some(error));
+    }
+    assert_eq!(ClientError::from_code(6000 + ClientError::ALL.len() as u32), None);
+}
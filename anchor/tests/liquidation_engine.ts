@@ -9,31 +9,406 @@ describe("liquidation_engine", () => {
   anchor.setProvider(provider);
 
   const program = anchor.workspace.LiquidationEngine as Program<LiquidationEngine>;
+  const authority = provider.wallet;
 
-  let insuranceFund = anchor.web3.Keypair.generate();
-  let position = anchor.web3.Keypair.generate();
-  let liquidator = provider.wallet;
+  const pda = (...seeds: Buffer[]) =>
+    anchor.web3.PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+  const config = pda(Buffer.from("config"));
+  const insuranceFund = pda(Buffer.from("insurance_fund"));
+  const insuranceVault = pda(Buffer.from("insurance_vault"));
+  const vaultAuthority = pda(Buffer.from("vault_authority"));
+  const guardian = anchor.web3.Keypair.generate();
+  const trader = anchor.web3.Keypair.generate();
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods
+  const symbol = "SOL/USD";
+  const market = pda(Buffer.from("market"), Buffer.from(symbol));
+  const vault = pda(Buffer.from("vault"), Buffer.from(symbol));
+  const position = pda(
+    Buffer.from("position"),
+    trader.publicKey.toBuffer(),
+    Buffer.from(symbol),
+    Buffer.from([1]) // long
+  );
+  // Fixture feeds loaded by Anchor.toml, owned by the Pyth program
+  const pythProgram = new anchor.web3.PublicKey("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+  const feedAt100 = new anchor.web3.PublicKey("DJzWVJ2ucy8GAvckXdWV3XnWsAWxjCikHcVafeeTkAd");
+  const feedAt88 = new anchor.web3.PublicKey("3XiYoD89dmeb8CqRc9H9rMjouUMzDLXSu64QDAPYxkNs");
+
+  // Amounts and prices use 6 decimals
+  const units = (n: number) => new anchor.BN(n * 1_000_000);
+
+  // SPL Token mint with `decimals`, created without @solana/spl-token
+  const createMint = async (decimals: number) => {
+    const mint = anchor.web3.Keypair.generate();
+    const space = 82;
+    const lamports = await provider.connection.getMinimumBalanceForRentExemption(space);
+    const initializeMint2 = new anchor.web3.TransactionInstruction({
+      programId: anchor.utils.token.TOKEN_PROGRAM_ID,
+      keys: [{ pubkey: mint.publicKey, isSigner: false, isWritable: true }],
+      data: Buffer.concat([Buffer.from([20, decimals]), authority.publicKey.toBuffer(), Buffer.from([0])]),
+    });
+    const tx = new anchor.web3.Transaction().add(
+      anchor.web3.SystemProgram.createAccount({
+        fromPubkey: authority.publicKey,
+        newAccountPubkey: mint.publicKey,
+        space,
+        lamports,
+        programId: anchor.utils.token.TOKEN_PROGRAM_ID,
+      }),
+      initializeMint2
+    );
+    await provider.sendAndConfirm(tx, [mint]);
+    return mint.publicKey;
+  };
+  // SPL Token account for `owner`, created without @solana/spl-token
+  const createTokenAccount = async (mint: anchor.web3.PublicKey, owner: anchor.web3.PublicKey) => {
+    const account = anchor.web3.Keypair.generate();
+    const space = 165;
+    const lamports = await provider.connection.getMinimumBalanceForRentExemption(space);
+    const initializeAccount3 = new anchor.web3.TransactionInstruction({
+      programId: anchor.utils.token.TOKEN_PROGRAM_ID,
+      keys: [
+        { pubkey: account.publicKey, isSigner: false, isWritable: true },
+        { pubkey: mint, isSigner: false, isWritable: false },
+      ],
+      data: Buffer.concat([Buffer.from([18]), owner.toBuffer()]),
+    });
+    const tx = new anchor.web3.Transaction().add(
+      anchor.web3.SystemProgram.createAccount({
+        fromPubkey: authority.publicKey,
+        newAccountPubkey: account.publicKey,
+        space,
+        lamports,
+        programId: anchor.utils.token.TOKEN_PROGRAM_ID,
+      }),
+      initializeAccount3
+    );
+    await provider.sendAndConfirm(tx, [account]);
+    return account.publicKey;
+  };
+  const mintTo = async (mint: anchor.web3.PublicKey, destination: anchor.web3.PublicKey, amount: anchor.BN) => {
+    const tx = new anchor.web3.Transaction().add(
+      new anchor.web3.TransactionInstruction({
+        programId: anchor.utils.token.TOKEN_PROGRAM_ID,
+        keys: [
+          { pubkey: mint, isSigner: false, isWritable: true },
+          { pubkey: destination, isSigner: false, isWritable: true },
+          { pubkey: authority.publicKey, isSigner: true, isWritable: false },
+        ],
+        data: Buffer.concat([Buffer.from([7]), amount.toArrayLike(Buffer, "le", 8)]),
+      })
+    );
+    await provider.sendAndConfirm(tx);
+  };
+  const tokenBalance = async (account: anchor.web3.PublicKey) =>
+    new anchor.BN((await provider.connection.getTokenAccountBalance(account)).value.amount);
+  const expectError = async (promise: Promise<unknown>, code: string) => {
+    try {
+      await promise;
+    } catch (e) {
+      assert.instanceOf(e, anchor.AnchorError);
+      assert.equal((e as anchor.AnchorError).error.errorCode.code, code);
+      return;
+    }
+    assert.fail(`expected ${code}`);
+  };
+
+  const marketParams = (oracle: anchor.web3.PublicKey) => ({
+    oracle,
+    oracleProgram: pythProgram,
+    // The fixtures never update, so any slot is recent enough
+    maxStalenessSlots: new anchor.BN(1_000_000_000),
+    maxConfidenceBps: 100,
+    maxLeverage: 10,
+    marginTiers: [
+      { maxLeverage: 2, maintenanceMarginBps: 200 },
+      { maxLeverage: 4, maintenanceMarginBps: 300 },
+      { maxLeverage: 6, maintenanceMarginBps: 400 },
+      { maxLeverage: 10, maintenanceMarginBps: 500 },
+    ],
+    liquidationFeeBps: 100,
+    liquidationFeeMaxBps: 300,
+    liquidationFeeRampBps: 500,
+    insuranceFundShareBps: 2000,
+    closeFactorBps: 5000,
+    targetMarginBps: 1500,
+    minPositionSize: units(1),
+    liquidationMode: { direct: {} },
+    auctionStartDiscountBps: 0,
+    auctionMaxDiscountBps: 0,
+    auctionDurationSlots: new anchor.BN(0),
+    maxFundingRateBps: 0,
+    minCollateral: units(10),
+    maxPositionSize: units(1_000),
+    maxOpenInterest: units(10_000),
+    openingEnabled: true,
+  });
+
+  const initializeInsuranceFund = (mint: anchor.web3.PublicKey) =>
+    program.methods
       .initializeInsuranceFund()
       .accounts({
-        insuranceFund: insuranceFund.publicKey,
-        authority: provider.wallet.publicKey,
+        insuranceFund,
+        insuranceVault,
+        mint,
+        vaultAuthority,
+        authority: authority.publicKey,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
       })
-      .signers([insuranceFund])
       .rpc();
-    
-    console.log("Your transaction signature", tx);
 
-    const account = await program.account.insuranceFund.fetch(insuranceFund.publicKey);
+  before(async () => {
+    await program.methods
+      .initializeConfig(guardian.publicKey)
+      .accounts({
+        config,
+        authority: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+  });
+
+  it("Initializes the config", async () => {
+    const account = await program.account.config.fetch(config);
+    assert.ok(account.authority.equals(authority.publicKey));
+    assert.ok(account.guardian.equals(guardian.publicKey));
+    assert.isFalse(account.openingPaused);
+    assert.isFalse(account.liquidationsPaused);
+  });
+
+  it("Rejects a mint with the wrong decimals", async () => {
+    const mint = await createMint(9);
+    await expectError(initializeInsuranceFund(mint), "InvalidCollateralMint");
+  });
+
+  let mint: anchor.web3.PublicKey;
+  let traderTokens: anchor.web3.PublicKey;
+  let liquidatorTokens: anchor.web3.PublicKey;
+
+  it("Initializes the insurance fund", async () => {
+    // Collateral uses 6 decimals, the program's fixed-point scale
+    mint = await createMint(6);
+    await initializeInsuranceFund(mint);
+
+    const account = await program.account.insuranceFund.fetch(insuranceFund);
+    assert.ok(account.authority.equals(authority.publicKey));
+    assert.ok(account.mint.equals(mint));
     assert.equal(account.balance.toNumber(), 0);
+    assert.equal(account.totalShares.toNumber(), 0);
+  });
+
+  it("Lists a market with its vault", async () => {
+    await program.methods
+      .initializeMarket(symbol, marketParams(feedAt100))
+      .accounts({
+        config,
+        market,
+        authority: authority.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+    await program.methods
+      .initializeVault(symbol)
+      .accounts({
+        insuranceFund,
+        vault,
+        mint,
+        vaultAuthority,
+        authority: authority.publicKey,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    const account = await program.account.market.fetch(market);
+    assert.ok(account.oracle.equals(feedAt100));
+    assert.equal((await tokenBalance(vault)).toNumber(), 0);
+  });
+
+  it("Locks the collateral of a new position in the vault", async () => {
+    await provider.connection.confirmTransaction(
+      await provider.connection.requestAirdrop(trader.publicKey, anchor.web3.LAMPORTS_PER_SOL),
+      "confirmed"
+    );
+    traderTokens = await createTokenAccount(mint, trader.publicKey);
+    liquidatorTokens = await createTokenAccount(mint, authority.publicKey);
+    await mintTo(mint, traderTokens, units(1_000));
+
+    // 10 SOL at $100 on 10x: $100 initial margin, $150 posted
+    await program.methods
+      .openPosition(symbol, true, units(10), units(150), units(101), 10)
+      .accounts({
+        position,
+        market,
+        oracle: feedAt100,
+        config,
+        vault,
+        ownerTokenAccount: traderTokens,
+        owner: trader.publicKey,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([trader])
+      .rpc();
+
+    const account = await program.account.position.fetch(position);
+    assert.equal(account.size.toString(), units(10).toString());
+    assert.equal(account.collateral.toString(), units(150).toString());
+    assert.equal(account.entryPrice.toString(), units(100).toString());
+    assert.equal((await tokenBalance(vault)).toString(), units(150).toString());
+    assert.equal((await tokenBalance(traderTokens)).toString(), units(850).toString());
   });
 
-  // Note: In a real test we would need to initialize a Position account first.
-  // Since we didn't create an 'initialize_position' instruction in the program for this demo,
-  // we can't fully test the liquidation flow on-chain without it. 
-  // However, this demonstrates the test structure.
+  it("Deposits collateral into the vault", async () => {
+    await program.methods
+      .addCollateral(units(50))
+      .accounts({
+        position,
+        market,
+        vault,
+        ownerTokenAccount: traderTokens,
+        owner: trader.publicKey,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      })
+      .signers([trader])
+      .rpc();
+
+    const account = await program.account.position.fetch(position);
+    assert.equal(account.collateral.toString(), units(200).toString());
+    assert.equal((await tokenBalance(vault)).toString(), units(200).toString());
+    assert.equal((await tokenBalance(traderTokens)).toString(), units(800).toString());
+  });
+
+  const withdraw = (amount: anchor.BN, oracle = feedAt100) =>
+    program.methods
+      .withdrawCollateral(amount)
+      .accounts({
+        position,
+        market,
+        oracle,
+        config,
+        vault,
+        vaultAuthority,
+        ownerTokenAccount: traderTokens,
+        owner: trader.publicKey,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      })
+      .signers([trader])
+      .rpc();
+
+  it("Withdraws collateral from the vault down to the initial margin", async () => {
+    await withdraw(units(50));
+
+    const account = await program.account.position.fetch(position);
+    assert.equal(account.collateral.toString(), units(150).toString());
+    assert.equal((await tokenBalance(vault)).toString(), units(150).toString());
+    assert.equal((await tokenBalance(traderTokens)).toString(), units(850).toString());
+
+    // $90 left would be under the $100 initial margin
+    await expectError(withdraw(units(60)), "InitialMarginBreached");
+    assert.equal((await tokenBalance(vault)).toString(), units(150).toString());
+  });
+
+  const liquidatePartial = (amount: anchor.BN) =>
+    program.methods
+      .liquidatePartial(amount)
+      .accounts({
+        position,
+        market,
+        config,
+        insuranceFund,
+        oracle: feedAt88,
+        vault,
+        insuranceVault,
+        vaultAuthority,
+        liquidatorTokenAccount: liquidatorTokens,
+        liquidator: authority.publicKey,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+  it("Pays a partial liquidation out of the vault", async () => {
+    await program.methods
+      .updateMarket(marketParams(feedAt88))
+      .accounts({ config, market, authority: authority.publicKey })
+      .rpc();
+
+    // At $88 the $30 margin is under the $44 maintenance margin
+    const vaultBefore = await tokenBalance(vault);
+    await liquidatePartial(units(1));
+
+    const account = await program.account.position.fetch(position);
+    assert.equal(account.size.toString(), units(9).toString());
+
+    const reward = await tokenBalance(liquidatorTokens);
+    const insuranceFee = await tokenBalance(insuranceVault);
+    assert.isTrue(reward.gtn(0));
+    assert.isTrue(insuranceFee.gtn(0));
+    // The penalty leaves the vault, split between liquidator and fund
+    assert.equal(vaultBefore.sub(await tokenBalance(vault)).toString(), reward.add(insuranceFee).toString());
+    const fund = await program.account.insuranceFund.fetch(insuranceFund);
+    assert.equal(fund.balance.toString(), insuranceFee.toString());
+  });
+
+  it("Rejects a partial liquidation beyond the close factor", async () => {
+    // Half of the remaining 9 SOL
+    await expectError(liquidatePartial(units(5)), "LiquidationAmountTooLarge");
+  });
+
+  const setPause = (method: "emergencyPause" | "unpause", signer: anchor.web3.Keypair | null) =>
+    program.methods[method](true, true)
+      .accounts({ config, guardian: signer ? signer.publicKey : authority.publicKey })
+      .signers(signer ? [signer] : [])
+      .rpc();
+
+  it("Only lets the guardian pause", async () => {
+    await expectError(setPause("emergencyPause", anchor.web3.Keypair.generate()), "ConstraintHasOne");
+    // The config authority can only rotate the guardian
+    await expectError(setPause("emergencyPause", null), "ConstraintHasOne");
+
+    await setPause("emergencyPause", guardian);
+    const account = await program.account.config.fetch(config);
+    assert.isTrue(account.openingPaused);
+    assert.isTrue(account.liquidationsPaused);
+  });
+
+  it("Rejects opening, withdrawing and liquidating while paused", async () => {
+    const short = pda(Buffer.from("position"), trader.publicKey.toBuffer(), Buffer.from(symbol), Buffer.from([0]));
+    await expectError(
+      program.methods
+        .openPosition(symbol, false, units(1), units(20), units(87), 10)
+        .accounts({
+          position: short,
+          market,
+          oracle: feedAt88,
+          config,
+          vault,
+          ownerTokenAccount: traderTokens,
+          owner: trader.publicKey,
+          tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([trader])
+        .rpc(),
+      "ProgramPaused"
+    );
+    await expectError(withdraw(units(1), feedAt88), "ProgramPaused");
+    await expectError(liquidatePartial(units(1)), "ProgramPaused");
+  });
+
+  it("Only lets the guardian unpause", async () => {
+    await expectError(setPause("unpause", anchor.web3.Keypair.generate()), "ConstraintHasOne");
+    await expectError(setPause("unpause", null), "ConstraintHasOne");
+
+    await setPause("unpause", guardian);
+    const account = await program.account.config.fetch(config);
+    assert.isFalse(account.openingPaused);
+    assert.isFalse(account.liquidationsPaused);
+
+    // Past the pause check, the withdrawal now fails on margin instead
+    await expectError(withdraw(units(1), feedAt88), "InitialMarginBreached");
+  });
 });
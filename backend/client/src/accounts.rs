@@ -0,0 +1,387 @@
+//! Mirrors of the program's accounts. Field order must match the program's
+//! structs exactly: Borsh has no field names.
+
+use anyhow::bail;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+/// An account type owned by the program.
+pub trait ProgramAccount: Sized {
+    /// Struct name in the program; Anchor derives the discriminator from it.
+    const NAME: &'static str;
+
+    /// Decodes the account body following the discriminator.
+    fn decode_body(data: &[u8]) -> anyhow::Result<Self>;
+
+    fn discriminator() -> [u8; 8] {
+        crate::discriminator("account", Self::NAME)
+    }
+
+    /// Whether `data` starts with this account type's discriminator.
+    fn matches(data: &[u8]) -> bool {
+        data.len() >= 8 && data[..8] == Self::discriminator()
+    }
+
+    /// Decodes full account data, rejecting any other account type.
+    fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        if !Self::matches(data) {
+            bail!("Not a {} account", Self::NAME);
+        }
+        Self::decode_body(&data[8..])
+    }
+}
+
+/// Implements `ProgramAccount` for a plain Borsh layout. Accounts sized for
+/// their longest strings or vectors carry trailing padding, which is ignored.
+macro_rules! borsh_account {
+    ($type:ty, $name:literal) => {
+        impl ProgramAccount for $type {
+            const NAME: &'static str = $name;
+
+            fn decode_body(data: &[u8]) -> anyhow::Result<Self> {
+                Ok(Self::deserialize(&mut &data[..])?)
+            }
+        }
+    };
+}
+
+/// Mirror of the program's `Config` account.
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct OnChainConfig {
+    pub authority: Pubkey,
+    pub guardian: Pubkey,
+    pub opening_paused: bool,
+    pub liquidations_paused: bool,
+    pub pending_authority: Pubkey,
+}
+borsh_account!(OnChainConfig, "Config");
+
+/// Mirror of the program's `InsuranceFund` account.
+#[derive(BorshDeserialize, Debug)]
+pub struct OnChainInsuranceFund {
+    pub authority: Pubkey,
+    pub mint: Pubkey,
+    pub balance: u64,
+    pub total_contributions: u64,
+    pub total_bad_debt_covered: u64,
+    pub utilization_ratio: u64,
+    pub withdrawal_window_start: i64,
+    pub withdrawn_in_window: u64,
+    pub pending_authority: Pubkey,
+    pub total_shares: u64,
+    pub protocol_shares: u64,
+}
+borsh_account!(OnChainInsuranceFund, "InsuranceFund");
+
+impl OnChainInsuranceFund {
+    /// Fund balance per share, in token units per share. Before anyone
+    /// stakes, shares are minted 1:1.
+    pub fn share_price(&self) -> f64 {
+        if self.total_shares == 0 {
+            1.0
+        } else {
+            self.balance as f64 / self.total_shares as f64
+        }
+    }
+}
+
+/// Mirror of a staker's `InsuranceStake` account.
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct OnChainInsuranceStake {
+    pub owner: Pubkey,
+    pub shares: u64,
+    pub unstake_shares: u64,
+    pub unstake_requested_at: i64,
+}
+borsh_account!(OnChainInsuranceStake, "InsuranceStake");
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default)]
+pub struct OnChainMarginTier {
+    pub max_leverage: u16,
+    pub maintenance_margin_bps: u16,
+}
+
+/// How under-margined positions on a market are liquidated.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LiquidationMode {
+    #[default]
+    Direct,
+    Auction,
+}
+
+/// Mirror of the program's `Market` account.
+#[derive(BorshDeserialize, Debug, Default)]
+pub struct OnChainMarket {
+    pub symbol: String,
+    pub oracle: Pubkey,
+    pub oracle_program: Pubkey,
+    pub max_staleness_slots: u64,
+    pub max_confidence_bps: u16,
+    pub max_leverage: u16,
+    pub margin_tiers: [OnChainMarginTier; 4],
+    pub liquidation_fee_bps: u16,
+    pub liquidation_fee_max_bps: u16,
+    pub liquidation_fee_ramp_bps: u16,
+    pub insurance_fund_share_bps: u16,
+    pub close_factor_bps: u16,
+    pub target_margin_bps: u16,
+    pub min_position_size: u64,
+    pub liquidation_mode: LiquidationMode,
+    pub auction_start_discount_bps: u16,
+    pub auction_max_discount_bps: u16,
+    pub auction_duration_slots: u64,
+    pub max_funding_rate_bps: u16,
+    pub min_collateral: u64,
+    pub max_position_size: u64,
+    pub max_open_interest: u64,
+    pub opening_enabled: bool,
+    pub long_funding_index: i128,
+    pub short_funding_index: i128,
+    pub last_funding_update: i64,
+    pub open_interest_long: u64,
+    pub open_interest_short: u64,
+    pub uncovered_bad_debt: u64,
+    pub bankruptcy_price: u64,
+    pub bankrupt_is_long: bool,
+}
+borsh_account!(OnChainMarket, "Market");
+
+impl OnChainMarket {
+    /// Same tier lookup as the program: the first tier covering `leverage`,
+    /// else the last one.
+    pub fn maintenance_margin_bps(&self, leverage: u16) -> u16 {
+        self.margin_tiers
+            .iter()
+            .find(|tier| leverage <= tier.max_leverage)
+            .unwrap_or(&self.margin_tiers[self.margin_tiers.len() - 1])
+            .maintenance_margin_bps
+    }
+
+    /// Funding paid per unit of size on one side since market creation.
+    pub fn funding_index(&self, is_long: bool) -> i128 {
+        if is_long {
+            self.long_funding_index
+        } else {
+            self.short_funding_index
+        }
+    }
+
+    /// Same fee curve as the program: `liquidation_fee_bps` at the
+    /// maintenance margin, rising linearly to `liquidation_fee_max_bps` once
+    /// the margin ratio is `liquidation_fee_ramp_bps` below it.
+    pub fn liquidation_fee_bps(&self, margin_ratio_bps: i64, leverage: u16) -> u16 {
+        let (floor, cap, range) = (
+            self.liquidation_fee_bps,
+            self.liquidation_fee_max_bps,
+            self.liquidation_fee_ramp_bps,
+        );
+        if range == 0 || cap <= floor {
+            return floor;
+        }
+        let shortfall = (self.maintenance_margin_bps(leverage) as i64 - margin_ratio_bps).clamp(0, range as i64);
+        floor + ((cap - floor) as i64 * shortfall / range as i64) as u16
+    }
+
+    /// Largest amount `liquidate_partial` accepts for a position of `size`
+    /// with `margin` at `price`, all in 6-decimal units: the size that
+    /// restores the target margin. `None` when the program requires
+    /// `liquidate_full` instead: the position is at or below the minimum
+    /// size, bankrupt, or out of reach of one close-factor-capped partial.
+    /// Same integer arithmetic and rounding as the program, so the amount
+    /// never exceeds its cap at the same price.
+    pub fn partial_liquidation_size(&self, size: u64, margin: i128, price: u64, leverage: u16) -> Option<u64> {
+        let notional = size as u128 * price as u128 / 1_000_000;
+        if size <= self.min_position_size || margin <= 0 || notional == 0 {
+            return None;
+        }
+        let margin_ratio_bps = margin * 10_000 / notional as i128;
+        let fee_bps = self.liquidation_fee_bps(margin_ratio_bps as i64, leverage);
+        let target = notional * self.target_margin_bps as u128 / 10_000;
+        let deficit = target as i128 - margin;
+        let size_needed = if deficit <= 0 {
+            0
+        } else if self.target_margin_bps <= fee_bps {
+            return None;
+        } else {
+            // Each unit of notional closed lowers the target requirement by
+            // the target margin but costs the margin the fee
+            let improvement_bps = (self.target_margin_bps - fee_bps) as u128;
+            let notional_needed = (deficit as u128 * 10_000).div_ceil(improvement_bps);
+            (notional_needed * 1_000_000).div_ceil(price as u128) as u64
+        };
+
+        let close_factor_cap = (size as u128 * self.close_factor_bps as u128 / 10_000) as u64;
+        if size_needed > close_factor_cap || size - size_needed < self.min_position_size {
+            return None;
+        }
+        Some(size_needed)
+    }
+}
+
+#[derive(Debug)]
+pub struct OnChainPosition {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub collateral: u64,
+    pub entry_price: u64,
+    pub leverage: u16,
+    pub maintenance_margin: u64,
+    /// `None` for baseline positions that have not been migrated yet.
+    pub last_funding_index: Option<i128>,
+}
+
+/// `Position` as first deployed: no version, direction or funding index.
+#[derive(BorshDeserialize)]
+struct BaselinePositionLayout {
+    owner: Pubkey,
+    symbol: String,
+    size: u64,
+    collateral: u64,
+    entry_price: u64,
+    leverage: u16,
+    maintenance_margin: u64,
+}
+
+#[derive(BorshDeserialize)]
+struct PositionLayout {
+    owner: Pubkey,
+    symbol: String,
+    is_long: bool,
+    size: u64,
+    collateral: u64,
+    entry_price: u64,
+    leverage: u16,
+    maintenance_margin: u64,
+    last_funding_index: i128,
+}
+
+const POSITION_VERSION: u8 = 2;
+// owner + symbol prefix + size, collateral, entry_price, leverage, maintenance_margin
+const BASELINE_POSITION_FIXED_LEN: usize = 32 + 4 + 8 + 8 + 8 + 2 + 8;
+
+impl ProgramAccount for OnChainPosition {
+    const NAME: &'static str = "Position";
+
+    /// Decodes the current layout or the baseline one `migrate_position`
+    /// still accepts. Baseline accounts were sized exactly to their symbol,
+    /// so the length identifies them; current accounts lead with a version
+    /// byte and are padded to a fixed size. Baseline positions had no
+    /// direction and are reported as long, as `migrate_position` writes them.
+    fn decode_body(data: &[u8]) -> anyhow::Result<Self> {
+        if let Some(symbol_len) = baseline_symbol_len(data) {
+            if data.len() == BASELINE_POSITION_FIXED_LEN + symbol_len {
+                let layout = BaselinePositionLayout::try_from_slice(data)?;
+                return Ok(OnChainPosition {
+                    owner: layout.owner,
+                    symbol: layout.symbol,
+                    is_long: true,
+                    size: layout.size,
+                    collateral: layout.collateral,
+                    entry_price: layout.entry_price,
+                    leverage: layout.leverage,
+                    maintenance_margin: layout.maintenance_margin,
+                    last_funding_index: None,
+                });
+            }
+        }
+        match data.first() {
+            Some(&POSITION_VERSION) => {
+                let layout = PositionLayout::deserialize(&mut &data[1..])?;
+                Ok(OnChainPosition {
+                    owner: layout.owner,
+                    symbol: layout.symbol,
+                    is_long: layout.is_long,
+                    size: layout.size,
+                    collateral: layout.collateral,
+                    entry_price: layout.entry_price,
+                    leverage: layout.leverage,
+                    maintenance_margin: layout.maintenance_margin,
+                    last_funding_index: Some(layout.last_funding_index),
+                })
+            }
+            Some(version) => bail!("Unsupported position version {}", version),
+            None => bail!("Empty position account"),
+        }
+    }
+}
+
+impl OnChainPosition {
+    /// Funding owed against `market`'s index for the position's side since
+    /// it last settled, in 6-decimal quote units; negative when it is owed
+    /// funding.
+    pub fn unsettled_funding(&self, market: &OnChainMarket) -> i128 {
+        let Some(last_funding_index) = self.last_funding_index else {
+            // Not settled on-chain until the position is migrated
+            return 0;
+        };
+        (market.funding_index(self.is_long) - last_funding_index) * self.size as i128 / 1_000_000
+    }
+}
+
+fn baseline_symbol_len(data: &[u8]) -> Option<usize> {
+    let symbol_len = data.get(32..36)?;
+    Some(u32::from_le_bytes(symbol_len.try_into().unwrap()) as usize)
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct OnChainCrossPosition {
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub entry_price: u64,
+    pub leverage: u16,
+    pub last_funding_index: i128,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct OnChainCollateralBalance {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Mirror of the program's cross-margin `MarginAccount`.
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct OnChainMarginAccount {
+    pub owner: Pubkey,
+    pub collateral: u64,
+    pub positions: Vec<OnChainCrossPosition>,
+    pub deposits: Vec<OnChainCollateralBalance>,
+}
+borsh_account!(OnChainMarginAccount, "MarginAccount");
+
+/// Mirror of the program's `CollateralAsset` registry entry.
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct OnChainCollateralAsset {
+    pub mint: Pubkey,
+    pub symbol: String,
+    pub decimals: u8,
+    pub oracle: Pubkey,
+    pub oracle_program: Pubkey,
+    pub max_staleness_slots: u64,
+    pub max_confidence_bps: u16,
+    pub weight_bps: u16,
+    pub liquidation_discount_bps: u16,
+}
+borsh_account!(OnChainCollateralAsset, "CollateralAsset");
+
+impl OnChainCollateralAsset {
+    /// Value of `amount` base units at `price`, after the haircut.
+    pub fn weighted_value(&self, amount: u64, price: f64) -> f64 {
+        amount as f64 / 10f64.powi(self.decimals as i32) * price * self.weight_bps as f64 / 10_000.0
+    }
+}
+
+/// Mirror of an open Dutch `Auction` on a position.
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct OnChainAuction {
+    pub position: Pubkey,
+    pub market: Pubkey,
+    pub keeper: Pubkey,
+    pub start_slot: u64,
+    pub start_discount_bps: u16,
+    pub max_discount_bps: u16,
+    pub duration_slots: u64,
+}
+borsh_account!(OnChainAuction, "Auction");
@@ -0,0 +1,111 @@
+//! The program's custom error codes. Anchor numbers them from 6000 in the
+//! order `LiquidationError` declares them.
+
+/// Mirror of the program's `LiquidationError`, in declaration order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum LiquidationError {
+    NotLiquidatable = 6000,
+    InvalidLiquidationAmount,
+    InvalidOracleAccount,
+    InvalidCollateralMint,
+    InvalidAmount,
+    WithdrawalLimitExceeded,
+    InvalidLeverage,
+    InvalidMarketParams,
+    InitialMarginBreached,
+    PositionUnderwater,
+    MathOverflow,
+    ProgramPaused,
+    PositionNotMigrated,
+    PositionAlreadyMigrated,
+    WrongLiquidationMode,
+    AuctionStillActive,
+    LiquidationAmountTooLarge,
+    RemainingSizeBelowMinimum,
+    MarketMismatch,
+    InvalidRemainingAccounts,
+    CrossPositionExists,
+    CrossPositionNotFound,
+    TooManyCrossPositions,
+    NotPendingAuthority,
+    StaleOracle,
+    OracleConfidenceTooWide,
+    NoUncoveredBadDebt,
+    NotAdlCounterparty,
+    InsuranceFundDepleted,
+    InsufficientShares,
+    UnstakeCooldownActive,
+    MarketOpeningDisabled,
+    PositionSizeOutOfBounds,
+    CollateralBelowMinimum,
+    OpenInterestCapExceeded,
+    SlippageExceeded,
+    InvalidCollateralParams,
+    CollateralAssetMismatch,
+    TooManyCollateralAssets,
+    InsufficientCollateralBalance,
+    CollateralNotSeized,
+    InsuranceFundAlreadyMigrated,
+    PartialLiquidationRequired,
+    UnstakeRequestExpired,
+}
+
+impl LiquidationError {
+    pub const ALL: [Self; 44] = [
+        Self::NotLiquidatable,
+        Self::InvalidLiquidationAmount,
+        Self::InvalidOracleAccount,
+        Self::InvalidCollateralMint,
+        Self::InvalidAmount,
+        Self::WithdrawalLimitExceeded,
+        Self::InvalidLeverage,
+        Self::InvalidMarketParams,
+        Self::InitialMarginBreached,
+        Self::PositionUnderwater,
+        Self::MathOverflow,
+        Self::ProgramPaused,
+        Self::PositionNotMigrated,
+        Self::PositionAlreadyMigrated,
+        Self::WrongLiquidationMode,
+        Self::AuctionStillActive,
+        Self::LiquidationAmountTooLarge,
+        Self::RemainingSizeBelowMinimum,
+        Self::MarketMismatch,
+        Self::InvalidRemainingAccounts,
+        Self::CrossPositionExists,
+        Self::CrossPositionNotFound,
+        Self::TooManyCrossPositions,
+        Self::NotPendingAuthority,
+        Self::StaleOracle,
+        Self::OracleConfidenceTooWide,
+        Self::NoUncoveredBadDebt,
+        Self::NotAdlCounterparty,
+        Self::InsuranceFundDepleted,
+        Self::InsufficientShares,
+        Self::UnstakeCooldownActive,
+        Self::MarketOpeningDisabled,
+        Self::PositionSizeOutOfBounds,
+        Self::CollateralBelowMinimum,
+        Self::OpenInterestCapExceeded,
+        Self::SlippageExceeded,
+        Self::InvalidCollateralParams,
+        Self::CollateralAssetMismatch,
+        Self::TooManyCollateralAssets,
+        Self::InsufficientCollateralBalance,
+        Self::CollateralNotSeized,
+        Self::InsuranceFundAlreadyMigrated,
+        Self::PartialLiquidationRequired,
+        Self::UnstakeRequestExpired,
+    ];
+
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    /// The error a failed instruction's `Custom(code)` stands for, if it is
+    /// one of the program's own.
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|error| error.code() == code)
+    }
+}
@@ -0,0 +1,189 @@
+//! Mirrors of the program's events and a decoder for the `Program data:`
+//! lines `emit!` writes to transaction logs.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use borsh::BorshDeserialize;
+use solana_program::pubkey::Pubkey;
+
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct LiquidationEvent {
+    pub position_owner: Pubkey,
+    pub liquidator: Pubkey,
+    pub symbol: String,
+    pub liquidated_size: u64,
+    pub liquidation_price: u64,
+    pub margin_before: i64,
+    pub margin_after: i64,
+    pub liquidator_reward: u64,
+    pub insurance_fee: u64,
+    pub bad_debt: u64,
+    pub timestamp: i64,
+    pub is_full: bool,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct PositionOpened {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub collateral: u64,
+    pub entry_price: u64,
+    pub leverage: u16,
+    pub maintenance_margin: u64,
+    pub last_funding_index: i128,
+    pub timestamp: i64,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct PositionModified {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub collateral: u64,
+    pub maintenance_margin: u64,
+    pub last_funding_index: i128,
+    pub size_reduced: u64,
+    pub collateral_change: i64,
+    pub timestamp: i64,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct PositionClosed {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub is_long: bool,
+    pub closed_size: u64,
+    pub close_price: u64,
+    pub collateral_returned: u64,
+    pub timestamp: i64,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct InsuranceFundChanged {
+    pub balance: u64,
+    pub change: i64,
+    pub total_contributions: u64,
+    pub total_bad_debt_covered: u64,
+    pub total_shares: u64,
+    pub timestamp: i64,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct InsuranceStaked {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub total_shares: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct InsuranceUnstaked {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub total_shares: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct ContributionEvent {
+    pub contributor: Pubkey,
+    pub amount: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct InsuranceWithdrawalEvent {
+    pub authority: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[derive(BorshDeserialize, Debug, Clone)]
+pub struct MarginAccountLiquidated {
+    pub owner: Pubkey,
+    pub liquidator: Pubkey,
+    pub symbols: Vec<String>,
+    pub liquidated_notional: u64,
+    pub equity_before: i64,
+    pub maintenance_requirement: u64,
+    pub equity_after: i64,
+    pub liquidator_reward: u64,
+    pub insurance_fee: u64,
+    pub bad_debt: u64,
+    pub timestamp: i64,
+    pub is_full: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum ProgramEvent {
+    Liquidation(LiquidationEvent),
+    MarginAccountLiquidation(MarginAccountLiquidated),
+    Contribution(ContributionEvent),
+    InsuranceWithdrawal(InsuranceWithdrawalEvent),
+    PositionOpened(PositionOpened),
+    PositionModified(PositionModified),
+    PositionClosed(PositionClosed),
+    InsuranceFundChanged(InsuranceFundChanged),
+    Stake(InsuranceStaked),
+    Unstake(InsuranceUnstaked),
+}
+
+/// Anchor prefixes every event with the first 8 bytes of sha256("event:<Name>").
+fn event_discriminator(name: &str) -> [u8; 8] {
+    crate::discriminator("event", name)
+}
+
+/// Decodes a single `emit!` payload (discriminator + borsh body).
+pub fn decode_event(data: &[u8]) -> Option<ProgramEvent> {
+    if data.len() < 8 {
+        return None;
+    }
+    let (discriminator, mut body) = data.split_at(8);
+
+    if discriminator == event_discriminator("LiquidationEvent") {
+        LiquidationEvent::deserialize(&mut body).ok().map(ProgramEvent::Liquidation)
+    } else if discriminator == event_discriminator("MarginAccountLiquidated") {
+        MarginAccountLiquidated::deserialize(&mut body).ok().map(ProgramEvent::MarginAccountLiquidation)
+    } else if discriminator == event_discriminator("ContributionEvent") {
+        ContributionEvent::deserialize(&mut body).ok().map(ProgramEvent::Contribution)
+    } else if discriminator == event_discriminator("InsuranceWithdrawalEvent") {
+        InsuranceWithdrawalEvent::deserialize(&mut body).ok().map(ProgramEvent::InsuranceWithdrawal)
+    } else if discriminator == event_discriminator("PositionOpened") {
+        PositionOpened::deserialize(&mut body).ok().map(ProgramEvent::PositionOpened)
+    } else if discriminator == event_discriminator("PositionModified") {
+        PositionModified::deserialize(&mut body).ok().map(ProgramEvent::PositionModified)
+    } else if discriminator == event_discriminator("PositionClosed") {
+        PositionClosed::deserialize(&mut body).ok().map(ProgramEvent::PositionClosed)
+    } else if discriminator == event_discriminator("InsuranceFundChanged") {
+        InsuranceFundChanged::deserialize(&mut body).ok().map(ProgramEvent::InsuranceFundChanged)
+    } else if discriminator == event_discriminator("InsuranceStaked") {
+        InsuranceStaked::deserialize(&mut body).ok().map(ProgramEvent::Stake)
+    } else if discriminator == event_discriminator("InsuranceUnstaked") {
+        InsuranceUnstaked::deserialize(&mut body).ok().map(ProgramEvent::Unstake)
+    } else {
+        None
+    }
+}
+
+/// Extracts every program event from a transaction's log messages.
+pub fn parse_logs(logs: &[String]) -> Vec<ProgramEvent> {
+    logs.iter()
+        .filter_map(|line| line.strip_prefix(PROGRAM_DATA_PREFIX))
+        .filter_map(|payload| STANDARD.decode(payload).ok())
+        .filter_map(|data| decode_event(&data))
+        .collect()
+}
@@ -0,0 +1,744 @@
+//! Builders for every program instruction. Account order follows the
+//! program's `Accounts` structs; PDAs are derived here, so callers only pass
+//! the accounts that are not.
+
+use std::collections::HashMap;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_program, sysvar};
+
+use crate::accounts::{
+    LiquidationMode, OnChainCollateralAsset, OnChainMarginAccount, OnChainMarginTier, OnChainMarket,
+};
+use crate::{pda, PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+/// Mirror of the program's `MarketParams`.
+#[derive(BorshSerialize, Debug, Clone)]
+pub struct MarketParams {
+    pub oracle: Pubkey,
+    pub oracle_program: Pubkey,
+    pub max_staleness_slots: u64,
+    pub max_confidence_bps: u16,
+    pub max_leverage: u16,
+    pub margin_tiers: [OnChainMarginTier; 4],
+    pub liquidation_fee_bps: u16,
+    pub liquidation_fee_max_bps: u16,
+    pub liquidation_fee_ramp_bps: u16,
+    pub insurance_fund_share_bps: u16,
+    pub close_factor_bps: u16,
+    pub target_margin_bps: u16,
+    pub min_position_size: u64,
+    pub liquidation_mode: LiquidationMode,
+    pub auction_start_discount_bps: u16,
+    pub auction_max_discount_bps: u16,
+    pub auction_duration_slots: u64,
+    pub max_funding_rate_bps: u16,
+    pub min_collateral: u64,
+    pub max_position_size: u64,
+    pub max_open_interest: u64,
+    pub opening_enabled: bool,
+}
+
+/// Mirror of the program's `CollateralAssetParams`.
+#[derive(BorshSerialize, Debug, Clone)]
+pub struct CollateralAssetParams {
+    pub oracle: Pubkey,
+    pub oracle_program: Pubkey,
+    pub max_staleness_slots: u64,
+    pub max_confidence_bps: u16,
+    pub weight_bps: u16,
+    pub liquidation_discount_bps: u16,
+}
+
+/// Arguments of `open_position`, in the program's order.
+#[derive(BorshSerialize, Debug, Clone)]
+pub struct OpenPositionArgs {
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: u64,
+    pub collateral: u64,
+    /// Worst acceptable oracle price: the most a long pays, the least a
+    /// short receives.
+    pub limit_price: u64,
+    pub leverage: u16,
+}
+
+/// Mirror of the program's `PositionHealth`, returned by simulating
+/// `get_position_health`.
+#[derive(BorshDeserialize, Debug)]
+pub struct OnChainPositionHealth {
+    pub price: u64,
+    pub collateral: u64,
+    pub unrealized_pnl: i64,
+    pub margin: i64,
+    pub maintenance_requirement: u64,
+    pub margin_ratio_bps: i64,
+    pub maintenance_margin_bps: u16,
+    pub liquidation_price: u64,
+    pub is_liquidatable: bool,
+}
+
+impl OnChainPositionHealth {
+    /// Decodes the return data of a simulated `get_position_health`.
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self::try_from_slice(data)?)
+    }
+}
+
+fn instruction(name: &str, args: impl BorshSerialize, accounts: Vec<AccountMeta>) -> Instruction {
+    let mut data = crate::discriminator("global", name).to_vec();
+    args.serialize(&mut data).expect("serializing into a Vec cannot fail");
+    Instruction { program_id: PROGRAM_ID, accounts, data }
+}
+
+/// Remaining accounts the program needs for a margin account's health: the
+/// market (writable) and oracle of every position, then the asset and oracle
+/// of every collateral deposit, in order. Pass the account as it will stand
+/// once the instruction has applied its own change.
+pub fn margin_health_accounts(
+    margin_account: &OnChainMarginAccount,
+    markets: &HashMap<String, OnChainMarket>,
+    assets: &HashMap<Pubkey, OnChainCollateralAsset>,
+) -> anyhow::Result<Vec<AccountMeta>> {
+    let mut accounts = Vec::new();
+    for position in &margin_account.positions {
+        let market = markets
+            .get(&position.symbol)
+            .ok_or_else(|| anyhow::anyhow!("No market for {}", position.symbol))?;
+        accounts.push(AccountMeta::new(pda::market(&position.symbol), false));
+        accounts.push(AccountMeta::new_readonly(market.oracle, false));
+    }
+    for deposit in &margin_account.deposits {
+        let asset = assets
+            .get(&deposit.mint)
+            .ok_or_else(|| anyhow::anyhow!("No collateral asset for {}", deposit.mint))?;
+        accounts.push(AccountMeta::new_readonly(pda::collateral_asset(&deposit.mint), false));
+        accounts.push(AccountMeta::new_readonly(asset.oracle, false));
+    }
+    Ok(accounts)
+}
+
+pub fn open_position(owner: &Pubkey, owner_token_account: &Pubkey, oracle: &Pubkey, args: OpenPositionArgs) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::position(owner, &args.symbol, args.is_long), false),
+        AccountMeta::new(pda::market(&args.symbol), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::vault(&args.symbol), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    instruction("open_position", args, accounts)
+}
+
+pub fn add_collateral(
+    owner: &Pubkey,
+    position: &Pubkey,
+    symbol: &str,
+    owner_token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new(pda::vault(symbol), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("add_collateral", amount, accounts)
+}
+
+pub fn withdraw_collateral(
+    owner: &Pubkey,
+    position: &Pubkey,
+    symbol: &str,
+    oracle: &Pubkey,
+    owner_token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::vault(symbol), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("withdraw_collateral", amount, accounts)
+}
+
+pub fn migrate_position(payer: &Pubkey, position: &Pubkey, symbol: &str) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new_readonly(pda::market(symbol), false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    instruction("migrate_position", (), accounts)
+}
+
+pub fn reduce_position(owner: &Pubkey, position: &Pubkey, symbol: &str, oracle: &Pubkey, amount: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new_readonly(*owner, true),
+    ];
+    instruction("reduce_position", amount, accounts)
+}
+
+pub fn close_position(
+    owner: &Pubkey,
+    position: &Pubkey,
+    symbol: &str,
+    oracle: &Pubkey,
+    owner_token_account: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new(pda::vault(symbol), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("close_position", (), accounts)
+}
+
+pub fn initialize_insurance_fund(authority: &Pubkey, mint: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new(pda::insurance_vault(), false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    instruction("initialize_insurance_fund", (), accounts)
+}
+
+/// Upgrades the originally deployed fund and creates its vault for `mint`;
+/// signed by the fund's authority.
+pub fn migrate_insurance_fund(authority: &Pubkey, mint: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new(pda::insurance_vault(), false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    instruction("migrate_insurance_fund", (), accounts)
+}
+
+pub fn propose_insurance_fund_authority(authority: &Pubkey, new_authority: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    instruction("propose_insurance_fund_authority", *new_authority, accounts)
+}
+
+pub fn accept_insurance_fund_authority(new_authority: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new_readonly(*new_authority, true),
+    ];
+    instruction("accept_insurance_fund_authority", (), accounts)
+}
+
+pub fn contribute_to_insurance(contributor: &Pubkey, contributor_token_account: &Pubkey, amount: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new(pda::insurance_vault(), false),
+        AccountMeta::new(*contributor_token_account, false),
+        AccountMeta::new_readonly(*contributor, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("contribute_to_insurance", amount, accounts)
+}
+
+pub fn withdraw_from_insurance(authority: &Pubkey, destination: &Pubkey, amount: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new(pda::insurance_vault(), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("withdraw_from_insurance", amount, accounts)
+}
+
+pub fn create_insurance_stake(owner: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_stake(owner), false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    instruction("create_insurance_stake", (), accounts)
+}
+
+pub fn stake_insurance(owner: &Pubkey, owner_token_account: &Pubkey, amount: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new(pda::insurance_vault(), false),
+        AccountMeta::new(pda::insurance_stake(owner), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("stake_insurance", amount, accounts)
+}
+
+pub fn request_unstake(owner: &Pubkey, shares: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_stake(owner), false),
+        AccountMeta::new_readonly(*owner, true),
+    ];
+    instruction("request_unstake", shares, accounts)
+}
+
+pub fn unstake_insurance(owner: &Pubkey, owner_token_account: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new(pda::insurance_vault(), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(pda::insurance_stake(owner), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("unstake_insurance", (), accounts)
+}
+
+pub fn initialize_config(authority: &Pubkey, guardian: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::config(), false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    instruction("initialize_config", *guardian, accounts)
+}
+
+pub fn propose_config_authority(authority: &Pubkey, new_authority: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::config(), false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    instruction("propose_config_authority", *new_authority, accounts)
+}
+
+pub fn accept_config_authority(new_authority: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::config(), false),
+        AccountMeta::new_readonly(*new_authority, true),
+    ];
+    instruction("accept_config_authority", (), accounts)
+}
+
+pub fn set_guardian(authority: &Pubkey, guardian: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::config(), false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    instruction("set_guardian", *guardian, accounts)
+}
+
+pub fn emergency_pause(guardian: &Pubkey, pause_opening: bool, pause_liquidations: bool) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::config(), false),
+        AccountMeta::new_readonly(*guardian, true),
+    ];
+    instruction("emergency_pause", (pause_opening, pause_liquidations), accounts)
+}
+
+pub fn unpause(guardian: &Pubkey, resume_opening: bool, resume_liquidations: bool) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::config(), false),
+        AccountMeta::new_readonly(*guardian, true),
+    ];
+    instruction("unpause", (resume_opening, resume_liquidations), accounts)
+}
+
+pub fn initialize_market(authority: &Pubkey, symbol: &str, params: MarketParams) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    instruction("initialize_market", (symbol.to_string(), params), accounts)
+}
+
+pub fn update_market(authority: &Pubkey, symbol: &str, params: MarketParams) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    instruction("update_market", params, accounts)
+}
+
+pub fn update_funding(symbol: &str, oracle: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(*oracle, false),
+    ];
+    instruction("update_funding", (), accounts)
+}
+
+pub fn initialize_vault(authority: &Pubkey, symbol: &str, mint: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(pda::insurance_fund(), false),
+        AccountMeta::new(pda::vault(symbol), false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    instruction("initialize_vault", symbol.to_string(), accounts)
+}
+
+pub fn initialize_margin_vault(authority: &Pubkey, mint: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(pda::insurance_fund(), false),
+        AccountMeta::new(pda::margin_vault(), false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    instruction("initialize_margin_vault", (), accounts)
+}
+
+/// Accounts shared by `liquidate_partial` and `liquidate_full`.
+fn liquidation_accounts(
+    liquidator: &Pubkey,
+    position: &Pubkey,
+    symbol: &str,
+    oracle: &Pubkey,
+    liquidator_token_account: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new(pda::vault(symbol), false),
+        AccountMeta::new(pda::insurance_vault(), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*liquidator_token_account, false),
+        AccountMeta::new(*liquidator, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ]
+}
+
+pub fn liquidate_partial(
+    liquidator: &Pubkey,
+    position: &Pubkey,
+    symbol: &str,
+    oracle: &Pubkey,
+    liquidator_token_account: &Pubkey,
+    liquidation_amount: u64,
+) -> Instruction {
+    let accounts = liquidation_accounts(liquidator, position, symbol, oracle, liquidator_token_account);
+    instruction("liquidate_partial", liquidation_amount, accounts)
+}
+
+pub fn liquidate_full(
+    liquidator: &Pubkey,
+    position: &Pubkey,
+    symbol: &str,
+    oracle: &Pubkey,
+    liquidator_token_account: &Pubkey,
+) -> Instruction {
+    let accounts = liquidation_accounts(liquidator, position, symbol, oracle, liquidator_token_account);
+    instruction("liquidate_full", (), accounts)
+}
+
+/// Read-only; simulate it and decode the return data as
+/// `OnChainPositionHealth`.
+pub fn get_position_health(position: &Pubkey, symbol: &str, oracle: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*position, false),
+        AccountMeta::new_readonly(pda::market(symbol), false),
+        AccountMeta::new_readonly(*oracle, false),
+    ];
+    instruction("get_position_health", (), accounts)
+}
+
+pub fn start_auction(keeper: &Pubkey, position: &Pubkey, symbol: &str, oracle: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new(pda::auction(position), false),
+        AccountMeta::new(*keeper, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    instruction("start_auction", (), accounts)
+}
+
+/// `keeper` is the auction's keeper, refunded the auction account's rent.
+pub fn take_auction(
+    liquidator: &Pubkey,
+    position: &Pubkey,
+    symbol: &str,
+    oracle: &Pubkey,
+    liquidator_token_account: &Pubkey,
+    keeper: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*position, false),
+        AccountMeta::new(pda::auction(position), false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new(pda::vault(symbol), false),
+        AccountMeta::new(pda::insurance_vault(), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*liquidator_token_account, false),
+        AccountMeta::new(*liquidator, true),
+        AccountMeta::new(*keeper, false),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("take_auction", amount, accounts)
+}
+
+/// Closes an auction that can no longer run; its rent goes back to `keeper`.
+pub fn cancel_auction(position: &Pubkey, symbol: &str, oracle: &Pubkey, keeper: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::auction(position), false),
+        AccountMeta::new_readonly(*position, false),
+        AccountMeta::new_readonly(pda::market(symbol), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new(*keeper, false),
+    ];
+    instruction("cancel_auction", (), accounts)
+}
+
+pub fn auto_deleverage(authority: &Pubkey, position: &Pubkey, symbol: &str, oracle: &Pubkey, amount: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(*position, false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    instruction("auto_deleverage", amount, accounts)
+}
+
+pub fn create_margin_account(owner: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::margin_account(owner), false),
+        AccountMeta::new(*owner, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    instruction("create_margin_account", (), accounts)
+}
+
+pub fn deposit_margin(owner: &Pubkey, owner_token_account: &Pubkey, amount: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::margin_account(owner), false),
+        AccountMeta::new(pda::margin_vault(), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("deposit_margin", amount, accounts)
+}
+
+/// `health_accounts` from `margin_health_accounts`.
+pub fn withdraw_margin(
+    owner: &Pubkey,
+    owner_token_account: &Pubkey,
+    amount: u64,
+    health_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(pda::margin_account(owner), false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::margin_vault(), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    accounts.extend(health_accounts);
+    instruction("withdraw_margin", amount, accounts)
+}
+
+/// `health_accounts` from `margin_health_accounts`, including the new
+/// position.
+pub fn open_cross_position(
+    owner: &Pubkey,
+    symbol: &str,
+    oracle: &Pubkey,
+    is_long: bool,
+    size: u64,
+    leverage: u16,
+    health_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(pda::margin_account(owner), false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new_readonly(*owner, true),
+    ];
+    accounts.extend(health_accounts);
+    instruction("open_cross_position", (symbol.to_string(), is_long, size, leverage), accounts)
+}
+
+pub fn close_cross_position(owner: &Pubkey, symbol: &str, oracle: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::margin_account(owner), false),
+        AccountMeta::new(pda::market(symbol), false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new(pda::vault(symbol), false),
+        AccountMeta::new(pda::margin_vault(), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("close_cross_position", symbol.to_string(), accounts)
+}
+
+/// `health_accounts` from `margin_health_accounts` for `account`; the market
+/// vaults its positions settle against are appended.
+pub fn liquidate_margin_account(
+    liquidator: &Pubkey,
+    margin_account: &Pubkey,
+    account: &OnChainMarginAccount,
+    liquidator_token_account: &Pubkey,
+    health_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*margin_account, false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::insurance_fund(), false),
+        AccountMeta::new(pda::margin_vault(), false),
+        AccountMeta::new(pda::insurance_vault(), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*liquidator_token_account, false),
+        AccountMeta::new(*liquidator, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    accounts.extend(health_accounts);
+    accounts.extend(account.positions.iter().map(|position| AccountMeta::new(pda::vault(&position.symbol), false)));
+    instruction("liquidate_margin_account", (), accounts)
+}
+
+pub fn register_collateral_asset(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    symbol: &str,
+    params: CollateralAssetParams,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::collateral_asset(mint), false),
+        AccountMeta::new(pda::collateral_vault(mint), false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    instruction("register_collateral_asset", (symbol.to_string(), params), accounts)
+}
+
+pub fn update_collateral_asset(authority: &Pubkey, mint: &Pubkey, params: CollateralAssetParams) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::collateral_asset(mint), false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    instruction("update_collateral_asset", params, accounts)
+}
+
+pub fn deposit_collateral_asset(owner: &Pubkey, mint: &Pubkey, owner_token_account: &Pubkey, amount: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(pda::margin_account(owner), false),
+        AccountMeta::new_readonly(pda::collateral_asset(mint), false),
+        AccountMeta::new(pda::collateral_vault(mint), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    instruction("deposit_collateral_asset", amount, accounts)
+}
+
+/// `health_accounts` from `margin_health_accounts`.
+pub fn withdraw_collateral_asset(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    owner_token_account: &Pubkey,
+    amount: u64,
+    health_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(pda::margin_account(owner), false),
+        AccountMeta::new_readonly(pda::collateral_asset(mint), false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new(pda::collateral_vault(mint), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*owner_token_account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    accounts.extend(health_accounts);
+    instruction("withdraw_collateral_asset", amount, accounts)
+}
+
+/// Buys `amount` of `asset` from a liquidatable margin account.
+/// `health_accounts` from `margin_health_accounts`.
+pub fn liquidate_collateral(
+    liquidator: &Pubkey,
+    margin_account: &Pubkey,
+    asset: &OnChainCollateralAsset,
+    liquidator_asset_account: &Pubkey,
+    liquidator_quote_account: &Pubkey,
+    amount: u64,
+    health_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*margin_account, false),
+        AccountMeta::new_readonly(pda::config(), false),
+        AccountMeta::new_readonly(pda::collateral_asset(&asset.mint), false),
+        AccountMeta::new_readonly(asset.oracle, false),
+        AccountMeta::new(pda::collateral_vault(&asset.mint), false),
+        AccountMeta::new(pda::margin_vault(), false),
+        AccountMeta::new_readonly(pda::vault_authority(), false),
+        AccountMeta::new(*liquidator_asset_account, false),
+        AccountMeta::new(*liquidator_quote_account, false),
+        AccountMeta::new_readonly(*liquidator, true),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ];
+    accounts.extend(health_accounts);
+    instruction("liquidate_collateral", amount, accounts)
+}
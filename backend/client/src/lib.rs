@@ -0,0 +1,30 @@
+//! Client for the `liquidation_engine` Anchor program: account and event
+//! decoders, error codes, PDA derivation and instruction builders.
+//!
+//! The program pins an older Solana SDK than the services, so it cannot be a
+//! dependency; the layouts here mirror its accounts, events and instruction
+//! arguments. The client only depends on `solana-program` within both SDKs'
+//! range so the program can build it too: its `tests/client_layouts.rs`
+//! checks every mirror and builder against the program's own types.
+
+pub mod accounts;
+pub mod errors;
+pub mod events;
+pub mod instructions;
+pub mod pda;
+
+use solana_program::hash::hash;
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+pub const PROGRAM_ID: Pubkey = pubkey!("HCJaVaM9GEH4nYoX6UquxWXroXJyjSLp2LLKCa1gg5NJ");
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Anchor's 8-byte discriminator for `namespace:name`, e.g. `account:Market`
+/// or `global:liquidate_full`.
+pub fn discriminator(namespace: &str, name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(format!("{}:{}", namespace, name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
@@ -0,0 +1,90 @@
+//! Addresses of the program's PDAs, from the same seeds as its account
+//! constraints.
+
+use solana_program::pubkey::Pubkey;
+
+use crate::{ASSOCIATED_TOKEN_PROGRAM_ID, PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const MARKET_SEED: &[u8] = b"market";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const INSURANCE_FUND_SEED: &[u8] = b"insurance_fund";
+pub const INSURANCE_VAULT_SEED: &[u8] = b"insurance_vault";
+pub const VAULT_AUTHORITY_SEED: &[u8] = b"vault_authority";
+pub const POSITION_SEED: &[u8] = b"position";
+pub const AUCTION_SEED: &[u8] = b"auction";
+pub const MARGIN_ACCOUNT_SEED: &[u8] = b"margin_account";
+pub const MARGIN_VAULT_SEED: &[u8] = b"margin_vault";
+pub const INSURANCE_STAKE_SEED: &[u8] = b"insurance_stake";
+pub const COLLATERAL_ASSET_SEED: &[u8] = b"collateral_asset";
+pub const COLLATERAL_VAULT_SEED: &[u8] = b"collateral_vault";
+
+fn find(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &PROGRAM_ID).0
+}
+
+pub fn config() -> Pubkey {
+    find(&[CONFIG_SEED])
+}
+
+pub fn market(symbol: &str) -> Pubkey {
+    find(&[MARKET_SEED, symbol.as_bytes()])
+}
+
+/// Token vault holding the collateral of `symbol`'s isolated positions.
+pub fn vault(symbol: &str) -> Pubkey {
+    find(&[VAULT_SEED, symbol.as_bytes()])
+}
+
+pub fn insurance_fund() -> Pubkey {
+    find(&[INSURANCE_FUND_SEED])
+}
+
+pub fn insurance_vault() -> Pubkey {
+    find(&[INSURANCE_VAULT_SEED])
+}
+
+/// Signs for transfers out of every program vault.
+pub fn vault_authority() -> Pubkey {
+    find(&[VAULT_AUTHORITY_SEED])
+}
+
+/// An owner's isolated position; one per market and side.
+pub fn position(owner: &Pubkey, symbol: &str, is_long: bool) -> Pubkey {
+    find(&[POSITION_SEED, owner.as_ref(), symbol.as_bytes(), &[is_long as u8]])
+}
+
+pub fn auction(position: &Pubkey) -> Pubkey {
+    find(&[AUCTION_SEED, position.as_ref()])
+}
+
+pub fn margin_account(owner: &Pubkey) -> Pubkey {
+    find(&[MARGIN_ACCOUNT_SEED, owner.as_ref()])
+}
+
+/// Token vault holding the quote collateral of every margin account.
+pub fn margin_vault() -> Pubkey {
+    find(&[MARGIN_VAULT_SEED])
+}
+
+pub fn insurance_stake(owner: &Pubkey) -> Pubkey {
+    find(&[INSURANCE_STAKE_SEED, owner.as_ref()])
+}
+
+pub fn collateral_asset(mint: &Pubkey) -> Pubkey {
+    find(&[COLLATERAL_ASSET_SEED, mint.as_ref()])
+}
+
+/// Token vault holding every margin account's deposits of `mint`.
+pub fn collateral_vault(mint: &Pubkey) -> Pubkey {
+    find(&[COLLATERAL_VAULT_SEED, mint.as_ref()])
+}
+
+/// `owner`'s associated token account for `mint`.
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
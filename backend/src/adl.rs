@@ -0,0 +1,114 @@
+//! Auto-deleveraging ranking. When a bankruptcy leaves a market with bad debt
+//! the insurance fund could not cover, the program recovers it from
+//! profitable positions on the other side by closing them at the bankruptcy
+//! price. Positions go in order of profit ratio times effective leverage, so
+//! the most profitable, most levered positions are deleveraged first.
+
+use crate::monitor::Position;
+use liquidation_engine_client::accounts::OnChainMarket;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdlCandidate {
+    pub position_id: String,
+    pub owner: String,
+    pub symbol: String,
+    pub is_long: bool,
+    pub size: f64,
+    pub unrealized_pnl: f64,
+    pub effective_leverage: f64,
+    pub score: f64,
+    /// Size that would recover the market's outstanding bad debt by itself,
+    /// capped at the position's size.
+    pub deleverage_size: f64,
+}
+
+/// Ranks every position eligible for ADL, highest score first. Only markets
+/// with uncovered bad debt contribute candidates.
+pub fn rank_candidates(
+    positions: &[Position],
+    markets: &HashMap<String, OnChainMarket>,
+    prices: &HashMap<String, f64>,
+) -> Vec<AdlCandidate> {
+    let scale = 1_000_000.0;
+    let mut candidates: Vec<AdlCandidate> = positions
+        .iter()
+        .filter_map(|position| {
+            let market = markets.get(&position.symbol)?;
+            // Debt from unpaid funding alone has no bankruptcy price to
+            // close at, and the program rejects deleveraging against it
+            if market.uncovered_bad_debt == 0
+                || market.bankruptcy_price == 0
+                || position.is_long == market.bankrupt_is_long
+            {
+                return None;
+            }
+            let price = *prices.get(&position.symbol)?;
+
+            let unrealized_pnl = if position.is_long {
+                position.size * (price - position.entry_price)
+            } else {
+                position.size * (position.entry_price - price)
+            };
+            let equity = position.collateral + unrealized_pnl - position.unsettled_funding;
+            if unrealized_pnl <= 0.0 || equity <= 0.0 || position.collateral <= 0.0 {
+                return None;
+            }
+            // Each unit closed at the bankruptcy price gives up this much
+            // profit. With the mark beyond the bankruptcy price closing gives
+            // up nothing, and the program rejects it.
+            let bankruptcy_price = market.bankruptcy_price as f64 / scale;
+            let forgone_per_unit = if position.is_long {
+                price - bankruptcy_price
+            } else {
+                bankruptcy_price - price
+            };
+            if forgone_per_unit <= 0.0 {
+                return None;
+            }
+            let deleverage_size = (market.uncovered_bad_debt as f64 / scale / forgone_per_unit).min(position.size);
+
+            let effective_leverage = position.size * price / equity;
+            let score = unrealized_pnl / position.collateral * effective_leverage;
+
+            Some(AdlCandidate {
+                position_id: position.id.clone(),
+                owner: position.owner.to_string(),
+                symbol: position.symbol.clone(),
+                is_long: position.is_long,
+                size: position.size,
+                unrealized_pnl,
+                effective_leverage,
+                score,
+                deleverage_size,
+            })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    candidates
+}
+
+/// Latest ranking computed by the monitor, served by the API.
+pub struct AdlRanking {
+    candidates: Mutex<Vec<AdlCandidate>>,
+}
+
+impl AdlRanking {
+    pub fn new() -> Self {
+        Self {
+            candidates: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn update(&self, candidates: Vec<AdlCandidate>) {
+        *self.candidates.lock().unwrap() = candidates;
+    }
+
+    pub fn snapshot(&self) -> Vec<AdlCandidate> {
+        self.candidates.lock().unwrap().clone()
+    }
+}
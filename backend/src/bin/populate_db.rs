@@ -1,7 +1,10 @@
 use anyhow::Result;
-use chrono::{Utc, Duration};
+use liquidation_engine_client::pda;
 use rand::Rng;
+use solana_sdk::pubkey::Pubkey;
 
+#[path = "../db.rs"]
+#[allow(dead_code)]
 mod db;
 use db::Database;
 
@@ -38,14 +41,16 @@ async fn main() -> Result<()> {
         
         let health_factor = rng.gen_range(0.85..1.09);
         let is_full = health_factor < 0.95;
+        let owner = Pubkey::new_unique();
+        let position = pda::position(&owner, symbol, rng.gen_bool(0.5));
         
         db.record_liquidation(
-            &format!("Pos{}...{}", i, rng.gen_range(1000..9999)),
-            &format!("Own{}...{}", i, rng.gen_range(1000..9999)),
+            &position.to_string(),
+            &owner.to_string(),
             symbol,
             liquidated_size,
             liquidation_price,
-            &format!("Liq{}...{}", i, rng.gen_range(1000..9999)),
+            &Pubkey::new_unique().to_string(),
             liquidated_size / 40, // 2.5% reward
             if is_full { rng.gen_range(0..100_000) } else { 0 },
             health_factor,
@@ -57,17 +62,20 @@ async fn main() -> Result<()> {
 
     // Create position snapshots for 8 positions over past 24 hours
     let positions = vec![
-        ("Pos1...2a3b", "Own1...4c5d", "SOL/USD", 850_000, 12_450_000, 21_500_000),
-        ("Pos2...6e7f", "Own2...8g9h", "BTC/USD", 1_200_000, 45_200_000, 44_500_000_000),
-        ("Pos3...0i1j", "Own3...2k3l", "ETH/USD", 15_500_000, 28_900_000, 2_350_000_000),
-        ("Pos4...4m5n", "Own4...6o7p", "SOL/USD", 520_000, 8_750_000, 22_100_000),
-        ("Pos5...8q9r", "Own5...0s1t", "AVAX/USD", 580_000, 15_600_000, 35_800_000),
-        ("Pos6...2u3v", "Own6...4w5x", "BTC/USD", 1_800_000, 67_300_000, 44_200_000_000),
-        ("Pos7...6y7z", "Own7...8a9b", "ETH/USD", 10_200_000, 19_400_000, 2_420_000_000),
-        ("Pos8...0c1d", "Own8...2e3f", "SOL/USD", 1_150_000, 22_100_000, 21_800_000),
+        ("SOL/USD", 850_000, 12_450_000, 21_500_000),
+        ("BTC/USD", 1_200_000, 45_200_000, 44_500_000_000),
+        ("ETH/USD", 15_500_000, 28_900_000, 2_350_000_000),
+        ("SOL/USD", 520_000, 8_750_000, 22_100_000),
+        ("AVAX/USD", 580_000, 15_600_000, 35_800_000),
+        ("BTC/USD", 1_800_000, 67_300_000, 44_200_000_000),
+        ("ETH/USD", 10_200_000, 19_400_000, 2_420_000_000),
+        ("SOL/USD", 1_150_000, 22_100_000, 21_800_000),
     ];
 
-    for (pos, owner, symbol, size, collateral, entry_price) in positions {
+    for (symbol, size, collateral, entry_price) in positions {
+        let owner = Pubkey::new_unique();
+        let pos = pda::position(&owner, symbol, true).to_string();
+        let owner = owner.to_string();
         // Create 24 snapshots (one per hour for past day)
         for hour in 0..24 {
             let price_variation = rng.gen_range(0.95..1.05);
@@ -78,8 +86,8 @@ async fn main() -> Result<()> {
             let health_factor = (collateral as f64 / position_value) * 2.0;
             
             db.save_position_snapshot(
-                pos,
-                owner,
+                &pos,
+                &owner,
                 symbol,
                 size,
                 collateral,
@@ -41,6 +41,15 @@ pub struct InsuranceFundTransaction {
     pub timestamp: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InsuranceSharePrice {
+    pub id: i64,
+    pub balance: i64,
+    pub total_shares: i64,
+    pub share_price: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct FailedLiquidation {
     pub id: i64,
@@ -131,6 +140,22 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS insurance_share_prices (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                balance INTEGER NOT NULL,
+                total_shares INTEGER NOT NULL,
+                share_price REAL NOT NULL,
+                timestamp TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE INDEX IF NOT EXISTS idx_share_price_timestamp 
+                ON insurance_share_prices(timestamp DESC);
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+
         sqlx::query(
             r#"
             CREATE TABLE IF NOT EXISTS failed_liquidations (
@@ -262,6 +287,34 @@ impl Database {
         Ok(record)
     }
 
+    /// Record an insurance fund share price sample
+    pub async fn record_insurance_share_price(
+        &self,
+        balance: i64,
+        total_shares: i64,
+        share_price: f64,
+    ) -> Result<i64> {
+        let timestamp = Utc::now().to_rfc3339();
+
+        let record = sqlx::query_scalar::<_, i64>(
+            r#"
+            INSERT INTO insurance_share_prices (
+                balance, total_shares, share_price, timestamp
+            )
+            VALUES (?, ?, ?, ?)
+            RETURNING id
+            "#
+        )
+        .bind(balance)
+        .bind(total_shares)
+        .bind(share_price)
+        .bind(timestamp)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
     /// Record failed liquidation
     pub async fn record_failed_liquidation(
         &self,
@@ -370,6 +423,23 @@ impl Database {
 
         Ok(records)
     }
+
+    /// Get insurance fund share price history
+    pub async fn get_insurance_share_price_history(&self, limit: i64) -> Result<Vec<InsuranceSharePrice>> {
+        let records = sqlx::query_as::<_, InsuranceSharePrice>(
+            r#"
+            SELECT id, balance, total_shares, share_price, timestamp
+            FROM insurance_share_prices
+            ORDER BY timestamp DESC
+            LIMIT ?
+            "#
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
 }
 
 #[derive(Debug, sqlx::FromRow, serde::Serialize)]
@@ -449,6 +519,24 @@ impl sqlx::FromRow<'_, sqlx::sqlite::SqliteRow> for InsuranceFundTransaction {
     }
 }
 
+impl sqlx::FromRow<'_, sqlx::sqlite::SqliteRow> for InsuranceSharePrice {
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+        let timestamp_str: String = row.try_get("timestamp")?;
+        let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?
+            .with_timezone(&Utc);
+            
+        Ok(Self {
+            id: row.try_get("id")?,
+            balance: row.try_get("balance")?,
+            total_shares: row.try_get("total_shares")?,
+            share_price: row.try_get("share_price")?,
+            timestamp,
+        })
+    }
+}
+
 impl sqlx::FromRow<'_, sqlx::sqlite::SqliteRow> for FailedLiquidation {
     fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
         use sqlx::Row;
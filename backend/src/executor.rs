@@ -1,21 +1,30 @@
 use log::{info, error};
+use crate::adl::AdlCandidate;
 use crate::monitor::Position;
 use crate::db::Database;
-use solana_client::rpc_client::RpcClient;
+use liquidation_engine_client::events::{self, LiquidationEvent, ProgramEvent};
+use liquidation_engine_client::accounts::{
+    LiquidationMode, OnChainAuction, OnChainCollateralAsset, OnChainCollateralBalance, OnChainInsuranceFund,
+    OnChainMarginAccount, OnChainMarket, ProgramAccount,
+};
+use liquidation_engine_client::errors::LiquidationError;
+use liquidation_engine_client::instructions::{self, OnChainPositionHealth};
+use liquidation_engine_client::pda;
+use solana_client::{client_error::ClientError, rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
 use solana_sdk::{
-    signature::{Keypair, Signer, read_keypair_file},
+    signature::{Keypair, Signature, Signer, read_keypair_file},
     transaction::Transaction,
     pubkey::Pubkey,
-    instruction::{Instruction, AccountMeta},
     commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
+    instruction::{Instruction, InstructionError},
+    transaction::TransactionError,
 };
+use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionEncoding};
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
-use borsh::BorshSerialize;
-
-// Program ID
-const PROGRAM_ID: &str = "HCJaVaM9GEH4nYoX6UquxWXroXJyjSLp2LLKCa1gg5NJ";
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 
 pub struct LiquidationExecutor {
     rpc_client: Arc<RpcClient>,
@@ -23,8 +32,30 @@ pub struct LiquidationExecutor {
     db: Arc<Database>,
 }
 
-#[derive(BorshSerialize)]
-struct LiquidateFullArgs {}
+/// The program error behind a failed liquidation, so oracle problems can be
+/// told apart from a position that simply isn't liquidatable.
+fn program_error(e: &ClientError) -> Option<LiquidationError> {
+    match e.get_transaction_error()? {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => LiquidationError::from_code(code),
+        _ => None,
+    }
+}
+
+fn failure_reason(e: &ClientError) -> String {
+    match program_error(e) {
+        Some(error) => format!("{:?}: {}", error, e),
+        None => e.to_string(),
+    }
+}
+
+/// `instruction` behind a compute unit limit of `units` and a priority fee.
+fn prioritized(units: u32, instruction: Instruction) -> Vec<Instruction> {
+    vec![
+        ComputeBudgetInstruction::set_compute_unit_limit(units),
+        ComputeBudgetInstruction::set_compute_unit_price(1_000_000), // 1,000,000 micro-lamports
+        instruction,
+    ]
+}
 
 impl LiquidationExecutor {
     pub fn new(db: Arc<Database>) -> Self {
@@ -48,78 +79,268 @@ impl LiquidationExecutor {
     pub async fn liquidate_position(
         &self,
         position: &Position,
+        market: &OnChainMarket,
+        auction: Option<&OnChainAuction>,
         mark_price: f64,
         margin_ratio: f64,
     ) -> anyhow::Result<()> {
         info!("Executing liquidation for position: {}", position.id);
-        
-        let maintenance_req = self.get_maintenance_margin_ratio(position.leverage as u16);
-        let critical_threshold = maintenance_req * 0.1; 
 
-        if margin_ratio < critical_threshold {
-            info!("Critical margin level ({:.4} < {:.4}). Executing FULL liquidation.", margin_ratio, critical_threshold);
-            self.execute_full_liquidation(position, mark_price, margin_ratio).await?;
-        } else {
-            let target_margin = maintenance_req * 1.2;
-            let liquidation_amount = self.calculate_partial_amount(position, mark_price, target_margin);
-            
-            info!("Margin level ({:.4}). Executing PARTIAL liquidation of size {:.4}.", margin_ratio, liquidation_amount);
-            // For this demo, we'll just do full liquidation to prove the concept
-            self.execute_full_liquidation(position, mark_price, margin_ratio).await?;
+        // Auction markets reject direct liquidation: the first pass opens
+        // the auction and a later one takes it
+        if market.liquidation_mode == LiquidationMode::Auction {
+            return match auction {
+                None => self.start_auction(position, market).await,
+                Some(auction) => self.take_auction(position, market, auction, margin_ratio).await,
+            };
+        }
+        
+        // The program only allows a full liquidation when no partial within
+        // the close factor restores the target margin
+        match self.calculate_partial_amount(position, market, mark_price) {
+            Some(amount) => {
+                info!("Margin level ({:.4}). Executing PARTIAL liquidation of size {:.6}.",
+                    margin_ratio, amount as f64 / 1_000_000.0);
+                self.execute_partial_liquidation(position, market, amount, mark_price, margin_ratio).await?;
+            }
+            None => {
+                info!("Margin level ({:.4}) is beyond a partial liquidation. Executing FULL liquidation.", margin_ratio);
+                self.execute_full_liquidation(position, market, mark_price, margin_ratio).await?;
+            }
         }
 
         Ok(())
     }
 
-    fn get_maintenance_margin_ratio(&self, leverage: u16) -> f64 {
-        match leverage {
-            1..=20 => 0.025,
-            21..=50 => 0.01,
-            51..=100 => 0.005,
-            101..=500 => 0.0025,
-            501..=1000 => 0.001,
-            _ => 0.025,
+    /// Simulates `get_position_health` for an authoritative view of the
+    /// position at the program's oracle price. Nothing is submitted.
+    pub fn simulate_position_health(
+        &self,
+        position: &Position,
+        market: &OnChainMarket,
+    ) -> anyhow::Result<OnChainPositionHealth> {
+        let position_pubkey = Pubkey::from_str(&position.id)?;
+        let instruction = instructions::get_position_health(&position_pubkey, &position.symbol, &market.oracle);
+
+        let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.payer.pubkey()),
+            &[&self.payer],
+            recent_blockhash,
+        );
+
+        let result = self.rpc_client.simulate_transaction(&transaction)?.value;
+        if let Some(err) = result.err {
+            anyhow::bail!("Simulation failed: {:?}", err);
         }
+        let return_data = result
+            .return_data
+            .ok_or_else(|| anyhow::anyhow!("get_position_health returned no data"))?;
+        let data = STANDARD.decode(&return_data.data.0)?;
+        OnChainPositionHealth::decode(&data)
     }
 
-    fn calculate_partial_amount(&self, position: &Position, _mark_price: f64, _target_margin: f64) -> f64 {
-        position.size * 0.5
+    /// The `LiquidationEvent` emitted by a confirmed transaction, carrying
+    /// what the program actually settled.
+    fn confirmed_liquidation_event(&self, signature: &Signature) -> anyhow::Result<LiquidationEvent> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Json),
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+        };
+        let tx = self.rpc_client.get_transaction_with_config(signature, config)?;
+        let logs = match tx.transaction.meta.map(|meta| meta.log_messages) {
+            Some(OptionSerializer::Some(logs)) => logs,
+            _ => anyhow::bail!("Transaction {} has no logs", signature),
+        };
+        events::parse_logs(&logs)
+            .into_iter()
+            .find_map(|event| match event {
+                ProgramEvent::Liquidation(e) => Some(e),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("Transaction {} emitted no liquidation event", signature))
     }
 
-    async fn execute_full_liquidation(&self, position: &Position, mark_price: f64, margin_ratio: f64) -> anyhow::Result<()> {
+    /// Size of the partial liquidation that brings `position` back up to the
+    /// market's target margin at `mark_price`; `None` when the program would
+    /// require a full liquidation instead. Sized in integers exactly as the
+    /// program does, so rounding never takes it past the program's cap.
+    fn calculate_partial_amount(&self, position: &Position, market: &OnChainMarket, mark_price: f64) -> Option<u64> {
+        let to_units = |value: f64| (value * 1_000_000.0).round() as i128;
+        let price = to_units(mark_price);
+        let price_delta = if position.is_long {
+            price - to_units(position.entry_price)
+        } else {
+            to_units(position.entry_price) - price
+        };
+        let margin = to_units(position.collateral) - to_units(position.unsettled_funding)
+            + price_delta * position.raw_size as i128 / 1_000_000;
+        market.partial_liquidation_size(position.raw_size, margin, price as u64, position.leverage)
+    }
+
+    /// Liquidator's share of the health-scaled fee on `size` of the
+    /// position, using the program's curve. Approximate: it does not account
+    /// for fees the collateral or the insurance fund cannot pay.
+    fn expected_reward(
+        &self,
+        position: &Position,
+        market: &OnChainMarket,
+        size: f64,
+        mark_price: f64,
+        margin_ratio: f64,
+    ) -> f64 {
+        let margin_ratio_bps = (margin_ratio * 10_000.0).floor() as i64;
+        let fee_bps = market.liquidation_fee_bps(margin_ratio_bps, position.leverage) as f64;
+        let liquidator_share = 1.0 - market.insurance_fund_share_bps as f64 / 10_000.0;
+        size * mark_price * fee_bps / 10_000.0 * liquidator_share
+    }
+
+    async fn execute_partial_liquidation(
+        &self,
+        position: &Position,
+        market: &OnChainMarket,
+        amount: u64,
+        mark_price: f64,
+        margin_ratio: f64,
+    ) -> anyhow::Result<()> {
+        info!("Submitting PARTIAL liquidation tx for position {}...", position.id);
+
+        let position_pubkey = Pubkey::from_str(&position.id)?;
+
+        // Rewards are paid into the payer's associated token account
+        let fund = OnChainInsuranceFund::decode(&self.rpc_client.get_account_data(&pda::insurance_fund())?)?;
+        let liquidator_token_account = pda::associated_token_address(&self.payer.pubkey(), &fund.mint);
+
+        let instruction = instructions::liquidate_partial(
+            &self.payer.pubkey(),
+            &position_pubkey,
+            &position.symbol,
+            &market.oracle,
+            &liquidator_token_account,
+            amount,
+        );
+        let expected_reward =
+            self.expected_reward(position, market, amount as f64 / 1_000_000.0, mark_price, margin_ratio);
+        self.submit_liquidation(position, instruction, margin_ratio, Some(expected_reward)).await
+    }
+
+    async fn execute_full_liquidation(
+        &self,
+        position: &Position,
+        market: &OnChainMarket,
+        mark_price: f64,
+        margin_ratio: f64,
+    ) -> anyhow::Result<()> {
         info!("Submitting FULL liquidation tx for position {}...", position.id);
         
-        let program_id = Pubkey::from_str(PROGRAM_ID)?;
         let position_pubkey = Pubkey::from_str(&position.id)?;
-        
-        // Derive Insurance Fund PDA
-        let (insurance_fund_pda, _bump) = Pubkey::find_program_address(&[b"insurance_fund"], &program_id);
-        info!("Derived Insurance Fund PDA: {}", insurance_fund_pda);
 
-        // Construct Instruction
-        let discriminator: [u8; 8] = [184, 122, 137, 225, 224, 51, 220, 170];
-        
-        let mut data = Vec::new();
-        data.extend_from_slice(&discriminator);
-        
-        let accounts = vec![
-            AccountMeta::new(position_pubkey, false),
-            AccountMeta::new(insurance_fund_pda, false),
-            AccountMeta::new(self.payer.pubkey(), true),
-        ];
-
-        let instruction = Instruction::new_with_bytes(
-            program_id,
-            &data,
-            accounts,
+        // Rewards are paid into the payer's associated token account
+        let fund = OnChainInsuranceFund::decode(&self.rpc_client.get_account_data(&pda::insurance_fund())?)?;
+        let liquidator_token_account = pda::associated_token_address(&self.payer.pubkey(), &fund.mint);
+
+        let instruction = instructions::liquidate_full(
+            &self.payer.pubkey(),
+            &position_pubkey,
+            &position.symbol,
+            &market.oracle,
+            &liquidator_token_account,
         );
+        let expected_reward = self.expected_reward(position, market, position.size, mark_price, margin_ratio);
+        self.submit_liquidation(position, instruction, margin_ratio, Some(expected_reward)).await
+    }
+
+    /// Opens a Dutch auction on `position`, with the payer as its keeper.
+    async fn start_auction(&self, position: &Position, market: &OnChainMarket) -> anyhow::Result<()> {
+        info!("Submitting auction start tx for position {}...", position.id);
+
+        let position_pubkey = Pubkey::from_str(&position.id)?;
+        let instruction =
+            instructions::start_auction(&self.payer.pubkey(), &position_pubkey, &position.symbol, &market.oracle);
+        self.submit(&[instruction], "Auction start", Some(&position.id)).await?;
+        Ok(())
+    }
+
+    /// Takes the whole of an auctioned position at the auction's current
+    /// discount.
+    async fn take_auction(
+        &self,
+        position: &Position,
+        market: &OnChainMarket,
+        auction: &OnChainAuction,
+        margin_ratio: f64,
+    ) -> anyhow::Result<()> {
+        info!("Submitting auction take tx for position {}...", position.id);
+
+        let position_pubkey = Pubkey::from_str(&position.id)?;
+        let fund = OnChainInsuranceFund::decode(&self.rpc_client.get_account_data(&pda::insurance_fund())?)?;
+        let liquidator_token_account = pda::associated_token_address(&self.payer.pubkey(), &fund.mint);
 
-        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(200_000);
-        let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(1_000_000); // 1,000,000 micro-lamports
+        let instruction = instructions::take_auction(
+            &self.payer.pubkey(),
+            &position_pubkey,
+            &position.symbol,
+            &market.oracle,
+            &liquidator_token_account,
+            &auction.keeper,
+            position.raw_size,
+        );
+        // The auction's discount, not the fee curve, sets its reward
+        self.submit_liquidation(position, instruction, margin_ratio, None).await
+    }
+
+    /// Sends a liquidation instruction and records what the program settled
+    /// from its `LiquidationEvent`, logged against `expected_reward` when
+    /// there is an estimate.
+    async fn submit_liquidation(
+        &self,
+        position: &Position,
+        instruction: Instruction,
+        margin_ratio: f64,
+        expected_reward: Option<f64>,
+    ) -> anyhow::Result<()> {
+        let signature = self
+            .submit(&prioritized(200_000, instruction), "Liquidation", Some(&position.id))
+            .await?;
+
+        // Record to DB what the program settled
+        let event = match self.confirmed_liquidation_event(&signature) {
+            Ok(event) => event,
+            Err(e) => {
+                error!("Failed to read liquidation event: {}", e);
+                return Ok(());
+            }
+        };
+        let reward = event.liquidator_reward as f64 / 1_000_000.0;
+        match expected_reward {
+            Some(expected) => info!("Liquidator reward {:.6} (expected {:.6})", reward, expected),
+            None => info!("Liquidator reward {:.6}", reward),
+        }
+        let _ = self.db.record_liquidation(
+            &position.id,
+            &position.owner.to_string(),
+            &position.symbol,
+            event.liquidated_size as i64,
+            event.liquidation_price as i64,
+            &self.payer.pubkey().to_string(),
+            event.liquidator_reward as i64,
+            event.bad_debt as i64,
+            margin_ratio,
+            event.is_full,
+        ).await.map_err(|e| error!("Failed to record liquidation to DB: {}", e));
+
+        Ok(())
+    }
 
+    /// Signs and sends `instructions` with the payer, logging the outcome
+    /// as a `kind` transaction. Failures are recorded against `id` when
+    /// given, with the program error behind them.
+    async fn submit(&self, instructions: &[Instruction], kind: &str, id: Option<&str>) -> anyhow::Result<Signature> {
         let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
         let transaction = Transaction::new_signed_with_payer(
-            &[compute_budget_ix, priority_fee_ix, instruction],
+            instructions,
             Some(&self.payer.pubkey()),
             &[&self.payer],
             recent_blockhash,
@@ -127,35 +348,106 @@ impl LiquidationExecutor {
 
         match self.rpc_client.send_and_confirm_transaction(&transaction) {
             Ok(signature) => {
-                info!("Full liquidation tx confirmed! Signature: {}", signature);
-                
-                // Record to DB
-                let reward = (position.size * mark_price * 0.025) as i64; // Approx 2.5% reward
-                let _ = self.db.record_liquidation(
-                    &position.id,
-                    &position.owner.to_string(),
-                    &position.symbol,
-                    position.size as i64,
-                    mark_price as i64,
-                    &self.payer.pubkey().to_string(),
-                    reward,
-                    0, // bad debt
-                    margin_ratio,
-                    true // is_full
-                ).await.map_err(|e| error!("Failed to record liquidation to DB: {}", e));
-                
-                Ok(())
+                info!("{} tx confirmed! Signature: {}", kind, signature);
+                Ok(signature)
             }
             Err(e) => {
-                error!("Liquidation transaction failed: {}", e);
-                // Record failure
-                let _ = self.db.record_failed_liquidation(
-                    &position.id,
-                    &e.to_string(),
-                    1
-                ).await;
+                let reason = failure_reason(&e);
+                error!("{} transaction failed: {}", kind, reason);
+                if let Some(id) = id {
+                    let _ = self.db.record_failed_liquidation(id, &reason, 1).await;
+                }
                 Err(anyhow::anyhow!("Transaction failed: {}", e))
             }
         }
     }
+
+    /// Liquidates a cross-margin account. The program picks which positions
+    /// to close; it needs every position's market (writable) and oracle.
+    pub async fn liquidate_margin_account(
+        &self,
+        account_pubkey: &Pubkey,
+        margin_account: &OnChainMarginAccount,
+        markets: &HashMap<String, OnChainMarket>,
+        assets: &HashMap<Pubkey, OnChainCollateralAsset>,
+    ) -> anyhow::Result<()> {
+        info!("Submitting account liquidation tx for margin account {}...", account_pubkey);
+
+        let fund = OnChainInsuranceFund::decode(&self.rpc_client.get_account_data(&pda::insurance_fund())?)?;
+        let liquidator_token_account = pda::associated_token_address(&self.payer.pubkey(), &fund.mint);
+        let instruction = instructions::liquidate_margin_account(
+            &self.payer.pubkey(),
+            account_pubkey,
+            margin_account,
+            &liquidator_token_account,
+            instructions::margin_health_accounts(margin_account, markets, assets)?,
+        );
+
+        let id = account_pubkey.to_string();
+        self.submit(&prioritized(400_000, instruction), "Account liquidation", Some(&id)).await?;
+        Ok(())
+    }
+
+    /// Buys a liquidatable margin account's whole `deposit` at the asset's
+    /// liquidation discount, paying in the quote token.
+    pub async fn liquidate_collateral(
+        &self,
+        account_pubkey: &Pubkey,
+        margin_account: &OnChainMarginAccount,
+        markets: &HashMap<String, OnChainMarket>,
+        assets: &HashMap<Pubkey, OnChainCollateralAsset>,
+        deposit: &OnChainCollateralBalance,
+    ) -> anyhow::Result<()> {
+        info!("Submitting collateral liquidation tx for margin account {} ({})...", account_pubkey, deposit.mint);
+
+        let asset = assets
+            .get(&deposit.mint)
+            .ok_or_else(|| anyhow::anyhow!("No collateral asset for {}", deposit.mint))?;
+        let fund = OnChainInsuranceFund::decode(&self.rpc_client.get_account_data(&pda::insurance_fund())?)?;
+        let liquidator_asset_account = pda::associated_token_address(&self.payer.pubkey(), &deposit.mint);
+        let liquidator_quote_account = pda::associated_token_address(&self.payer.pubkey(), &fund.mint);
+        let instruction = instructions::liquidate_collateral(
+            &self.payer.pubkey(),
+            account_pubkey,
+            asset,
+            &liquidator_asset_account,
+            &liquidator_quote_account,
+            deposit.amount,
+            instructions::margin_health_accounts(margin_account, markets, assets)?,
+        );
+
+        let id = account_pubkey.to_string();
+        self.submit(&prioritized(400_000, instruction), "Collateral liquidation", Some(&id)).await?;
+        Ok(())
+    }
+
+    /// Upgrades a position written by an older program version in place;
+    /// every other instruction rejects it until then.
+    pub async fn migrate_position(&self, position_pubkey: &Pubkey, symbol: &str) -> anyhow::Result<()> {
+        info!("Submitting migration tx for position {}...", position_pubkey);
+
+        let instruction = instructions::migrate_position(&self.payer.pubkey(), position_pubkey, symbol);
+        self.submit(&[instruction], "Migration", None).await?;
+        Ok(())
+    }
+
+    /// Deleverages `candidate` by its ADL size. The instruction is restricted
+    /// to the config authority, so the payer must hold that role.
+    pub async fn auto_deleverage(&self, candidate: &AdlCandidate, market: &OnChainMarket) -> anyhow::Result<()> {
+        info!("Submitting ADL tx for position {}...", candidate.position_id);
+
+        let position_pubkey = Pubkey::from_str(&candidate.position_id)?;
+
+        let scale = 1_000_000.0;
+        let amount = ((candidate.deleverage_size * scale).ceil() as u64).min((candidate.size * scale).round() as u64);
+        let instruction = instructions::auto_deleverage(
+            &self.payer.pubkey(),
+            &position_pubkey,
+            &candidate.symbol,
+            &market.oracle,
+            amount,
+        );
+        self.submit(&[instruction], "ADL", None).await?;
+        Ok(())
+    }
 }
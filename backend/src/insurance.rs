@@ -1,32 +1,28 @@
 use std::sync::Arc;
 use tokio::time::{interval, Duration};
 use log::{info, warn, error};
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::{pubkey::Pubkey, commitment_config::CommitmentConfig};
-use borsh::BorshDeserialize;
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_sdk::{pubkey::Pubkey, commitment_config::CommitmentConfig, signature::Signature};
+use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionEncoding};
 use crate::db::Database;
+use liquidation_engine_client::events::{self, ProgramEvent};
 use std::str::FromStr;
-
-// Program ID
-const PROGRAM_ID: &str = "HCJaVaM9GEH4nYoX6UquxWXroXJyjSLp2LLKCa1gg5NJ";
-
-#[derive(BorshDeserialize, Debug)]
-pub struct OnChainInsuranceFund {
-    pub authority: Pubkey,
-    pub balance: u64,
-    pub total_contributions: u64,
-    pub total_bad_debt_covered: u64,
-    pub utilization_ratio: u64,
-}
+use liquidation_engine_client::accounts::{OnChainInsuranceFund, ProgramAccount};
+use liquidation_engine_client::pda;
 
 use crate::websocket::{Broadcaster, WsMessage};
 
+/// Largest page `getSignaturesForAddress` returns.
+const SIGNATURE_PAGE_SIZE: usize = 1000;
+
 pub struct InsuranceFundManager {
     rpc_client: Arc<RpcClient>,
     db: Arc<Database>,
     broadcaster: Arc<Broadcaster>,
     check_interval_ms: u64,
     last_balance: u64,
+    last_total_shares: u64,
+    last_signature: Option<Signature>,
 }
 
 impl InsuranceFundManager {
@@ -40,6 +36,8 @@ impl InsuranceFundManager {
             broadcaster,
             check_interval_ms: 5000, // Check every 5 seconds
             last_balance: 0,
+            last_total_shares: 0,
+            last_signature: None,
         }
     }
 
@@ -47,23 +45,19 @@ impl InsuranceFundManager {
         let mut timer = interval(Duration::from_millis(self.check_interval_ms));
         info!("Insurance Fund Manager started.");
 
-        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
-        let (insurance_fund_pda, _) = Pubkey::find_program_address(&[b"insurance_fund"], &program_id);
+        let insurance_fund_pda = pda::insurance_fund();
 
         loop {
             timer.tick().await;
             
             match self.rpc_client.get_account_data(&insurance_fund_pda) {
                 Ok(data) => {
-                    if data.len() < 8 {
-                        continue;
-                    }
-                    // Skip discriminator
-                    let account_data = &data[8..];
-                    
-                    match OnChainInsuranceFund::try_from_slice(account_data) {
+                    match OnChainInsuranceFund::decode(&data) {
                         Ok(fund) => {
                             self.check_fund_health(&fund).await;
+                            if let Err(e) = self.sync_fund_events(&insurance_fund_pda).await {
+                                error!("Failed to sync insurance fund events: {}", e);
+                            }
                         }
                         Err(e) => {
                             error!("Failed to deserialize Insurance Fund account: {}", e);
@@ -78,24 +72,108 @@ impl InsuranceFundManager {
         }
     }
 
+    /// Records fund movements from the program's events in every transaction
+    /// touching the fund since the last poll. The first poll only sets the
+    /// cursor, which then advances past each transaction once it is replayed.
+    async fn sync_fund_events(&mut self, insurance_fund_pda: &Pubkey) -> anyhow::Result<()> {
+        let Some(last_signature) = self.last_signature else {
+            let config = GetConfirmedSignaturesForAddress2Config {
+                limit: Some(1),
+                ..Default::default()
+            };
+            let newest = self.rpc_client.get_signatures_for_address_with_config(insurance_fund_pda, config)?;
+            if let Some(newest) = newest.first() {
+                self.last_signature = Some(Signature::from_str(&newest.signature)?);
+            }
+            return Ok(());
+        };
+
+        // RPC returns at most a page per call, newest first; walk back to the cursor
+        let mut signatures = Vec::new();
+        let mut before = None;
+        loop {
+            let config = GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: Some(last_signature),
+                limit: Some(SIGNATURE_PAGE_SIZE),
+                ..Default::default()
+            };
+            let page = self.rpc_client.get_signatures_for_address_with_config(insurance_fund_pda, config)?;
+            let Some(oldest) = page.last() else {
+                break;
+            };
+            before = Some(Signature::from_str(&oldest.signature)?);
+            let done = page.len() < SIGNATURE_PAGE_SIZE;
+            signatures.extend(page);
+            if done {
+                break;
+            }
+        }
+
+        // Replay in chain order
+        for status in signatures.iter().rev() {
+            let signature = Signature::from_str(&status.signature)?;
+            if status.err.is_none() {
+                let tx = self.rpc_client.get_transaction(&signature, UiTransactionEncoding::Json)?;
+                if let Some(OptionSerializer::Some(logs)) = tx.transaction.meta.map(|meta| meta.log_messages) {
+                    for event in events::parse_logs(&logs) {
+                        self.record_fund_event(&event).await?;
+                    }
+                }
+            }
+            self.last_signature = Some(signature);
+        }
+
+        Ok(())
+    }
+
+    async fn record_fund_event(&self, event: &ProgramEvent) -> anyhow::Result<()> {
+        let (amount, tx_type, reason) = match event {
+            ProgramEvent::Contribution(e) => (e.amount, "DEPOSIT", "Contribution"),
+            ProgramEvent::InsuranceWithdrawal(e) => (e.amount, "WITHDRAWAL", "Authority Withdrawal"),
+            ProgramEvent::Stake(e) => (e.amount, "DEPOSIT", "Stake"),
+            ProgramEvent::Unstake(e) => (e.amount, "WITHDRAWAL", "Unstake"),
+            ProgramEvent::Liquidation(e) if e.bad_debt > 0 => (e.bad_debt, "BAD_DEBT_COVER", "Bad Debt Cover"),
+            // Margin left after the reward and fee stays with the owner
+            ProgramEvent::Liquidation(e) => (e.insurance_fee, "DEPOSIT", "Liquidation Fee"),
+            ProgramEvent::MarginAccountLiquidation(e) if e.bad_debt > 0 => {
+                (e.bad_debt, "BAD_DEBT_COVER", "Bad Debt Cover")
+            }
+            ProgramEvent::MarginAccountLiquidation(e) => (e.insurance_fee, "DEPOSIT", "Liquidation Fee"),
+            // Balance snapshots and position lifecycle carry nothing to record here
+            ProgramEvent::InsuranceFundChanged(_)
+            | ProgramEvent::PositionOpened(_)
+            | ProgramEvent::PositionModified(_)
+            | ProgramEvent::PositionClosed(_) => return Ok(()),
+        };
+        if amount == 0 {
+            return Ok(());
+        }
+
+        info!("Insurance Fund {}: {} ({})", tx_type, amount, reason);
+        self.db.record_insurance_fund_transaction(amount as i64, tx_type, reason).await?;
+        Ok(())
+    }
+
     async fn check_fund_health(&mut self, fund: &OnChainInsuranceFund) {
+        // Fees and bad debt move the share price; stakes and unstakes only
+        // move the share count
+        if fund.balance != self.last_balance || fund.total_shares != self.last_total_shares {
+            self.last_total_shares = fund.total_shares;
+            if let Err(e) = self.db.record_insurance_share_price(
+                fund.balance as i64,
+                fund.total_shares as i64,
+                fund.share_price(),
+            ).await {
+                error!("Failed to record insurance share price: {}", e);
+            }
+        }
+
         // Check for balance changes
         if fund.balance != self.last_balance {
             let diff = fund.balance as i64 - self.last_balance as i64;
-            let reason = if diff > 0 { "Deposit/Fee" } else { "Bad Debt Cover" };
-            let tx_type = if diff > 0 { "DEPOSIT" } else { "WITHDRAWAL" };
-
             info!("Insurance Fund Balance changed: {} -> {} (Diff: {})", self.last_balance, fund.balance, diff);
 
-            // Record to DB
-            if let Err(e) = self.db.record_insurance_fund_transaction(
-                diff.abs(),
-                tx_type,
-                reason
-            ).await {
-                error!("Failed to record insurance fund transaction: {}", e);
-            }
-
             self.last_balance = fund.balance;
 
             // Broadcast update
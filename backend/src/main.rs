@@ -6,6 +6,7 @@ mod db;
 mod server;
 mod websocket;
 mod insurance;
+mod adl;
 #[cfg(test)]
 mod tests;
 
@@ -16,6 +17,7 @@ use db::Database;
 use queue::LiquidationQueue;
 use websocket::Broadcaster;
 use insurance::InsuranceFundManager;
+use adl::AdlRanking;
 use std::sync::Arc;
 
 #[tokio::main]
@@ -47,8 +49,9 @@ async fn main() -> anyhow::Result<()> {
 
     let queue = Arc::new(LiquidationQueue::new());
     let broadcaster = Arc::new(Broadcaster::new());
+    let adl_ranking = Arc::new(AdlRanking::new());
 
-    let engine = LiquidationEngine::new(db.clone(), queue.clone(), broadcaster.clone()).await?;
+    let engine = LiquidationEngine::new(db.clone(), queue.clone(), broadcaster.clone(), adl_ranking.clone()).await?;
     let mut insurance_manager = InsuranceFundManager::new(db.clone(), broadcaster.clone());
 
     // Start insurance manager in background
@@ -58,7 +61,7 @@ async fn main() -> anyhow::Result<()> {
 
     // Start server and engine concurrently
     tokio::select! {
-        result = server::start_server(db.clone(), queue.clone(), broadcaster.clone()) => {
+        result = server::start_server(db.clone(), queue.clone(), broadcaster.clone(), adl_ranking.clone()) => {
             if let Err(e) = result {
                 error!("API Server failed: {}", e);
             }
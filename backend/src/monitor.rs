@@ -1,20 +1,23 @@
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
 use std::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tokio::time::{interval, Duration};
 use log::{info, warn, error};
 use crate::oracle::PriceOracle;
 use crate::queue::LiquidationQueue;
 use crate::executor::LiquidationExecutor;
+use crate::adl::{self, AdlCandidate, AdlRanking};
 use crate::db::Database;
 use crate::websocket::{Broadcaster, WsMessage};
-use std::str::FromStr;
-use borsh::BorshDeserialize;
 use serde::Serialize;
-
-const PROGRAM_ID: &str = "HCJaVaM9GEH4nYoX6UquxWXroXJyjSLp2LLKCa1gg5NJ";
+use liquidation_engine_client::accounts::{
+    OnChainAuction, OnChainCollateralAsset, OnChainCollateralBalance, OnChainMarginAccount, OnChainMarket,
+    OnChainPosition, ProgramAccount,
+};
+use liquidation_engine_client::PROGRAM_ID;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Position {
@@ -22,21 +25,101 @@ pub struct Position {
     pub owner: Pubkey,
     pub symbol: String,
     pub size: f64,
+    /// `size` in the program's 6-decimal units, exactly as stored on-chain.
+    pub raw_size: u64,
     pub collateral: f64,
     pub entry_price: f64,
-    pub leverage: u8,
+    pub leverage: u16,
     pub is_long: bool,
+    /// Funding owed since the position last settled on-chain (negative when
+    /// the position is owed funding).
+    pub unsettled_funding: f64,
 }
 
-#[derive(BorshDeserialize, Debug)]
-pub struct OnChainPosition {
+/// Account-level margin of one owner's cross-margin account.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountHealth {
+    pub account: String,
     pub owner: Pubkey,
-    pub symbol: String,
-    pub size: u64,
-    pub collateral: u64,
-    pub entry_price: u64,
-    pub leverage: u16,
-    pub maintenance_margin: u64,
+    /// Collateral plus the weighted value of the deposits plus every
+    /// position's PnL net of unsettled funding.
+    pub equity: f64,
+    /// Deposits at `prices` less each asset's haircut.
+    pub collateral_value: f64,
+    pub maintenance_requirement: f64,
+    pub symbols: Vec<String>,
+}
+
+/// Marks every position of a margin account at `prices`, mirroring the
+/// program's `cross_margin::account_health`.
+pub fn margin_account_health(
+    account_id: &Pubkey,
+    account: &OnChainMarginAccount,
+    markets: &HashMap<String, OnChainMarket>,
+    assets: &HashMap<Pubkey, OnChainCollateralAsset>,
+    prices: &HashMap<String, f64>,
+) -> anyhow::Result<AccountHealth> {
+    let scale = 1_000_000.0;
+    let mut collateral_value = 0.0;
+    for deposit in &account.deposits {
+        let asset = assets
+            .get(&deposit.mint)
+            .ok_or_else(|| anyhow::anyhow!("No collateral asset for {}", deposit.mint))?;
+        let price = *prices
+            .get(&asset.symbol)
+            .ok_or_else(|| anyhow::anyhow!("No price for {}", asset.symbol))?;
+        collateral_value += asset.weighted_value(deposit.amount, price);
+    }
+    let mut equity = account.collateral as f64 / scale + collateral_value;
+    let mut maintenance_requirement = 0.0;
+
+    for position in &account.positions {
+        let market = markets
+            .get(&position.symbol)
+            .ok_or_else(|| anyhow::anyhow!("No market for {}", position.symbol))?;
+        let price = *prices
+            .get(&position.symbol)
+            .ok_or_else(|| anyhow::anyhow!("No price for {}", position.symbol))?;
+
+        let size = position.size as f64 / scale;
+        let entry_price = position.entry_price as f64 / scale;
+        let unrealized_pnl = if position.is_long {
+            size * (price - entry_price)
+        } else {
+            size * (entry_price - price)
+        };
+        let funding = (market.funding_index(position.is_long) - position.last_funding_index)
+            * position.size as i128
+            / 1_000_000;
+
+        equity += unrealized_pnl - funding as f64 / scale;
+        maintenance_requirement +=
+            size * price * market.maintenance_margin_bps(position.leverage) as f64 / 10_000.0;
+    }
+
+    Ok(AccountHealth {
+        account: account_id.to_string(),
+        owner: account.owner,
+        equity,
+        collateral_value,
+        maintenance_requirement,
+        symbols: account.positions.iter().map(|p| p.symbol.clone()).collect(),
+    })
+}
+
+/// Everything the monitor reads from the program in one pass.
+struct ProgramState {
+    positions: Vec<Position>,
+    /// Positions in a layout older than the program's current one, with
+    /// their symbols. They must be migrated before anything else can touch
+    /// them.
+    unmigrated_positions: Vec<(Pubkey, String)>,
+    margin_accounts: Vec<(Pubkey, OnChainMarginAccount)>,
+    markets: HashMap<String, OnChainMarket>,
+    /// Registered collateral assets by mint.
+    collateral_assets: HashMap<Pubkey, OnChainCollateralAsset>,
+    /// Open auctions by the position they liquidate.
+    auctions: HashMap<Pubkey, OnChainAuction>,
 }
 
 pub struct LiquidationEngine {
@@ -46,24 +129,29 @@ pub struct LiquidationEngine {
     queue: Arc<LiquidationQueue>,
     rpc_client: Arc<RpcClient>,
     broadcaster: Arc<Broadcaster>,
+    adl_ranking: Arc<AdlRanking>,
 }
 
 impl LiquidationEngine {
     pub async fn new(
         db: Arc<Database>, 
         queue: Arc<LiquidationQueue>,
-        broadcaster: Arc<Broadcaster>
+        broadcaster: Arc<Broadcaster>,
+        adl_ranking: Arc<AdlRanking>,
     ) -> anyhow::Result<Self> {
         let rpc_url = std::env::var("SOLANA_RPC_URL").unwrap_or_else(|_| "http://127.0.0.1:8899".to_string());
         let rpc_client = Arc::new(RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed()));
 
+        let oracle = Arc::new(PriceOracle::new());
+
         Ok(Self {
             check_interval_ms: 2000,
-            oracle: Arc::new(PriceOracle::new()),
             executor: Arc::new(LiquidationExecutor::new(db)),
+            oracle,
             queue,
             rpc_client,
             broadcaster,
+            adl_ranking,
         })
     }
 
@@ -80,23 +168,35 @@ impl LiquidationEngine {
     }
 
     async fn check_all_positions(&self) -> anyhow::Result<()> {
-        let open_positions = self.fetch_on_chain_positions().await?;
+        let state = self.fetch_program_state().await?;
+        // Baseline positions were never backed by the vault, so migrating
+        // leaves them flat for their owners to close
+        for (pubkey, symbol) in &state.unmigrated_positions {
+            if let Err(e) = self.executor.migrate_position(pubkey, symbol).await {
+                error!("Failed to migrate position {}: {:?}", pubkey, e);
+            }
+        }
+        let open_positions = &state.positions;
         
         if !open_positions.is_empty() {
             info!("Found {} open positions on-chain.", open_positions.len());
-        } else {
+        } else if state.margin_accounts.is_empty() {
              return Ok(());
         }
 
         let mut price_cache: HashMap<String, f64> = HashMap::new();
 
         for position in open_positions {
-            let mark_price = if let Some(&cached) = price_cache.get(&position.symbol) {
-                cached
-            } else {
-                let price = self.oracle.get_mark_price(&position.symbol).await?;
-                price_cache.insert(position.symbol.clone(), price);
-                price
+            let Some(market) = state.markets.get(&position.symbol) else {
+                warn!("No market account for position {} ({})", position.id, position.symbol);
+                continue;
+            };
+            let mark_price = match self.cached_price(&mut price_cache, &position.symbol).await {
+                Ok(price) => price,
+                Err(e) => {
+                    warn!("No price for position {} ({}): {:?}", position.id, position.symbol, e);
+                    continue;
+                }
             };
 
             let unrealized_pnl = if position.is_long {
@@ -111,16 +211,41 @@ impl LiquidationEngine {
                 continue;
             }
 
-            let margin_ratio = (position.collateral + unrealized_pnl) / position_value;
-            let maintenance_margin_ratio = self.get_maintenance_margin_ratio(position.leverage);
+            let margin_ratio =
+                (position.collateral + unrealized_pnl - position.unsettled_funding) / position_value;
+            let maintenance_margin_ratio = market.maintenance_margin_bps(position.leverage) as f64 / 10_000.0;
             let warning_threshold = maintenance_margin_ratio * 1.1;
-            
-            if margin_ratio < maintenance_margin_ratio {
-                info!("LIQUIDATION TRIGGERED: Position {}. Margin Ratio: {:.4} < Maintenance: {:.4}", 
+            if margin_ratio >= warning_threshold {
+                continue;
+            }
+
+            // Near the threshold the program's view at its own oracle price
+            // decides, whichever side of it the off-chain estimate falls
+            let off_chain_liquidatable = margin_ratio < maintenance_margin_ratio;
+            let is_liquidatable = match self.executor.simulate_position_health(position, market) {
+                Ok(health) => {
+                    if health.is_liquidatable != off_chain_liquidatable {
+                        warn!("Position {} off-chain margin ratio {:.4} disagrees with on-chain ({} bps vs {} bps maintenance, liquidation price {})",
+                            position.id, margin_ratio, health.margin_ratio_bps, health.maintenance_margin_bps, health.liquidation_price);
+                    }
+                    health.is_liquidatable
+                }
+                Err(e) => {
+                    warn!("Health simulation failed for position {}: {:?}", position.id, e);
+                    off_chain_liquidatable
+                }
+            };
+
+            if is_liquidatable {
+                info!("LIQUIDATION TRIGGERED: Position {}. Margin Ratio: {:.4}, Maintenance: {:.4}", 
                     position.id, margin_ratio, maintenance_margin_ratio);
                 
                 self.queue.push(position.clone(), margin_ratio).await;
-                self.executor.liquidate_position(&position, mark_price, margin_ratio).await?;
+                let auction = Pubkey::from_str(&position.id).ok().and_then(|pubkey| state.auctions.get(&pubkey));
+                if let Err(e) = self.executor.liquidate_position(position, market, auction, mark_price, margin_ratio).await {
+                    error!("Failed to liquidate position {}: {:?}", position.id, e);
+                    continue;
+                }
 
                 self.broadcaster.send(WsMessage::LiquidationEvent {
                     position_id: position.id.clone(),
@@ -128,18 +253,154 @@ impl LiquidationEngine {
                     amount: position.size,
                     price: mark_price,
                 });
-            } else if margin_ratio < warning_threshold {
+            } else {
                 warn!("RISK ALERT: Position {} is approaching liquidation. Margin: {:.4}, Threshold: {:.4}", 
                     position.id, margin_ratio, maintenance_margin_ratio);
             }
         }
+
+        self.check_margin_accounts(&state, &mut price_cache).await;
+
+        let ranking = adl::rank_candidates(&state.positions, &state.markets, &price_cache);
+        self.adl_ranking.update(ranking.clone());
+        self.auto_deleverage(&ranking, &state.markets).await;
+        Ok(())
+    }
+
+    /// Deleverages the top-ranked position of every market with uncovered
+    /// bad debt; the rest follow on later passes if debt remains.
+    async fn auto_deleverage(
+        &self,
+        ranking: &[AdlCandidate],
+        markets: &HashMap<String, OnChainMarket>,
+    ) {
+        let mut handled = HashSet::new();
+        for candidate in ranking {
+            if candidate.deleverage_size <= 0.0 || !handled.insert(candidate.symbol.as_str()) {
+                continue;
+            }
+            let Some(market) = markets.get(&candidate.symbol) else {
+                continue;
+            };
+            info!("ADL TRIGGERED: {} bad debt {} recovered from position {} (score {:.4})",
+                candidate.symbol, market.uncovered_bad_debt, candidate.position_id, candidate.score);
+            if let Err(e) = self.executor.auto_deleverage(candidate, market).await {
+                error!("Failed to deleverage position {}: {:?}", candidate.position_id, e);
+            }
+        }
+    }
+
+    /// Cross-margin accounts are judged as a whole: one owner's equity
+    /// against the sum of their positions' maintenance requirements.
+    /// Collateral deposits are liquidated first, the most valuable one per
+    /// pass; positions are closed once only quote collateral is left.
+    async fn check_margin_accounts(&self, state: &ProgramState, price_cache: &mut HashMap<String, f64>) {
+        for (pubkey, account) in &state.margin_accounts {
+            if account.positions.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.check_margin_account(pubkey, account, state, price_cache).await {
+                error!("Failed to check margin account {}: {:?}", pubkey, e);
+            }
+        }
+    }
+
+    /// Checks and, if needed, liquidates one margin account. Errors only
+    /// skip this account; the rest of the pass carries on.
+    async fn check_margin_account(
+        &self,
+        pubkey: &Pubkey,
+        account: &OnChainMarginAccount,
+        state: &ProgramState,
+        price_cache: &mut HashMap<String, f64>,
+    ) -> anyhow::Result<()> {
+        let (markets, assets) = (&state.markets, &state.collateral_assets);
+        for position in &account.positions {
+            self.cached_price(price_cache, &position.symbol).await?;
+        }
+        for deposit in &account.deposits {
+            if let Some(asset) = assets.get(&deposit.mint) {
+                self.cached_price(price_cache, &asset.symbol).await?;
+            }
+        }
+
+        let health = margin_account_health(pubkey, account, markets, assets, price_cache)?;
+        let warning_threshold = health.maintenance_requirement * 1.1;
+
+        if health.equity < health.maintenance_requirement {
+            info!("ACCOUNT LIQUIDATION TRIGGERED: Owner {} ({}). Equity: {:.4} < Maintenance: {:.4}",
+                health.owner, health.account, health.equity, health.maintenance_requirement);
+
+            let largest_deposit = account.deposits.iter().max_by(|a, b| {
+                let value = |deposit: &OnChainCollateralBalance| {
+                    assets
+                        .get(&deposit.mint)
+                        .map(|asset| asset.weighted_value(deposit.amount, price_cache[&asset.symbol]))
+                        .unwrap_or(0.0)
+                };
+                value(a).partial_cmp(&value(b)).unwrap_or(std::cmp::Ordering::Equal)
+            });
+            match largest_deposit {
+                Some(deposit) => {
+                    self.executor.liquidate_collateral(pubkey, account, markets, assets, deposit).await?;
+                }
+                None => self.executor.liquidate_margin_account(pubkey, account, markets, assets).await?,
+            }
+
+            self.broadcaster.send(WsMessage::AccountLiquidationEvent {
+                account: health.account.clone(),
+                owner: health.owner.to_string(),
+                equity: health.equity,
+                maintenance_requirement: health.maintenance_requirement,
+            });
+        } else if health.equity < warning_threshold {
+            warn!("RISK ALERT: Owner {} margin account is approaching liquidation. Equity: {:.4}, Threshold: {:.4}",
+                health.owner, health.equity, warning_threshold);
+        }
         Ok(())
     }
 
-    async fn fetch_on_chain_positions(&self) -> anyhow::Result<Vec<Position>> {
-        let program_id = Pubkey::from_str(PROGRAM_ID)?;
-        let accounts = self.rpc_client.get_program_accounts(&program_id)?;
+    async fn cached_price(&self, price_cache: &mut HashMap<String, f64>, symbol: &str) -> anyhow::Result<f64> {
+        if let Some(&cached) = price_cache.get(symbol) {
+            return Ok(cached);
+        }
+        let price = self.oracle.get_mark_price(symbol).await?;
+        price_cache.insert(symbol.to_string(), price);
+        Ok(price)
+    }
+
+    async fn fetch_program_state(&self) -> anyhow::Result<ProgramState> {
+        let accounts = self.rpc_client.get_program_accounts(&PROGRAM_ID)?;
         let mut positions = Vec::new();
+        let mut unmigrated_positions = Vec::new();
+        let mut margin_accounts = Vec::new();
+        let mut auctions = HashMap::new();
+        // The program also owns config, insurance fund and stake accounts
+
+        // Markets first: positions need their funding index
+        let mut markets: HashMap<String, OnChainMarket> = HashMap::new();
+        for (pubkey, account) in &accounts {
+            if OnChainMarket::matches(&account.data) {
+                match OnChainMarket::decode(&account.data) {
+                    Ok(market) => {
+                        markets.insert(market.symbol.clone(), market);
+                    }
+                    Err(e) => warn!("Failed to decode market {}: {:?}", pubkey, e),
+                }
+            }
+        }
+
+        let mut collateral_assets = HashMap::new();
+        for (pubkey, account) in &accounts {
+            if OnChainCollateralAsset::matches(&account.data) {
+                match OnChainCollateralAsset::decode(&account.data) {
+                    Ok(asset) => {
+                        collateral_assets.insert(asset.mint, asset);
+                    }
+                    Err(e) => warn!("Failed to decode collateral asset {}: {:?}", pubkey, e),
+                }
+            }
+        }
 
         for (pubkey, account) in accounts {
             if account.data.len() < 8 {
@@ -147,37 +408,61 @@ impl LiquidationEngine {
                 continue;
             }
 
-            if pubkey.to_string() == "9K3sRRPZRxfP8ECxkW7mPZ3GiMLbW1PWU2HuBn8seCHS" {
+            if OnChainAuction::matches(&account.data) {
+                match OnChainAuction::decode(&account.data) {
+                    Ok(auction) => {
+                        auctions.insert(auction.position, auction);
+                    }
+                    Err(e) => warn!("Failed to decode auction {}: {:?}", pubkey, e),
+                }
                 continue;
             }
 
-            let data_slice = &account.data[8..];
-            
-            if let Ok(on_chain_pos) = OnChainPosition::try_from_slice(data_slice) {
+            if OnChainMarginAccount::matches(&account.data) {
+                match OnChainMarginAccount::decode(&account.data) {
+                    Ok(margin_account) => margin_accounts.push((pubkey, margin_account)),
+                    Err(e) => warn!("Failed to decode margin account {}: {:?}", pubkey, e),
+                }
+                continue;
+            }
+
+            if !OnChainPosition::matches(&account.data) {
+                continue;
+            }
+
+            if let Ok(on_chain_pos) = OnChainPosition::decode(&account.data) {
+                if on_chain_pos.last_funding_index.is_none() {
+                    unmigrated_positions.push((pubkey, on_chain_pos.symbol));
+                    continue;
+                }
                 let scale = 1_000_000.0;
+                let unsettled_funding = markets
+                    .get(&on_chain_pos.symbol)
+                    .map(|market| on_chain_pos.unsettled_funding(market))
+                    .unwrap_or(0);
                 
                 positions.push(Position {
                     id: pubkey.to_string(),
                     owner: on_chain_pos.owner,
                     symbol: on_chain_pos.symbol,
                     size: on_chain_pos.size as f64 / scale,
+                    raw_size: on_chain_pos.size,
                     collateral: on_chain_pos.collateral as f64 / scale,
                     entry_price: on_chain_pos.entry_price as f64 / scale,
-                    leverage: on_chain_pos.leverage as u8,
-                    is_long: true,
+                    leverage: on_chain_pos.leverage,
+                    is_long: on_chain_pos.is_long,
+                    unsettled_funding: unsettled_funding as f64 / scale,
                 });
             }
         }
 
-        Ok(positions)
-    }
-
-    fn get_maintenance_margin_ratio(&self, leverage: u8) -> f64 {
-        match leverage {
-            1..=20 => 0.025,
-            21..=50 => 0.01,
-            51..=100 => 0.005,
-            _ => 0.025,
-        }
+        Ok(ProgramState {
+            positions,
+            unmigrated_positions,
+            margin_accounts,
+            markets,
+            collateral_assets,
+            auctions,
+        })
     }
 }
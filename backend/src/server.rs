@@ -3,10 +3,12 @@ use actix_cors::Cors;
 use std::sync::Arc;
 use crate::db::Database;
 use crate::queue::LiquidationQueue;
+use crate::adl::AdlRanking;
 
 pub struct AppState {
     pub db: Arc<Database>,
     pub queue: Arc<LiquidationQueue>,
+    pub adl: Arc<AdlRanking>,
 }
 
 async fn get_health() -> impl Responder {
@@ -34,6 +36,13 @@ async fn get_insurance_fund_history(data: web::Data<AppState>) -> impl Responder
     }
 }
 
+async fn get_insurance_share_price_history(data: web::Data<AppState>) -> impl Responder {
+    match data.db.get_insurance_share_price_history(100).await {
+        Ok(history) => HttpResponse::Ok().json(history),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": e.to_string() })),
+    }
+}
+
 // Mock endpoint for pending liquidations since Queue doesn't expose list yet
 async fn get_pending_liquidations(data: web::Data<AppState>) -> impl Responder {
     let snapshot = data.queue.get_snapshot().await;
@@ -43,6 +52,15 @@ async fn get_pending_liquidations(data: web::Data<AppState>) -> impl Responder {
     }))
 }
 
+/// Positions next in line for auto-deleveraging, highest rank first.
+async fn get_adl_ranking(data: web::Data<AppState>) -> impl Responder {
+    let candidates = data.adl.snapshot();
+    HttpResponse::Ok().json(serde_json::json!({
+        "candidate_count": candidates.len(),
+        "candidates": candidates
+    }))
+}
+
 use crate::websocket::{self, Broadcaster};
 
 // ... imports ...
@@ -50,11 +68,13 @@ use crate::websocket::{self, Broadcaster};
 pub async fn start_server(
     db: Arc<Database>, 
     queue: Arc<LiquidationQueue>,
-    broadcaster: Arc<Broadcaster>
+    broadcaster: Arc<Broadcaster>,
+    adl: Arc<AdlRanking>,
 ) -> std::io::Result<()> {
     let app_state = web::Data::new(AppState {
         db,
         queue,
+        adl,
     });
     let broadcaster_data = web::Data::new(broadcaster);
 
@@ -68,6 +88,8 @@ pub async fn start_server(
             .route("/liquidations/stats", web::get().to(get_liquidation_stats))
             .route("/liquidations/pending", web::get().to(get_pending_liquidations))
             .route("/insurance-fund/history", web::get().to(get_insurance_fund_history))
+            .route("/insurance-fund/share-price", web::get().to(get_insurance_share_price_history))
+            .route("/adl/ranking", web::get().to(get_adl_ranking))
             .route("/ws", web::get().to(websocket::ws_handler))
     })
     .bind(("0.0.0.0", 8080))?
@@ -5,25 +5,56 @@ mod tests {
 
     #[test]
     fn test_maintenance_margin_tiers() {
-        // We can't easily test async methods in a simple unit test without a runtime,
-        // but we can test the logic helper functions if we expose them or move them to a util.
-        // For now, let's simulate the logic here.
-        
-        fn get_maintenance_margin_ratio(leverage: u16) -> f64 {
-            match leverage {
-                1..=20 => 0.025,
-                21..=50 => 0.01,
-                51..=100 => 0.005,
-                101..=500 => 0.0025,
-                501..=1000 => 0.001,
-                _ => 0.025,
-            }
-        }
+        // Tiers come from the market account, as on-chain
+        use liquidation_engine_client::accounts::{OnChainMarginTier, OnChainMarket};
 
-        assert_eq!(get_maintenance_margin_ratio(10), 0.025);
-        assert_eq!(get_maintenance_margin_ratio(50), 0.01);
-        assert_eq!(get_maintenance_margin_ratio(100), 0.005);
-        assert_eq!(get_maintenance_margin_ratio(1000), 0.001);
+        let tier = |max_leverage, maintenance_margin_bps| OnChainMarginTier { max_leverage, maintenance_margin_bps };
+        let market = OnChainMarket {
+            margin_tiers: [tier(20, 250), tier(50, 100), tier(100, 50), tier(1000, 10)],
+            ..Default::default()
+        };
+
+        assert_eq!(market.maintenance_margin_bps(10), 250);
+        assert_eq!(market.maintenance_margin_bps(50), 100);
+        assert_eq!(market.maintenance_margin_bps(100), 50);
+        assert_eq!(market.maintenance_margin_bps(1000), 10);
+        // Beyond the last tier, the last tier applies
+        assert_eq!(market.maintenance_margin_bps(2000), 10);
+    }
+
+    #[test]
+    fn test_partial_liquidation_size_matches_program() {
+        use liquidation_engine_client::accounts::{OnChainMarginTier, OnChainMarket};
+
+        // The program's own fixture: 100 units long from $100 on 20x with
+        // $500 collateral, a 5% target margin and a 1% fee
+        let mut market = OnChainMarket {
+            margin_tiers: [OnChainMarginTier { max_leverage: 20, maintenance_margin_bps: 250 }; 4],
+            liquidation_fee_bps: 100,
+            liquidation_fee_max_bps: 100,
+            close_factor_bps: 5_000,
+            target_margin_bps: 500,
+            ..Default::default()
+        };
+        let (size, collateral, entry_price) = (100_000_000u64, 500_000_000i128, 100_000_000i128);
+        let margin = |price: u64| collateral + (price as i128 - entry_price) * size as i128 / 1_000_000;
+
+        // Exactly the program's cap at $99, where the ceil rounding matters
+        assert_eq!(market.partial_liquidation_size(size, margin(99_000_000), 99_000_000, 20), Some(23_989_899));
+        // Already at target: nothing to close
+        assert_eq!(market.partial_liquidation_size(size, margin(100_000_000), 100_000_000, 20), Some(0));
+        // Beyond the close factor at $97, bankrupt at $94
+        assert_eq!(market.partial_liquidation_size(size, margin(97_000_000), 97_000_000, 20), None);
+        assert_eq!(market.partial_liquidation_size(size, margin(94_000_000), 94_000_000, 20), None);
+        // A fee at the target margin means no partial ever restores it
+        market.liquidation_fee_bps = 500;
+        market.liquidation_fee_max_bps = 500;
+        assert_eq!(market.partial_liquidation_size(size, margin(99_000_000), 99_000_000, 20), None);
+        // Would leave less than the minimum size behind
+        market.liquidation_fee_bps = 100;
+        market.liquidation_fee_max_bps = 100;
+        market.min_position_size = 80_000_000;
+        assert_eq!(market.partial_liquidation_size(size, margin(99_000_000), 99_000_000, 20), None);
     }
 
     #[test]
@@ -51,4 +82,371 @@ mod tests {
         // Maintenance for 10x is 0.025
         assert!(margin_ratio_drop < 0.025); // Should liquidate
     }
+
+    #[test]
+    fn test_decode_contribution_event() {
+        use liquidation_engine_client::events::{parse_logs, ProgramEvent};
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+        let mut data = solana_sdk::hash::hash(b"event:ContributionEvent").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&[7u8; 32]); // contributor
+        data.extend_from_slice(&5_000_000u64.to_le_bytes()); // amount
+        data.extend_from_slice(&15_000_000u64.to_le_bytes()); // new_balance
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes()); // timestamp
+
+        let logs = vec![
+            "Program log: Instruction: ContributeToInsurance".to_string(),
+            format!("Program data: {}", STANDARD.encode(&data)),
+        ];
+
+        let events = parse_logs(&logs);
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            ProgramEvent::Contribution(e) => {
+                assert_eq!(e.amount, 5_000_000);
+                assert_eq!(e.new_balance, 15_000_000);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_position_modified_event() {
+        use liquidation_engine_client::events::{decode_event, ProgramEvent};
+
+        let mut data = solana_sdk::hash::hash(b"event:PositionModified").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&[1u8; 32]); // position
+        data.extend_from_slice(&[2u8; 32]); // owner
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"SOL");
+        data.push(1); // is_long
+        data.extend_from_slice(&50_000_000u64.to_le_bytes()); // size
+        data.extend_from_slice(&900_000_000u64.to_le_bytes()); // collateral
+        data.extend_from_slice(&25_000_000u64.to_le_bytes()); // maintenance_margin
+        data.extend_from_slice(&(-42i128).to_le_bytes()); // last_funding_index
+        data.extend_from_slice(&50_000_000u64.to_le_bytes()); // size_reduced
+        data.extend_from_slice(&(-100_000_000i64).to_le_bytes()); // collateral_change
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes()); // timestamp
+
+        match decode_event(&data) {
+            Some(ProgramEvent::PositionModified(e)) => {
+                assert_eq!(e.symbol, "SOL");
+                assert_eq!(e.size, 50_000_000);
+                assert_eq!(e.last_funding_index, -42);
+                assert_eq!(e.collateral_change, -100_000_000);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_adl_ranks_profitable_opposing_positions() {
+        use crate::adl::rank_candidates;
+        use crate::monitor::Position;
+        use liquidation_engine_client::accounts::OnChainMarket;
+        use solana_sdk::pubkey::Pubkey;
+        use std::collections::HashMap;
+
+        fn position(id: &str, is_long: bool, collateral: f64, entry_price: f64) -> Position {
+            Position {
+                id: id.to_string(),
+                owner: Pubkey::default(),
+                symbol: "SOL".to_string(),
+                size: 10.0,
+                raw_size: 10_000_000,
+                collateral,
+                entry_price,
+                leverage: 10,
+                is_long,
+                unsettled_funding: 0.0,
+            }
+        }
+
+        // A long went bankrupt at $18, leaving $5 of bad debt; SOL is now $17
+        let mut markets = HashMap::new();
+        markets.insert("SOL".to_string(), OnChainMarket {
+            symbol: "SOL".to_string(),
+            uncovered_bad_debt: 5_000_000,
+            bankruptcy_price: 18_000_000,
+            bankrupt_is_long: true,
+            ..Default::default()
+        });
+        let prices = HashMap::from([("SOL".to_string(), 17.0)]);
+
+        let positions = vec![
+            position("low-leverage-short", false, 100.0, 20.0), // +30 on 100
+            position("high-leverage-short", false, 20.0, 20.0), // +30 on 20
+            position("losing-short", false, 20.0, 16.0),
+            position("long", true, 20.0, 10.0),
+        ];
+
+        let ranking = rank_candidates(&positions, &markets, &prices);
+        let ids: Vec<&str> = ranking.iter().map(|c| c.position_id.as_str()).collect();
+        assert_eq!(ids, vec!["high-leverage-short", "low-leverage-short"]);
+        // $1 given up per unit closed at $18 instead of $17
+        assert!((ranking[0].deleverage_size - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_adl_skips_positions_with_nothing_to_give_up() {
+        use crate::adl::rank_candidates;
+        use crate::monitor::Position;
+        use liquidation_engine_client::accounts::OnChainMarket;
+        use solana_sdk::pubkey::Pubkey;
+        use std::collections::HashMap;
+
+        // A long went bankrupt at $18, but SOL has since risen to $19
+        let mut markets = HashMap::new();
+        markets.insert("SOL".to_string(), OnChainMarket {
+            symbol: "SOL".to_string(),
+            uncovered_bad_debt: 5_000_000,
+            bankruptcy_price: 18_000_000,
+            bankrupt_is_long: true,
+            ..Default::default()
+        });
+        let prices = HashMap::from([("SOL".to_string(), 19.0)]);
+
+        // Still in profit, but closing at $18 would pay the short more
+        let positions = vec![Position {
+            id: "profitable-short".to_string(),
+            owner: Pubkey::default(),
+            symbol: "SOL".to_string(),
+            size: 10.0,
+            raw_size: 10_000_000,
+            collateral: 20.0,
+            entry_price: 20.0,
+            leverage: 10,
+            is_long: false,
+            unsettled_funding: 0.0,
+        }];
+
+        assert!(rank_candidates(&positions, &markets, &prices).is_empty());
+    }
+
+    #[test]
+    fn test_liquidation_fee_scales_with_shortfall() {
+        use liquidation_engine_client::accounts::{OnChainMarginTier, OnChainMarket};
+
+        let market = OnChainMarket {
+            margin_tiers: [OnChainMarginTier { max_leverage: 20, maintenance_margin_bps: 500 }; 4],
+            liquidation_fee_bps: 100,
+            liquidation_fee_max_bps: 500,
+            liquidation_fee_ramp_bps: 400,
+            ..Default::default()
+        };
+
+        // Floor at maintenance, halfway 200 bps below it, capped from 400 below
+        assert_eq!(market.liquidation_fee_bps(500, 10), 100);
+        assert_eq!(market.liquidation_fee_bps(300, 10), 300);
+        assert_eq!(market.liquidation_fee_bps(100, 10), 500);
+        assert_eq!(market.liquidation_fee_bps(-2_000, 10), 500);
+    }
+
+    #[test]
+    fn test_decode_position_layouts() {
+        use liquidation_engine_client::accounts::{OnChainPosition, ProgramAccount};
+
+        // Baseline: no direction, sized exactly to the symbol
+        let mut baseline = vec![3u8; 32]; // owner
+        baseline.extend_from_slice(&3u32.to_le_bytes());
+        baseline.extend_from_slice(b"SOL");
+        baseline.extend_from_slice(&100_000_000u64.to_le_bytes()); // size
+        baseline.extend_from_slice(&1_000_000_000u64.to_le_bytes()); // collateral
+        baseline.extend_from_slice(&20_000_000u64.to_le_bytes()); // entry_price
+        baseline.extend_from_slice(&10u16.to_le_bytes()); // leverage
+        baseline.extend_from_slice(&50_000_000u64.to_le_bytes()); // maintenance_margin
+        assert_eq!(baseline.len(), 32 + 4 + 3 + 34);
+
+        // Current: leading version byte, is_long after the symbol, symbol
+        // padded to 32 bytes, then the funding index
+        let mut funded = vec![2u8];
+        funded.extend_from_slice(&baseline[..32 + 4 + 3]);
+        funded.push(0); // is_long
+        funded.extend_from_slice(&baseline[32 + 4 + 3..]);
+        funded.extend_from_slice(&(-1_500i128).to_le_bytes());
+        funded.resize(1 + 32 + 4 + 32 + 1 + 8 + 8 + 8 + 2 + 8 + 16, 0);
+
+        // Baseline positions had no side and are reported as long
+        let position = OnChainPosition::decode_body(&baseline).unwrap();
+        assert!(position.is_long);
+        assert_eq!(position.last_funding_index, None);
+
+        let position = OnChainPosition::decode_body(&funded).unwrap();
+        assert!(!position.is_long);
+        assert_eq!(position.last_funding_index, Some(-1_500));
+
+        for data in [&baseline, &funded] {
+            let position = OnChainPosition::decode_body(data).unwrap();
+            assert_eq!(position.symbol, "SOL");
+            assert_eq!(position.collateral, 1_000_000_000);
+            assert_eq!(position.maintenance_margin, 50_000_000);
+        }
+
+        funded[0] = 1;
+        assert!(OnChainPosition::decode_body(&funded).is_err());
+    }
+
+    #[test]
+    fn test_margin_account_counts_weighted_deposits() {
+        use crate::monitor::margin_account_health;
+        use liquidation_engine_client::accounts::{OnChainCollateralAsset, OnChainMarginAccount, ProgramAccount};
+        use solana_sdk::pubkey::Pubkey;
+        use std::collections::HashMap;
+
+        let mint = Pubkey::new_unique();
+        let mut data = vec![5u8; 32]; // owner
+        data.extend_from_slice(&50_000_000u64.to_le_bytes()); // $50 collateral
+        data.extend_from_slice(&0u32.to_le_bytes()); // no positions
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(mint.as_ref());
+        data.extend_from_slice(&2_000_000_000u64.to_le_bytes()); // 2 tokens at 9 decimals
+        data.resize(data.len() + 64, 0); // padding up to the account's max size
+        let account = OnChainMarginAccount::decode_body(&data).unwrap();
+        assert_eq!(account.deposits.len(), 1);
+
+        let asset = OnChainCollateralAsset {
+            mint,
+            symbol: "SOL/USD".to_string(),
+            decimals: 9,
+            oracle: Pubkey::default(),
+            oracle_program: Pubkey::default(),
+            max_staleness_slots: 25,
+            max_confidence_bps: 200,
+            weight_bps: 8_000,
+            liquidation_discount_bps: 500,
+        };
+        let assets = HashMap::from([(mint, asset)]);
+        let prices = HashMap::from([("SOL/USD".to_string(), 100.0)]);
+
+        let health =
+            margin_account_health(&Pubkey::new_unique(), &account, &HashMap::new(), &assets, &prices).unwrap();
+        // 80% of $200 on top of the quote collateral
+        assert!((health.collateral_value - 160.0).abs() < 1e-9);
+        assert!((health.equity - 210.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_margin_account_health_aggregates_positions() {
+        use crate::monitor::margin_account_health;
+        use liquidation_engine_client::accounts::{
+            LiquidationMode, OnChainCrossPosition, OnChainMarginAccount, OnChainMarginTier, OnChainMarket,
+        };
+        use solana_sdk::pubkey::Pubkey;
+        use std::collections::HashMap;
+
+        fn market(symbol: &str) -> OnChainMarket {
+            OnChainMarket {
+                symbol: symbol.to_string(),
+                oracle: Pubkey::default(),
+                oracle_program: Pubkey::default(),
+                max_staleness_slots: 25,
+                max_confidence_bps: 200,
+                max_leverage: 20,
+                margin_tiers: [OnChainMarginTier { max_leverage: 20, maintenance_margin_bps: 500 }; 4],
+                liquidation_fee_bps: 250,
+                liquidation_fee_max_bps: 250,
+                liquidation_fee_ramp_bps: 100,
+                insurance_fund_share_bps: 0,
+                close_factor_bps: 5_000,
+                target_margin_bps: 1_000,
+                min_position_size: 0,
+                liquidation_mode: LiquidationMode::Direct,
+                auction_start_discount_bps: 0,
+                auction_max_discount_bps: 0,
+                auction_duration_slots: 0,
+                max_funding_rate_bps: 0,
+                min_collateral: 0,
+                max_position_size: 0,
+                max_open_interest: 0,
+                opening_enabled: true,
+                long_funding_index: 0,
+                short_funding_index: 0,
+                last_funding_update: 0,
+                open_interest_long: 0,
+                open_interest_short: 0,
+                uncovered_bad_debt: 0,
+                bankruptcy_price: 0,
+                bankrupt_is_long: false,
+            }
+        }
+        fn position(symbol: &str, is_long: bool, entry_price: u64) -> OnChainCrossPosition {
+            OnChainCrossPosition {
+                symbol: symbol.to_string(),
+                is_long,
+                size: 10_000_000, // 10 units
+                entry_price,
+                leverage: 10,
+                last_funding_index: 0,
+            }
+        }
+
+        let account = OnChainMarginAccount {
+            owner: Pubkey::new_unique(),
+            collateral: 100_000_000, // $100
+            positions: vec![position("SOL", true, 100_000_000), position("ETH", false, 100_000_000)],
+            deposits: Vec::new(),
+        };
+        let markets: HashMap<_, _> = [("SOL", market("SOL")), ("ETH", market("ETH"))]
+            .into_iter()
+            .map(|(symbol, market)| (symbol.to_string(), market))
+            .collect();
+        // SOL long loses $50, ETH short gains $30
+        let prices: HashMap<_, _> = [("SOL".to_string(), 95.0), ("ETH".to_string(), 97.0)].into();
+
+        let health = margin_account_health(&Pubkey::new_unique(), &account, &markets, &HashMap::new(), &prices).unwrap();
+        assert!((health.equity - 80.0).abs() < 1e-9);
+        // 5% of $950 + 5% of $970
+        assert!((health.maintenance_requirement - 96.0).abs() < 1e-9);
+        assert!(health.equity < health.maintenance_requirement);
+    }
+
+    #[test]
+    fn test_client_decoders_check_discriminators() {
+        use liquidation_engine_client::accounts::{OnChainMarginAccount, OnChainMarket, ProgramAccount};
+
+        let mut body = vec![5u8; 32]; // owner
+        body.extend_from_slice(&50_000_000u64.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes()); // no positions
+        body.extend_from_slice(&0u32.to_le_bytes()); // no deposits
+
+        let mut data = OnChainMarginAccount::discriminator().to_vec();
+        data.extend_from_slice(&body);
+        assert!(OnChainMarginAccount::matches(&data));
+        assert_eq!(OnChainMarginAccount::decode(&data).unwrap().collateral, 50_000_000);
+
+        // The same bytes under another account's discriminator are rejected
+        let mut market_data = OnChainMarket::discriminator().to_vec();
+        market_data.extend_from_slice(&body);
+        assert!(!OnChainMarginAccount::matches(&market_data));
+        assert!(OnChainMarginAccount::decode(&market_data).is_err());
+        assert!(OnChainMarginAccount::decode(&[]).is_err());
+    }
+
+    #[test]
+    fn test_client_builds_liquidation_instructions() {
+        use liquidation_engine_client::{instructions, pda, PROGRAM_ID, TOKEN_PROGRAM_ID};
+        use solana_sdk::pubkey::Pubkey;
+
+        let liquidator = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let position = pda::position(&owner, "SOL", true);
+        let oracle = Pubkey::new_unique();
+        let token_account = pda::associated_token_address(&liquidator, &Pubkey::new_unique());
+
+        let ix = instructions::liquidate_full(&liquidator, &position, "SOL", &oracle, &token_account);
+        assert_eq!(ix.program_id, PROGRAM_ID);
+        assert_eq!(ix.data, vec![184, 122, 137, 225, 224, 51, 220, 170]);
+        assert_eq!(ix.accounts.len(), 11);
+        assert_eq!(ix.accounts[1].pubkey, pda::market("SOL"));
+        assert!(ix.accounts[1].is_writable);
+        assert_eq!(ix.accounts[5].pubkey, pda::vault("SOL"));
+        assert!(ix.accounts[9].is_signer && ix.accounts[9].pubkey == liquidator);
+        assert_eq!(ix.accounts[10].pubkey, TOKEN_PROGRAM_ID);
+
+        // Arguments follow the discriminator, Borsh-encoded
+        let ix = instructions::liquidate_partial(&liquidator, &position, "SOL", &oracle, &token_account, 7);
+        assert_eq!(&ix.data[..8], &liquidation_engine_client::discriminator("global", "liquidate_partial"));
+        assert_eq!(&ix.data[8..], &7u64.to_le_bytes());
+        assert_ne!(pda::position(&owner, "SOL", false), position);
+    }
 }
@@ -14,6 +14,9 @@ pub enum WsMessage {
     
     #[serde(rename = "liquidation")]
     LiquidationEvent { position_id: String, symbol: String, amount: f64, price: f64 },
+
+    #[serde(rename = "account_liquidation")]
+    AccountLiquidationEvent { account: String, owner: String, equity: f64, maintenance_requirement: f64 },
     
     #[serde(rename = "insurance_fund")]
     InsuranceFundUpdate { balance: u64 },